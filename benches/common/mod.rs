@@ -0,0 +1,31 @@
+// Stores of a given size for the benchmarks, each in a temporary HOME of its own
+
+use pin::{PinAlias, Store};
+use tempfile::TempDir;
+
+pub const SIZES: [usize; 4] = [100, 1_000, 10_000, 100_000];
+
+// Point pin at a fresh directory and fill its store with n pins, returning an alias halfway
+// through it. The store lasts as long as the directory
+pub fn store_of(n: usize) -> (TempDir, String) {
+    let dir = tempfile::tempdir().unwrap();
+    // SAFETY: the benchmarks run one at a time, with nothing else reading the environment
+    unsafe { std::env::set_var("PIN_STORE", dir.path()) };
+
+    let mut store = Store::lock().unwrap();
+    for i in 0..n {
+        store.add(format!("project-{i:06}"), record(i));
+    }
+    store.save().unwrap();
+
+    (dir, format!("project-{:06}", n / 2))
+}
+
+// A pin as imported from another tool, without a directory behind it
+pub fn record(i: usize) -> PinAlias {
+    PinAlias {
+        path: format!("/home/me/code/project-{i}"),
+        note: "imported".to_string(),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,29 @@
+// How long a jump takes to find its pin as the store grows
+//
+// `cargo bench --bench jump` compares Store::find, which jumping uses, with reading the whole
+// store first as jumps used to.
+
+mod common;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use pin::Store;
+
+fn jump(c: &mut Criterion) {
+    let mut group = c.benchmark_group("jump");
+    group.sample_size(20);
+
+    for n in common::SIZES {
+        let (_dir, alias) = common::store_of(n);
+
+        group.bench_with_input(BenchmarkId::new("find", n), &alias, |b, alias| {
+            b.iter(|| Store::find(alias).unwrap().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("whole store", n), &alias, |b, alias| {
+            b.iter(|| Store::init().unwrap().get(alias).unwrap().path.clone())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, jump);
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+// Reading, searching and writing the store as it grows
+//
+// `cargo bench --bench store` times Store::init, looking pins up in a store that has been read,
+// and saving it, for stores of 100 to 100,000 pins.
+
+mod common;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use pin::Store;
+
+fn init(c: &mut Criterion) {
+    let mut group = c.benchmark_group("init");
+    group.sample_size(20);
+
+    for n in common::SIZES {
+        let (_dir, _) = common::store_of(n);
+        group.bench_function(BenchmarkId::from_parameter(n), |b| {
+            b.iter(|| Store::init().unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+
+    for n in common::SIZES {
+        let (_dir, alias) = common::store_of(n);
+        let store = Store::init().unwrap();
+
+        group.bench_with_input(BenchmarkId::new("get", n), &alias, |b, alias| {
+            b.iter(|| store.get(alias).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("search", n), &alias, |b, alias| {
+            b.iter(|| store.search(alias).len())
+        });
+    }
+    group.finish();
+}
+
+fn save(c: &mut Criterion) {
+    let mut group = c.benchmark_group("save");
+    group.sample_size(10);
+
+    for n in common::SIZES {
+        let (_dir, _) = common::store_of(n);
+
+        // Each save changes one pin in a store that has been read, as most commands do
+        group.bench_function(BenchmarkId::from_parameter(n), |b| {
+            b.iter_batched(
+                || {
+                    let mut store = Store::lock().unwrap();
+                    store.add("changed".to_string(), common::record(n));
+                    store
+                },
+                |store| store.save().unwrap(),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, init, lookup, save);
+criterion_main!(benches);
@@ -0,0 +1,161 @@
+// Build script
+//
+// Renders the pin(1) man page and the markdown command reference from the help in src/help.rs,
+// so they can't drift from `pin --help`. Both are written to OUT_DIR and printed by
+// `_pin --help --man` and `_pin --help --markdown`.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "src/help.rs"]
+mod help;
+
+use help::{COMMANDS, COMMON_EXITS, EXIT_CODES};
+
+// Environment variables pin reads, for both documents
+const ENVIRONMENT: &[(&str, &str)] = &[
+    (
+        "PIN_STORE",
+        "The directory pin keeps everything in, instead of ~/.pin",
+    ),
+    (
+        "PIN_PROTOCOL",
+        "Set by the wrappers to ask for directives instead of exit codes",
+    ),
+    (
+        "PIN_SESSION",
+        "The pid of the shell that session pins belong to, set by the wrappers",
+    ),
+    ("VISUAL, EDITOR", "The editor file pins are opened in"),
+    ("HOME", "Used to find ~/.pin and to expand ~"),
+];
+
+fn main() {
+    println!("cargo::rerun-if-changed=src/help.rs");
+    println!("cargo::rerun-if-changed=build.rs");
+
+    let out = env::var("OUT_DIR").expect("cargo sets OUT_DIR");
+    fs::write(Path::new(&out).join("pin.1"), man()).expect("unable to write pin.1");
+    fs::write(Path::new(&out).join("pin.md"), markdown()).expect("unable to write pin.md");
+}
+
+// Escape text for roff, so dashes and quotes stay as they are and lines can't start a request
+fn roff(text: &str) -> String {
+    let text = (text.replace('\\', "\\e"))
+        .replace('-', "\\-")
+        .replace('"', "\\(dq");
+    match text.starts_with(['.', '\'']) {
+        true => format!("\\&{text}"),
+        false => text,
+    }
+}
+
+fn man() -> String {
+    let mut out = String::from(".TH PIN 1 \"\" \"pin\" \"User Commands\"\n");
+    out += ".SH NAME\npin \\- jump to directories by alias\n";
+
+    out += ".SH SYNOPSIS\n";
+    for usage in COMMANDS.iter().flat_map(|cmd| cmd.usage) {
+        out += &format!(".B {}\n.br\n", roff(usage));
+    }
+
+    out += ".SH DESCRIPTION\n";
+    out += "pin aliases paths so they can be jumped to from anywhere. \
+            The pin shell function is defined by the wrapper that \\fB_pin \\-\\-init\\fR prints, \
+            which runs \\fB_pin\\fR and changes directory, opens files and runs hooks as it is \
+            told.\n";
+
+    out += ".SH COMMANDS\n";
+    for cmd in COMMANDS {
+        out += &format!(".SS {}\n", roff(cmd.name));
+        for usage in cmd.usage {
+            out += &format!(".B {}\n.br\n", roff(usage));
+        }
+        out += &format!(".PP\n{}\n.PP\n{}\n", roff(cmd.summary), roff(cmd.details));
+        for (option, desc) in cmd.options {
+            out += &format!(".TP\n.B {}\n{}\n", roff(option), roff(desc));
+        }
+        out += ".PP\nExamples:\n";
+        for (example, desc) in cmd.examples {
+            out += &format!(".TP\n.B {}\n{}\n", roff(example), roff(desc));
+        }
+        if !cmd.exits.is_empty() {
+            let codes: Vec<String> = cmd.exits.iter().map(|c| c.to_string()).collect();
+            out += &format!(".PP\nExit codes: {}.\n", codes.join(", "));
+        }
+    }
+
+    out += ".SH EXIT STATUS\n";
+    for (code, desc) in EXIT_CODES {
+        out += &format!(".TP\n.B {code}\n{}\n", roff(desc));
+    }
+    let common: Vec<String> = COMMON_EXITS.iter().map(|c| c.to_string()).collect();
+    out += &format!(
+        ".PP\nAny command can also fail with {}. \\fBpin \\-\\-exec\\fR returns the exit code of \
+         the command it ran instead.\n",
+        common.join(", ")
+    );
+
+    out += ".SH ENVIRONMENT\n";
+    for (var, desc) in ENVIRONMENT {
+        out += &format!(".TP\n.B {var}\n{}\n", roff(desc));
+    }
+
+    out += ".SH FILES\n";
+    out += ".TP\n.B ~/.pin/config\nSettings, one key = value per line\n";
+    out += ".TP\n.B ~/.pin/store.bin\nThe pins, or store.toml, store.json or store.db when \
+            another store is configured\n";
+    out += ".TP\n.B ~/.pin/sessions/\nSession pins, one file per shell\n";
+    out
+}
+
+fn markdown() -> String {
+    let mut out = String::from("# pin command reference\n\n");
+    out += "Generated from `src/help.rs`. `pin --help <cmd>` shows the same for one command.\n";
+
+    for cmd in COMMANDS {
+        out += &format!("\n## {}\n\n", cmd.name);
+        out += "```\n";
+        for usage in cmd.usage {
+            out += &format!("{usage}\n");
+        }
+        out += "```\n\n";
+        out += &format!("{}. {}\n", cmd.summary, cmd.details);
+
+        if !cmd.options.is_empty() {
+            out += "\n| Option | Description |\n|---|---|\n";
+            for (option, desc) in cmd.options {
+                out += &format!("| `{option}` | {} |\n", desc.replace('|', "\\|"));
+            }
+        }
+
+        out += "\nExamples:\n\n";
+        for (example, desc) in cmd.examples {
+            out += &format!("- `{example}`: {desc}\n");
+        }
+
+        if !cmd.exits.is_empty() {
+            let codes: Vec<String> = cmd.exits.iter().map(|c| c.to_string()).collect();
+            out += &format!("\nExit codes: {}\n", codes.join(", "));
+        }
+    }
+
+    out += "\n## Exit codes\n\n| Code | Meaning |\n|---|---|\n";
+    for (code, desc) in EXIT_CODES {
+        out += &format!("| {code} | {desc} |\n");
+    }
+    let common: Vec<String> = COMMON_EXITS.iter().map(|c| c.to_string()).collect();
+    out += &format!(
+        "\nAny command can also fail with {}. `pin --exec` returns the exit code of the command \
+         it ran instead.\n",
+        common.join(", ")
+    );
+
+    out += "\n## Environment\n\n";
+    for (var, desc) in ENVIRONMENT {
+        out += &format!("- `{var}`: {desc}\n");
+    }
+    out
+}
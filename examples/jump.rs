@@ -0,0 +1,27 @@
+// Run a pin command line and act on its outcome, as the shell wrapper does. For example
+// `cargo run --example jump -- api` prints where `pin api` would go
+
+use pin::{DevTty, Directive, Outcome, parse_args};
+
+fn main() {
+    let cmd = parse_args(std::env::args());
+
+    match cmd.execute(&mut DevTty) {
+        Ok(Outcome::Shell(directives)) => {
+            for directive in directives {
+                match directive {
+                    Directive::Cd(path) | Directive::Pushd(path) => println!("cd {path}"),
+                    Directive::Open(path, _) => println!("open {path}"),
+                    other => println!("{other:?}"),
+                }
+            }
+        }
+        Ok(Outcome::Output(text)) => print!("{text}"),
+        Ok(Outcome::Done) => {}
+        Ok(Outcome::Exited(code)) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(e.code());
+        }
+    }
+}
@@ -0,0 +1,13 @@
+// Print every pin with where it goes, as a launcher might list them
+
+fn main() -> Result<(), pin::PinError> {
+    let store = pin::Store::init()?;
+
+    for (alias, record) in store.iter() {
+        match record.resolve() {
+            Ok(path) => println!("{alias}\t{path}"),
+            Err(e) => eprintln!("{alias}: {e}"),
+        }
+    }
+    Ok(())
+}
@@ -15,8 +15,12 @@ mkdir -p "$INST_D"
 
 # Install files
 echo "Installing pin..."
-cp "./pin.sh" "$INST_D/"
 cp "./target/release/_pin" "$INST_D/"
+# Generate the wrapper from the installed binary so it points at the right place
+"$INST_D/_pin" --init bash > "$INST_D/pin.sh"
+# Install the man page where man looks for user pages
+mkdir -p "$HOME/.local/share/man/man1"
+"$INST_D/_pin" --help --man > "$HOME/.local/share/man/man1/pin.1"
 
 # Add pin to PATH if not already there
 if ! grep -q "\$HOME/.pin/pin.sh" ~/.bashrc; then
@@ -1,13 +1,24 @@
 #!/bin/bash
 
+# Legacy exit code wrapper. It only understands exit code 2 (cd), so hooks and other directives
+# aren't run. install.sh now generates the wrapper with `_pin --init bash` instead.
+
 pin() {
-	local path exit
-	path=$( $HOME/.pin/_pin "$@" )
+	local path exit arg
+
+	# Commands run with `pin <alias> -- cmd` need the terminal, and their exit code is their own
+	for arg in "$@"; do
+		[ "$arg" = "--" ] && { PIN_SESSION=$$ $HOME/.pin/_pin "$@"; return; }
+	done
+
+	path=$( PIN_SESSION=$$ $HOME/.pin/_pin "$@" )
 	exit="$?"
 
+	# Errors are written to stderr by _pin, so only successful output is handled here
 	case "$exit" in
+		0) ;;
 		2) cd "$path" ;;
-		1) echo "$path" ;;
+		4) ${VISUAL:-${EDITOR:-vi}} "$path" ;;
+		*) [ -n "$path" ] && echo "$path"; return "$exit" ;;
 	esac
 }
-
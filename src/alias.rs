@@ -0,0 +1,183 @@
+// The record stored for each alias
+//
+// Records are written as a map of field name to encoded value rather than a fixed struct. Fields
+// can then be added without a store migration: old stores just fall back to the default for any
+// field they don't have, and unknown fields are ignored.
+
+use std::collections::BTreeMap;
+
+use bincode::config::{Configuration, standard};
+use bincode::{Decode, Encode};
+
+use std::path::Path;
+
+use crate::error::PinError;
+use crate::heal;
+use crate::history::now;
+use crate::path::{PathPolicy, expand_stored};
+
+// A record as it is written to disk
+pub type Fields = BTreeMap<String, Vec<u8>>;
+
+const CONFIG: Configuration = standard();
+
+/// Everything pin knows about one alias.
+#[derive(Clone, Default)]
+pub struct PinAlias {
+    /// Where the alias points, with ~ and $VAR left in when the policy is raw.
+    pub path: String,
+    /// Commands run in the shell after jumping, only if trusted.
+    pub hooks: Vec<String>,
+    /// Variables exported after jumping, only if trusted.
+    pub env: BTreeMap<String, String>,
+    /// Whether the user has approved the hooks and env. Reset whenever they change.
+    pub trusted: bool,
+    /// How the path was parsed, reused when it is edited.
+    pub policy: PathPolicy,
+    /// Device and inode of the directory, used to find it again if it moves.
+    pub identity: Option<(u64, u64)>,
+    /// Origin url if the directory is a git repository, for the same reason.
+    pub origin: Option<String>,
+    /// Points at a file, which is opened rather than cd'd into.
+    pub file: bool,
+    /// Sorted, for grouping pins and running commands across them.
+    pub tags: Vec<String>,
+    /// Free text describing what the pin is for.
+    pub note: String,
+    /// Seconds since the epoch after which the pin is dropped.
+    pub expires: Option<u64>,
+    /// Only lasts as long as the shell that made it. Not a field on disk, as session pins are
+    /// stored in a file of their own.
+    pub session: bool,
+    /// Refuse to overwrite, rename or delete the pin without --force.
+    pub locked: bool,
+    /// Seconds since the epoch when the pin was added. Unknown for pins from older versions.
+    pub created: Option<u64>,
+}
+
+impl PinAlias {
+    /// A record for a path that has already been checked, e.g. by the store's caller.
+    pub fn new(path: String, policy: PathPolicy) -> PinAlias {
+        let mut record = PinAlias {
+            path,
+            policy,
+            created: Some(now()),
+            ..Default::default()
+        };
+        record.stamp();
+        record
+    }
+
+    /// Remember what the target looks like now, so it can be found if it moves and opened the right
+    /// way.
+    pub fn stamp(&mut self) {
+        let Ok(path) = self.resolve() else {
+            return;
+        };
+        let path = Path::new(&path);
+
+        self.identity = heal::identity(path);
+        self.origin = heal::git_origin(path);
+        self.file = path.is_file();
+    }
+
+    /// The path to jump to, expanding raw paths.
+    pub fn resolve(&self) -> Result<String, PinError> {
+        expand_stored(&self.path, self.policy)
+    }
+
+    /// Whether jumping to this alias would do more than change directory.
+    pub fn has_hooks(&self) -> bool {
+        !self.hooks.is_empty() || !self.env.is_empty()
+    }
+
+    pub(crate) fn to_fields(&self) -> Result<Fields, bincode::error::EncodeError> {
+        let mut fields = Fields::new();
+        put(&mut fields, "path", &self.path)?;
+        if self.has_hooks() {
+            put(&mut fields, "hooks", &self.hooks)?;
+            put(&mut fields, "env", &self.env)?;
+            put(&mut fields, "trusted", &self.trusted)?;
+        }
+        if self.identity.is_some() {
+            put(&mut fields, "identity", &self.identity)?;
+        }
+        if self.origin.is_some() {
+            put(&mut fields, "origin", &self.origin)?;
+        }
+        if self.file {
+            put(&mut fields, "file", &self.file)?;
+        }
+        if !self.tags.is_empty() {
+            put(&mut fields, "tags", &self.tags)?;
+        }
+        if !self.note.is_empty() {
+            put(&mut fields, "note", &self.note)?;
+        }
+        if self.expires.is_some() {
+            put(&mut fields, "expires", &self.expires)?;
+        }
+        if self.locked {
+            put(&mut fields, "locked", &self.locked)?;
+        }
+        if self.created.is_some() {
+            put(&mut fields, "created", &self.created)?;
+        }
+        if self.policy != PathPolicy::default() {
+            let policy = &self.policy;
+            let flags = (policy.keep_symlinks, policy.allow_missing, policy.literal);
+            put(&mut fields, "policy", &flags)?;
+            // Kept apart from the other flags so older records still decode
+            put(&mut fields, "raw", &policy.raw)?;
+        }
+
+        Ok(fields)
+    }
+
+    pub(crate) fn from_fields(fields: &Fields) -> Result<PinAlias, bincode::error::DecodeError> {
+        let (keep_symlinks, allow_missing, literal) = take(fields, "policy")?;
+
+        Ok(PinAlias {
+            path: take(fields, "path")?,
+            hooks: take(fields, "hooks")?,
+            env: take(fields, "env")?,
+            trusted: take(fields, "trusted")?,
+            policy: PathPolicy {
+                keep_symlinks,
+                allow_missing,
+                literal,
+                raw: take(fields, "raw")?,
+            },
+            identity: take(fields, "identity")?,
+            origin: take(fields, "origin")?,
+            file: take(fields, "file")?,
+            tags: take(fields, "tags")?,
+            note: take(fields, "note")?,
+            expires: take(fields, "expires")?,
+            session: false,
+            locked: take(fields, "locked")?,
+            created: take(fields, "created")?,
+        })
+    }
+}
+
+// Encode one field into the record
+fn put<T: Encode>(
+    fields: &mut Fields,
+    name: &str,
+    value: &T,
+) -> Result<(), bincode::error::EncodeError> {
+    fields.insert(name.to_string(), bincode::encode_to_vec(value, CONFIG)?);
+    Ok(())
+}
+
+// Decode one field from the record, or its default if the record doesn't have it
+fn take<T: Decode<()> + Default>(
+    fields: &Fields,
+    name: &str,
+) -> Result<T, bincode::error::DecodeError> {
+    match fields.get(name) {
+        Some(bytes) => Ok(bincode::decode_from_slice(bytes, CONFIG)?.0),
+        None => Ok(T::default()),
+    }
+}
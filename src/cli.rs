@@ -0,0 +1,572 @@
+// Command line parsing
+//
+// The first argument picks the command and the rest are parsed into its struct. Anything that
+// can't be parsed becomes a ParseErr, which fails with a usage message when it is executed.
+
+use crate::cmd::*;
+use crate::expiry::{parse_ttl, parse_until};
+use crate::history::now;
+use crate::list::{ColorChoice, Column, Group, Sort};
+use crate::path::PathPolicy;
+use crate::storage::Backend;
+
+// What is left of the command line
+type Args = std::vec::IntoIter<String>;
+
+// Return a parse error message
+fn err_parse_msg(missing: &str, cmd: &str) -> Box<ParseErr> {
+    Box::new(ParseErr {
+        msg: format!("missing {missing} from command. Please use \"{cmd}\"."),
+    })
+}
+
+// Commands that change a locked pin take --force and nothing else after their arguments
+fn parse_force(args: Args, usage: &str) -> Result<bool, Box<ParseErr>> {
+    let mut force = false;
+    for arg in args {
+        match arg.as_str() {
+            "--force" | "-f" => force = true,
+            other => {
+                return Err(Box::new(ParseErr {
+                    msg: format!("unexpected \"{other}\". Please use \"{usage}\"."),
+                }));
+            }
+        }
+    }
+    Ok(force)
+}
+
+// Quote a word for the shell a hook runs in, if it needs it. Backslashes are left outside the
+// quotes, where bash, zsh and fish all read \\ as one
+fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_alphanumeric() || "-_./=:,+@".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_string();
+    }
+    let mut quoted = "'".to_string();
+    for c in word.chars() {
+        match c {
+            '\'' => quoted.push_str(r"'\''"),
+            '\\' => quoted.push_str(r"'\\'"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+// Everything after the -- is the command to run
+fn parse_exec(target: String, jobs: usize, args: Args) -> Box<dyn Cmd> {
+    let cmd: Vec<String> = args.collect();
+    if cmd.is_empty() {
+        return err_parse_msg("command", "pin --exec [alias|@tag] [-j n] -- [cmd...]");
+    }
+
+    Box::new(Exec { target, jobs, cmd })
+}
+
+// The filter and options of --list, in any order
+fn parse_list(mut args: Args) -> Box<dyn Cmd> {
+    let usage = "pin --list [filter] [--columns a,b,...] [--tree | --group-by tag] [--sort key] \
+                 [--reverse] [--color=when]";
+    let unexpected = |arg: &str| {
+        Box::new(ParseErr {
+            msg: format!("unexpected \"{arg}\". Please use \"{usage}\"."),
+        })
+    };
+    let mut list = List {
+        filter: None,
+        columns: None,
+        sort: Sort::Alias,
+        reverse: false,
+        color: ColorChoice::Auto,
+        group: None,
+    };
+
+    while let Some(arg) = args.next() {
+        // --color takes its value after an = or as the next argument
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        match flag {
+            "--columns" => {
+                let Some(names) = value.or_else(|| args.next()) else {
+                    return err_parse_msg("columns", usage);
+                };
+                let mut columns = Vec::new();
+                for name in names.split(',').map(str::trim) {
+                    let Some(column) = Column::parse(name) else {
+                        return Box::new(ParseErr {
+                            msg: format!("unknown column \"{name}\", use {}.", Column::NAMES),
+                        });
+                    };
+                    columns.push(column);
+                }
+                list.columns = Some(columns);
+            }
+            "--sort" => {
+                let Some(name) = value.or_else(|| args.next()) else {
+                    return err_parse_msg("sort", usage);
+                };
+                let Some(sort) = Sort::parse(&name) else {
+                    return Box::new(ParseErr {
+                        msg: format!("unknown sort \"{name}\", use {}.", Sort::NAMES),
+                    });
+                };
+                list.sort = sort;
+            }
+            "--reverse" if value.is_none() => list.reverse = true,
+            "--tree" if value.is_none() => list.group = Some(Group::Path),
+            "--group-by" => {
+                let Some(name) = value.or_else(|| args.next()) else {
+                    return err_parse_msg("group", usage);
+                };
+                let Some(group) = Group::parse(&name) else {
+                    return Box::new(ParseErr {
+                        msg: format!("unknown group \"{name}\", use path or tag."),
+                    });
+                };
+                list.group = Some(group);
+            }
+            "--color" => {
+                let Some(name) = value.or_else(|| args.next()) else {
+                    return err_parse_msg("color", usage);
+                };
+                let Some(color) = ColorChoice::parse(&name) else {
+                    return Box::new(ParseErr {
+                        msg: format!("unknown color \"{name}\", use auto, always or never."),
+                    });
+                };
+                list.color = color;
+            }
+            _ if arg.starts_with('-') || list.filter.is_some() => return unexpected(&arg),
+            _ => list.filter = Some(arg),
+        }
+    }
+
+    // Grouped pins aren't a table, so have no columns to pick
+    if list.group.is_some() && list.columns.is_some() {
+        return Box::new(ParseErr {
+            msg: "--columns can't be used with --tree or --group-by.".to_string(),
+        });
+    }
+
+    Box::new(list)
+}
+
+/// Parse a command line, starting with the program name as [`std::env::args`] does, into the
+/// command it asks for. A command line that can't be parsed gives a command that fails with
+/// [`PinError::Usage`](crate::PinError::Usage) when executed.
+// Maybe in the future limit argument numbers for each command
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Box<dyn Cmd> {
+    let mut args: Args = args.into_iter().collect::<Vec<_>>().into_iter();
+    let _ = args.next();
+
+    let arg = args.next().unwrap_or_default();
+    match arg.as_str() {
+        "--add" | "-a" => {
+            // Get the alias or return ParseErr
+            let Some(alias) = args.next() else {
+                return err_parse_msg("alias", "pin --add [alias] [path]");
+            };
+
+            // Get the path or return ParseErr
+            let Some(path) = args.next() else {
+                return err_parse_msg("path", "pin --add [alias] [path]");
+            };
+
+            // Anything else has to be an option or a path policy flag
+            let mut policy = PathPolicy::default();
+            let mut note = String::new();
+            let mut expires = None;
+            let mut session = false;
+            let mut force = false;
+            while let Some(flag) = args.next() {
+                match flag.as_str() {
+                    "--note" => {
+                        let Some(text) = args.next() else {
+                            return err_parse_msg("note", "pin --add [alias] [path] --note [text]");
+                        };
+                        note = text;
+                    }
+                    // A time to live too long to add to the clock is as bad as a malformed one
+                    "--ttl" => {
+                        let Some(ttl) = args.next() else {
+                            return err_parse_msg(
+                                "time to live",
+                                "pin --add [alias] [path] --ttl [time]",
+                            );
+                        };
+                        match parse_ttl(&ttl).and_then(|ttl| now().checked_add(ttl)) {
+                            Some(time) => expires = Some(time),
+                            None => {
+                                return Box::new(ParseErr {
+                                    msg: format!(
+                                        "invalid time to live \"{ttl}\". Use a number and a unit of s, m, h, d or w, e.g. 30m, 2h or 1d."
+                                    ),
+                                });
+                            }
+                        }
+                    }
+                    "--until" => {
+                        let Some(until) = args.next() else {
+                            return err_parse_msg(
+                                "date",
+                                "pin --add [alias] [path] --until [date]",
+                            );
+                        };
+                        match parse_until(&until) {
+                            Some(time) => expires = Some(time),
+                            None => {
+                                return Box::new(ParseErr {
+                                    msg: format!(
+                                        "invalid date \"{until}\". Use YYYY-MM-DD, YYYY-MM-DD HH:MM or YYYY-MM-DDTHH:MM."
+                                    ),
+                                });
+                            }
+                        }
+                    }
+                    "--session" => session = true,
+                    "--force" | "-f" => force = true,
+                    _ if policy.set_flag(&flag) => {}
+                    _ => {
+                        return Box::new(ParseErr {
+                            msg: format!(
+                                "unknown option \"{flag}\" for --add. Options are {}, --note, --ttl, --until, --session, --force.",
+                                PathPolicy::FLAGS
+                            ),
+                        });
+                    }
+                }
+            }
+
+            // Return an add command
+            Box::new(Add {
+                alias,
+                path,
+                policy,
+                note,
+                expires,
+                session,
+                force,
+            })
+        }
+        "--here" => Box::new(Here { alias: args.next() }),
+        "--tui" => Box::new(Tui),
+        "--delete" | "-d" => {
+            // Get the alias or return ParseErr
+            let Some(alias) = args.next() else {
+                return err_parse_msg("alias", "pin --delete [alias|pattern] [--force]");
+            };
+            let force = match parse_force(args, "pin --delete [alias|pattern] [--force]") {
+                Ok(force) => force,
+                Err(e) => return e,
+            };
+
+            Box::new(Delete { alias, force })
+        }
+        "--push" | "-p" => {
+            // Get the alias or return ParseErr
+            let Some(alias) = args.next() else {
+                return err_parse_msg("alias", "pin --push [alias]");
+            };
+
+            Box::new(Pin { alias, push: true })
+        }
+        "-" => Box::new(Previous),
+        "--back" | "-b" | "--forward" => {
+            let forward = arg == "--forward";
+            // Default to a single step
+            let steps = match args.next() {
+                None => 1,
+                Some(n) => match n.parse() {
+                    Ok(n) => n,
+                    Err(_) => {
+                        return Box::new(ParseErr {
+                            msg: format!("\"{n}\" is not a number of jumps."),
+                        });
+                    }
+                },
+            };
+
+            Box::new(Back { steps, forward })
+        }
+        "--history" => Box::new(ListHistory),
+        "--hook" => {
+            let usage = "pin --hook [alias] [add [cmd] | env [NAME=value] | rm [n] | clear]";
+            // Get the alias or return ParseErr
+            let Some(alias) = args.next() else {
+                return err_parse_msg("alias", usage);
+            };
+
+            let action = match args.next().as_deref() {
+                None => HookAction::Show,
+                // One argument is taken as shell code as it is. Several are each quoted, so the
+                // hook runs the same words as were typed
+                Some("add") => {
+                    let words: Vec<String> = args.collect();
+                    let cmd = match words.as_slice() {
+                        [] => return err_parse_msg("command", usage),
+                        [cmd] => cmd.clone(),
+                        words => words
+                            .iter()
+                            .map(|word| shell_quote(word))
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    };
+                    HookAction::Add(cmd)
+                }
+                Some("env") => {
+                    let Some(var) = args.next() else {
+                        return err_parse_msg("variable", usage);
+                    };
+                    match var.split_once('=') {
+                        Some((name, value)) => {
+                            HookAction::Env(name.to_string(), Some(value.to_string()))
+                        }
+                        None => HookAction::Env(var, None),
+                    }
+                }
+                Some("rm") => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) => HookAction::Remove(n),
+                    None => return err_parse_msg("hook number", usage),
+                },
+                Some("clear") => HookAction::Clear,
+                Some(other) => {
+                    return Box::new(ParseErr {
+                        msg: format!("unknown hook action \"{other}\". Please use \"{usage}\"."),
+                    });
+                }
+            };
+
+            Box::new(Hook { alias, action })
+        }
+        "--trust" | "--untrust" => {
+            let trust = arg == "--trust";
+            // Get the alias or return ParseErr
+            let Some(alias) = args.next() else {
+                return err_parse_msg("alias", "pin --trust [alias]");
+            };
+
+            Box::new(Trust { alias, trust })
+        }
+        "--doctor" => {
+            let fix = match args.next().as_deref() {
+                None => false,
+                Some("--fix") => true,
+                Some(other) => {
+                    return Box::new(ParseErr {
+                        msg: format!("unknown option \"{other}\" for --doctor."),
+                    });
+                }
+            };
+
+            Box::new(Doctor { fix })
+        }
+        "--tag" | "--untag" => {
+            let remove = arg == "--untag";
+            // Get the alias or return ParseErr
+            let Some(alias) = args.next() else {
+                return err_parse_msg("alias", "pin --tag [alias|pattern] [tags...]");
+            };
+            let tags: Vec<String> = args.collect();
+            if tags.is_empty() {
+                return err_parse_msg("tags", "pin --tag [alias|pattern] [tags...]");
+            }
+
+            Box::new(Tag {
+                alias,
+                tags,
+                remove,
+            })
+        }
+        "--note" => {
+            // Get the alias or return ParseErr
+            let Some(alias) = args.next() else {
+                return err_parse_msg("alias", "pin --note [alias] [text...]");
+            };
+            let text: Vec<String> = args.collect();
+
+            // Without any text the note is shown rather than changed
+            Box::new(Note {
+                alias,
+                text: (!text.is_empty()).then(|| text.join(" ")),
+            })
+        }
+        "--search" | "-s" => {
+            let text: Vec<String> = args.collect();
+            if text.is_empty() {
+                return err_parse_msg("text", "pin --search [text...]");
+            }
+
+            Box::new(Search {
+                text: text.join(" "),
+            })
+        }
+        "--exec" | "-x" => {
+            let usage = "pin --exec [alias|@tag] [-j n] -- [cmd...]";
+            // Get the alias or return ParseErr
+            let Some(target) = args.next() else {
+                return err_parse_msg("alias", usage);
+            };
+
+            let mut jobs = 1;
+            loop {
+                match args.next().as_deref() {
+                    Some("--") => break,
+                    Some("-j" | "--jobs") => {
+                        let Some(n) = args.next() else {
+                            return err_parse_msg("number of jobs", usage);
+                        };
+                        match n.parse() {
+                            Ok(n) if n > 0 => jobs = n,
+                            _ => {
+                                return Box::new(ParseErr {
+                                    msg: format!(
+                                        "invalid number of jobs \"{n}\". Please use 1 or more."
+                                    ),
+                                });
+                            }
+                        }
+                    }
+                    _ => return err_parse_msg("-- before the command", usage),
+                }
+            }
+
+            parse_exec(target, jobs, args)
+        }
+        "--init" => {
+            // Get the shell or return ParseErr
+            let Some(shell) = args.next() else {
+                return err_parse_msg("shell", "_pin --init [bash|zsh|fish]");
+            };
+
+            Box::new(Init { shell })
+        }
+        "--help" | "-h" => Box::new(Help { cmd: args.next() }),
+        "--list" | "-l" => parse_list(args),
+        "--update" | "-u" => {
+            // Get the alias or return ParseErr
+            let Some(alias) = args.next() else {
+                return err_parse_msg("alias", "pin --update [alias] [--force]");
+            };
+            let force = match parse_force(args, "pin --update [alias] [--force]") {
+                Ok(force) => force,
+                Err(e) => return e,
+            };
+
+            Box::new(Update { alias, force })
+        }
+        "--rename" => {
+            let usage = "pin --rename [alias] [new alias] [--force]";
+            // Get both aliases or return ParseErr
+            let (Some(alias), Some(new)) = (args.next(), args.next()) else {
+                return err_parse_msg("alias", usage);
+            };
+            let force = match parse_force(args, usage) {
+                Ok(force) => force,
+                Err(e) => return e,
+            };
+
+            Box::new(Rename { alias, new, force })
+        }
+        "--prune" => {
+            let force = match parse_force(args, "pin --prune [--force]") {
+                Ok(force) => force,
+                Err(e) => return e,
+            };
+
+            Box::new(Prune { force })
+        }
+        "--retarget" => {
+            let usage = "pin --retarget [old root] [new root] [--force]";
+            // Get both roots or return ParseErr
+            let (Some(from), Some(to)) = (args.next(), args.next()) else {
+                return err_parse_msg("directory", usage);
+            };
+            let force = match parse_force(args, usage) {
+                Ok(force) => force,
+                Err(e) => return e,
+            };
+
+            Box::new(Retarget { from, to, force })
+        }
+        "--export" => {
+            // Get the file or return ParseErr
+            let Some(file) = args.next() else {
+                return err_parse_msg("file", "pin --export [file]");
+            };
+
+            Box::new(Export { file })
+        }
+        "--import" => {
+            let usage = "pin --import [file] [--replace] [--force]";
+            // Get the file or return ParseErr
+            let Some(file) = args.next() else {
+                return err_parse_msg("file", usage);
+            };
+
+            let (mut replace, mut force) = (false, false);
+            for flag in args {
+                match flag.as_str() {
+                    "--replace" => replace = true,
+                    "--force" | "-f" => force = true,
+                    other => {
+                        return Box::new(ParseErr {
+                            msg: format!("unexpected \"{other}\". Please use \"{usage}\"."),
+                        });
+                    }
+                }
+            }
+
+            Box::new(Import {
+                file,
+                replace,
+                force,
+            })
+        }
+        "--migrate-store" => {
+            let usage = "pin --migrate-store --to [bincode|toml|json|sqlite]";
+            // Get the backend or return ParseErr
+            let (Some("--to"), Some(to)) = (args.next().as_deref(), args.next()) else {
+                return err_parse_msg("backend", usage);
+            };
+            let Some(to) = Backend::parse(&to) else {
+                return Box::new(ParseErr {
+                    msg: format!("unknown store \"{to}\", use one of {}.", Backend::NAMES),
+                });
+            };
+
+            Box::new(MigrateStore { to })
+        }
+        "--lock" | "--unlock" => {
+            let lock = arg == "--lock";
+            // Get the alias or return ParseErr
+            let Some(alias) = args.next() else {
+                return err_parse_msg("alias", "pin --lock [alias|pattern]");
+            };
+
+            Box::new(Lock { alias, lock })
+        }
+        catch if catch.starts_with("-") => Box::new(ParseErr {
+            msg: format!(
+                "No function named {}, type --help to list all commands.",
+                catch
+            ),
+        }),
+        // pin <alias> -- <cmd...> runs the command there instead of jumping
+        alias => match args.next().as_deref() {
+            Some("--") => parse_exec(alias.to_string(), 1, args),
+            None => Box::new(Pin {
+                alias: alias.to_string(),
+                push: false,
+            }),
+            Some(other) => Box::new(ParseErr {
+                msg: format!(
+                    "unexpected \"{other}\". To run a command use \"pin {alias} -- [cmd...]\"."
+                ),
+            }),
+        },
+    }
+}
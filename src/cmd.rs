@@ -1,51 +1,302 @@
 // The commands that can be passed into the program
 
-use crossterm::{
-    event::{self, Event, KeyCode},
-    terminal::{disable_raw_mode, enable_raw_mode},
-};
-use prettytable::{Table, row};
+use std::path::Path;
+
+use prettytable::{Cell, Row, Table, row};
+use unicode_width::UnicodeWidthStr;
 
-use crate::{parse_path, store::Store, tty::Tty};
+use crate::{
+    alias::PinAlias,
+    config::Config,
+    directive::{self, Directive},
+    error::{EXIT_OK, PinError},
+    exec, heal, help,
+    history::{History, fmt_ago, now},
+    list,
+    path::{PathPolicy, parse_for_store, parse_path, path_to_string},
+    pattern::Pattern,
+    shell::Shell,
+    storage::{Backend, Pins},
+    store::Store,
+    transfer::{self, Conflict},
+    tty::{Complete, LineInput, Terminal, load_history, remember},
+    tui,
+};
 
 // Trait for structs that can be executed. Each command should implement execute. This is vv
 // similar to how I would use message enums normally, but with named parameters vv easily and
 // seperate implementations of execute without having to pass functions as parameters. Win.
 //
+// Commands return what is left for the shell to do on success, which the binary turns into an exit
+// code for the wrapper. Every failure is a PinError, which carries its own exit code.
 
+/// A command parsed from the command line by [`parse_args`](crate::parse_args).
 pub trait Cmd {
-    fn execute(self: Box<Self>) -> i32;
+    /// Run the command, asking the user anything it needs to through `term`.
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError>;
+}
+
+/// What a command leaves for the shell to do once it has finished.
+#[derive(Debug)]
+pub enum Outcome {
+    /// Nothing more to do.
+    Done,
+    /// Directives for the shell wrapper, such as changing directory.
+    Shell(Vec<Directive>),
+    /// Text for stdout, such as a shell wrapper to eval.
+    Output(String),
+    /// A command was run in a pinned directory and exited with this code.
+    Exited(i32),
+}
+
+impl Outcome {
+    /// Hand the outcome to the shell wrapper on stdout, returning the exit code to use.
+    pub fn emit(self) -> Result<i32, PinError> {
+        match self {
+            Outcome::Done => Ok(EXIT_OK),
+            Outcome::Shell(directives) => directive::emit(directives),
+            Outcome::Output(text) => {
+                print!("{text}");
+                Ok(EXIT_OK)
+            }
+            Outcome::Exited(code) => Ok(code),
+        }
+    }
 }
 
 // Implementors of Cmd
 
 // pin <alias>
+// pin --push <alias>
 //
-// Used to jump to a string by the alias
+// Used to jump to a string by the alias. With --push the old directory is kept on the shell's
+// directory stack
 pub struct Pin {
     pub alias: String,
-}
-
-impl Pin {
-    const NAME: &str = "pin";
-    const USAGE: &str = "pin [alias]";
-    const DESC: &str = "Go to the path aliased";
+    pub push: bool,
 }
 
 impl Cmd for Pin {
     // Return path or error
-    fn execute(self: Box<Self>) -> i32 {
-        let store = Store::init();
-        match store.get(&self.alias) {
-            Some(path) => {
-                println!("{path}");
-                return 2;
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let (directives, path) = jump(term, &self.alias, self.push)?;
+        save_history(|history| history.record(&self.alias, &path));
+
+        Ok(Outcome::Shell(directives))
+    }
+}
+
+// Work out the directives for jumping to an alias, and the path being jumped to. This runs before
+// every cd, so only the one pin is read from the store
+fn jump(
+    term: &mut dyn Terminal,
+    alias: &str,
+    push: bool,
+) -> Result<(Vec<Directive>, String), PinError> {
+    let Some(record) = Store::find(alias)? else {
+        return Err(PinError::AliasNotFound(alias.to_string()));
+    };
+    let mut path = record.resolve()?;
+    let mut directives = Vec::new();
+
+    // Don't hand the wrapper a directory it can't cd into. Look for it first in case it moved
+    if !Path::new(&path).exists() {
+        match relocate(term, alias, &record)? {
+            Some(new) => {
+                directives.push(Directive::Echo(format!(
+                    "pin: \"{alias}\" moved to {new}. The pin has been updated."
+                )));
+                path = new;
             }
             None => {
-                eprintln!("Error: Alias not found in store. Type \"pin --help\" for help");
-                return 1;
+                return Err(PinError::PathMissing {
+                    alias: alias.to_string(),
+                    path,
+                });
+            }
+        }
+    }
+
+    // Files are opened instead. Hooks are about the shell's directory so they don't apply
+    if record.file {
+        let opener = opener_for(&path)?;
+        directives.insert(0, Directive::Open(path.clone(), opener));
+        return Ok((directives, path));
+    }
+
+    directives.insert(
+        0,
+        if push {
+            Directive::Pushd(path.clone())
+        } else {
+            Directive::Cd(path.clone())
+        },
+    );
+
+    // Hooks run after the cd, and only once the user has looked at them
+    if record.has_hooks() {
+        if record.trusted {
+            for (name, value) in &record.env {
+                directives.push(Directive::SetEnv(name.clone(), value.clone()));
+            }
+            for hook in &record.hooks {
+                directives.push(Directive::Run(hook.clone()));
+            }
+        } else {
+            directives.push(Directive::Echo(format!(
+                "pin: \"{alias}\" has on-enter hooks that haven't been trusted. Review them with \"pin --trust {alias}\"."
+            )));
+        }
+    }
+
+    Ok((directives, path))
+}
+
+// The command configured to open a file by its extension, e.g. `open.pdf = zathura`. None means
+// the wrapper uses $VISUAL or $EDITOR
+fn opener_for(path: &str) -> Result<Option<String>, PinError> {
+    let Some(ext) = Path::new(path).extension() else {
+        return Ok(None);
+    };
+
+    let config = Config::load()?;
+    Ok(config
+        .get(&format!("open.{}", ext.to_string_lossy()))
+        .map(String::from))
+}
+
+// Search for a pin whose directory has gone, and update the pin if it is found and the user agrees
+// (or has turned on auto_heal). Raw pins aren't touched since their path is deliberately symbolic
+fn relocate(
+    term: &mut dyn Terminal,
+    alias: &str,
+    record: &PinAlias,
+) -> Result<Option<String>, PinError> {
+    if record.policy.raw {
+        return Ok(None);
+    }
+
+    let search = heal::Search::for_jump(&Config::load()?)?;
+    let Some(found) = search.find(record) else {
+        return Ok(None);
+    };
+    let sure = found.sure;
+    let found = path_to_string(found.path)?;
+
+    // A directory that only shares the inode could be an unrelated one, so that is always asked
+    if !(search.auto && sure) {
+        // Without a terminal to ask on, leave the pin alone
+        let Ok(mut tty) = term.open() else {
+            return Ok(None);
+        };
+        let looks = if sure { "it looks like" } else { "it may have" };
+        tty.write(format!(
+            "\"{alias}\" points to {}, which no longer exists, but {looks} moved to {found}.\n",
+            record.path
+        ))?;
+        if !tty.confirm("Update the pin?")? {
+            return Ok(None);
+        }
+    }
+
+    let mut store = Store::lock()?;
+    if let Some(record) = store.get_mut(alias) {
+        record.path = found.clone();
+        record.stamp();
+    }
+    store.save()?;
+
+    Ok(Some(found))
+}
+
+// Change the history once the jump is known to work. A failure to write it shouldn't stop the jump
+fn save_history(change: impl FnOnce(&mut History)) {
+    if let Err(e) = History::update(change) {
+        eprintln!("Warning: unable to save jump history. {e}");
+    }
+}
+
+// pin -
+//
+// Used to jump back to the previous pin, like cd -
+pub struct Previous;
+
+impl Cmd for Previous {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let history = History::load()?;
+        let Some(alias) = history.previous().map(|e| e.alias.clone()) else {
+            return Err(PinError::Usage("there is no previous pin.".to_string()));
+        };
+
+        let (directives, path) = jump(term, &alias, false)?;
+        save_history(|history| history.record(&alias, &path));
+
+        Ok(Outcome::Shell(directives))
+    }
+}
+
+// pin --back [n]
+// pin --forward [n]
+//
+// Used to move through the jump history without adding to it
+pub struct Back {
+    pub steps: usize,
+    pub forward: bool,
+}
+
+impl Cmd for Back {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let mut history = History::load()?;
+        let entry = if self.forward {
+            history.forward(self.steps)
+        } else {
+            history.back(self.steps)
+        };
+        let Some(alias) = entry.map(|e| e.alias.clone()) else {
+            return Err(PinError::Usage(format!(
+                "the history doesn't go {} {} jump(s).",
+                if self.forward { "forward" } else { "back" },
+                self.steps
+            )));
+        };
+
+        // The cursor is moved again on the history as it is now, in case another shell jumped
+        // while this one was looking for the pin
+        let (directives, _) = jump(term, &alias, false)?;
+        save_history(|history| {
+            if self.forward {
+                history.forward(self.steps);
+            } else {
+                history.back(self.steps);
             }
+        });
+
+        Ok(Outcome::Shell(directives))
+    }
+}
+
+// pin --history
+//
+// Used to list recent jumps
+pub struct ListHistory;
+
+impl Cmd for ListHistory {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let history = History::load()?;
+
+        let mut table = Table::new();
+        table.add_row(row!["", "Alias", "Path", "When"]);
+        for (current, entry) in history.recent() {
+            table.add_row(row![
+                if current { "*" } else { "" },
+                entry.alias,
+                entry.path,
+                fmt_ago(entry.time)
+            ]);
         }
+
+        term.open()?.write(table.to_string())?;
+        Ok(Outcome::Done)
     }
 }
 
@@ -55,61 +306,204 @@ impl Cmd for Pin {
 pub struct Add {
     pub alias: String,
     pub path: String,
-}
-
-impl Add {
-    const NAME: &str = "add";
-    const SHORT: &str = "-a";
-    const USAGE: &str = "pin --add [alias] [path]";
-    const DESC: &str = "Add the supplied alias to the supplied path.";
+    pub policy: PathPolicy,
+    pub note: String,
+    pub expires: Option<u64>,
+    pub session: bool,
+    pub force: bool,
 }
 
 impl Cmd for Add {
     // Add an alias-path pair
-    fn execute(self: Box<Self>) -> i32 {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
         // Test path
-        let path = match crate::parse_path(&self.path) {
-            Ok(path) => path
-                .into_os_string()
-                .into_string()
-                .expect("Error: Unable to parse path as string."),
-            Err(e) => {
-                Tty::new()
-                    .expect("Error: unable to get tty access.")
-                    .write(e.to_string())
-                    .unwrap();
-                return 1;
-            }
+        let path = parse_for_store(&self.path, self.policy)?;
+
+        let mut store = Store::lock()?;
+
+        if let Some(time) = self.expires
+            && time <= now()
+        {
+            return Err(PinError::Usage(
+                "the pin would already have expired.".to_string(),
+            ));
+        }
+
+        let mut record = PinAlias::new(path, self.policy);
+        record.note = self.note;
+        record.expires = self.expires;
+        record.session = self.session;
+
+        // Save changes and exit
+        if add_confirmed(term, &mut store, self.alias, record, self.force)? {
+            store.save()?;
+        }
+        Ok(Outcome::Done)
+    }
+}
+
+// Add a record, asking first if it would overwrite an existing alias. Returns whether it was added
+fn add_confirmed(
+    term: &mut dyn Terminal,
+    store: &mut Store,
+    alias: String,
+    record: PinAlias,
+    force: bool,
+) -> Result<bool, PinError> {
+    check_unlocked(store, &alias, force)?;
+
+    // Confirmation on overwriting alias
+    if let Some(old) = store.get(&alias) {
+        let mut tty = term.open()?;
+        tty.write(format!(
+            "This action will overwrite existing alias to {}.\n",
+            old.path
+        ))?;
+
+        if !tty.confirm("Overwrite it?")? {
+            return Ok(false);
+        }
+    }
+
+    store.add(alias, record);
+    Ok(true)
+}
+
+// Fail if the alias exists and is locked, unless forced
+fn check_unlocked(store: &Store, alias: &str, force: bool) -> Result<(), PinError> {
+    match store.get(alias) {
+        Some(record) if record.locked && !force => Err(PinError::Locked(alias.to_string())),
+        _ => Ok(()),
+    }
+}
+
+// The aliases a pattern selects, or None if the argument is a single alias. An alias that exists
+// is always itself, even if it looks like a pattern
+fn select(store: &Store, arg: &str) -> Result<Option<Vec<String>>, PinError> {
+    if store.get(arg).is_some() {
+        return Ok(None);
+    }
+    let Some(pattern) = Pattern::parse(arg)? else {
+        return Ok(None);
+    };
+
+    let aliases: Vec<String> = store
+        .matching(&pattern)
+        .map(|(alias, _)| alias.clone())
+        .collect();
+    if aliases.is_empty() {
+        return Err(PinError::AliasNotFound(arg.to_string()));
+    }
+    Ok(Some(aliases))
+}
+
+// Show the pins a change to many pins will affect and ask once whether to go ahead. Locked pins
+// being left alone are listed first
+fn confirm_bulk(
+    term: &mut dyn Terminal,
+    header: &[&str],
+    rows: Vec<Vec<String>>,
+    kept: &[String],
+    question: String,
+) -> Result<bool, PinError> {
+    let mut table = Table::new();
+    table.add_row(Row::new(header.iter().map(|h| Cell::new(h)).collect()));
+    for cells in rows {
+        table.add_row(Row::new(cells.iter().map(|c| Cell::new(c)).collect()));
+    }
+
+    let mut tty = term.open()?;
+    for alias in kept {
+        tty.write(format!("Keeping \"{alias}\" as it is locked.\n"))?;
+    }
+    tty.write(table.to_string())?;
+    tty.confirm(&question)
+}
+
+// Split out the aliases that are locked, unless forced
+fn split_locked(store: &Store, aliases: Vec<String>, force: bool) -> (Vec<String>, Vec<String>) {
+    aliases
+        .into_iter()
+        .partition(|alias| force || store.get(alias).is_none_or(|r| !r.locked))
+}
+
+// pin --here [alias]
+//
+// Used to pin the current directory. Without an alias one is made from the directory name, or the
+// git repository name and the path inside it
+pub struct Here {
+    pub alias: Option<String>,
+}
+
+impl Cmd for Here {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let path = path_to_string(parse_path(".", PathPolicy::default())?)?;
+        let alias = match self.alias {
+            Some(alias) => alias,
+            None => default_alias(Path::new(&path)).ok_or_else(|| {
+                PinError::Usage(
+                    "unable to name this directory. Please use \"pin --here [alias]\".".to_string(),
+                )
+            })?,
         };
 
-        let mut store = Store::init();
-
-        // Confirmation on overwriting alias
-        match store.add(self.alias, path) {
-            Err(path) => {
-                let mut tty = Tty::new().expect("Error: Unable to get raw tty access.");
-                match tty.write(format!(
-                    "This action will overwrite existing alias to {}.\n\r",
-                    path
-                )) {
-                    Ok(()) => {}
-                    Err(_) => {
-                        eprintln!("Error: unable to get access to tty.");
-                        return 1;
-                    }
-                }
+        let mut store = Store::lock()?;
+
+        // Check for pins that already point here before adding another
+        let existing: Vec<String> = store
+            .iter()
+            .filter(|(_, record)| record.resolve().is_ok_and(|p| p == path))
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        if existing.contains(&alias) {
+            eprintln!("This directory is already pinned as \"{alias}\".");
+            return Ok(Outcome::Done);
+        }
+
+        let mut record = PinAlias::new(path, PathPolicy::default());
+        if let Some(other) = existing.first() {
+            let choice = {
+                let mut tty = term.open()?;
+                tty.write(format!(
+                    "This directory is already pinned as \"{}\".\n",
+                    existing.join("\", \"")
+                ))?;
 
-                match tty.ask_confirmation() {
-                    true => {}
-                    false => return 0,
+                let rename = format!("rename {other} to {alias}");
+                tty.select(&[("reuse", 'r'), (&rename, 'n'), ("add anyway", 'a')])?
+            };
+
+            match choice {
+                None | Some(0) => return Ok(Outcome::Done),
+                // Move the existing record so its hooks come with it
+                Some(1) => {
+                    check_unlocked(&store, other, false)?;
+                    if let Some(old) = store.delete(other) {
+                        record = old;
+                    }
                 }
+                _ => {}
             }
-            _ => {}
         }
 
-        // Save changes and exit
-        store.save();
-        return 0;
+        if add_confirmed(term, &mut store, alias, record, false)? {
+            store.save()?;
+        }
+        Ok(Outcome::Done)
+    }
+}
+
+// Name a directory after itself, or after its git repository and the path inside it
+fn default_alias(dir: &Path) -> Option<String> {
+    let name = |p: &Path| Some(p.file_name()?.to_string_lossy().into_owned());
+
+    let repo = dir.ancestors().find(|p| p.join(".git").exists());
+    match repo {
+        Some(root) if root != dir => {
+            let sub = dir.strip_prefix(root).ok()?;
+            Some(format!("{}/{}", name(root)?, sub.to_string_lossy()))
+        }
+        _ => name(dir),
     }
 }
 
@@ -118,161 +512,181 @@ impl Cmd for Add {
 // Used to delete an alias from the store
 pub struct Delete {
     pub alias: String,
-}
-
-impl Delete {
-    const NAME: &str = "delete";
-    const SHORT: &str = "-d";
-    const USAGE: &str = "pin --delete [alias]";
-    const DESC: &str = "Delete an alias from it's store.";
+    pub force: bool,
 }
 
 impl Cmd for Delete {
     // Delete a path
-    fn execute(self: Box<Self>) -> i32 {
-        let mut tty = Tty::new().expect("Error: unable to get raw tty access.");
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let mut store = Store::lock()?;
+        match select(&store, &self.alias)? {
+            None => {
+                if store.get(&self.alias).is_none() {
+                    return Err(PinError::AliasNotFound(self.alias));
+                }
+                check_unlocked(&store, &self.alias, self.force)?;
+
+                if !term
+                    .open()?
+                    .confirm(&format!("Delete \"{}\"?", self.alias))?
+                {
+                    return Ok(Outcome::Done);
+                }
+                store.delete(&self.alias);
+            }
+            Some(aliases) => {
+                // A pattern that only matches locked pins is the same as deleting a locked pin
+                let (aliases, kept) = split_locked(&store, aliases, self.force);
+                if aliases.is_empty() {
+                    return Err(PinError::Locked(kept.join(", ")));
+                }
 
-        if !tty.ask_confirmation() {
-            return 0;
+                let rows = aliases
+                    .iter()
+                    .filter_map(|alias| Some(vec![alias.clone(), store.get(alias)?.path.clone()]))
+                    .collect();
+                let question = format!("Delete {} pin(s)?", aliases.len());
+                if !confirm_bulk(term, &["Alias", "Path"], rows, &kept, question)? {
+                    return Ok(Outcome::Done);
+                }
+                for alias in aliases {
+                    store.delete(&alias);
+                }
+            }
         }
-        let mut store = Store::init();
-        let ok = store.delete(self.alias).is_ok();
-        store.save();
-        if ok {
-            0
-        } else {
-            eprintln!("Error: Alias not found in store");
-            1
+        store.save()?;
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --tui
+//
+// Used to browse and edit every pin in a full-screen manager. Enter jumps to the selected pin
+pub struct Tui;
+
+impl Cmd for Tui {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        match tui::run(term)? {
+            Some(alias) => Box::new(Pin { alias, push: false }).execute(term),
+            None => Ok(Outcome::Done),
         }
     }
 }
 
-// pin --help
+// pin --help [cmd]
+// pin --help --all
 //
-// Used to list all possible commands
+// Used to list all possible commands, or show everything about one. The help itself is in
+// help.rs, which build.rs also renders into the man page and markdown reference
 pub struct Help {
     pub cmd: Option<String>,
 }
 
-impl Help {
-    const NAME: &str = "help";
-    const SHORT: &str = "-h";
-    const USAGE: &str = "pin --help [cmd(optional)]";
-    const DESC: &str = "List all commands for pin or get specifics about one command.";
-}
-
 impl Cmd for Help {
     // Print out help for all commands
-    fn execute(self: Box<Self>) -> i32 {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
         let help = match self.cmd.as_deref() {
-            Some("add") => format!(
-                "{} ({}):\n  Usage: {}\n  Description: {}",
-                Add::NAME,
-                Add::SHORT,
-                Add::USAGE,
-                Add::DESC
-            ),
-            Some("delete") => format!(
-                "{} ({}):\n  Usage: {}\n  Description: {}",
-                Delete::NAME,
-                Delete::SHORT,
-                Delete::USAGE,
-                Delete::DESC
-            ),
-            Some("list") => format!(
-                "{} ({}):\n  Usage: {}\n  Description: {}",
-                List::NAME,
-                List::SHORT,
-                List::USAGE,
-                List::DESC
-            ),
-            Some("update") => format!(
-                "{} ({}):\n  Usage: {}\n  Description: {}",
-                Update::NAME,
-                Update::SHORT,
-                Update::USAGE,
-                Update::DESC
-            ),
-            Some("pin") => format!(
-                "{}:\n  Usage: {}\n  Description: {}",
-                Pin::NAME,
-                Pin::USAGE,
-                Pin::DESC
-            ),
-            Some("help") => format!(
-                "{} ({}):\n  Usage: {}\n  Description: {}",
-                Help::NAME,
-                Help::SHORT,
-                Help::USAGE,
-                Help::DESC
-            ),
-            Some(other) => format!("Unknown command: {}", other),
+            Some("--all") => help::all(),
+            // The generated documents go to stdout, to be redirected into a file
+            Some("--man") => {
+                return Ok(Outcome::Output(
+                    include_str!(concat!(env!("OUT_DIR"), "/pin.1")).to_string(),
+                ));
+            }
+            Some("--markdown") => {
+                return Ok(Outcome::Output(
+                    include_str!(concat!(env!("OUT_DIR"), "/pin.md")).to_string(),
+                ));
+            }
+            Some(name) => match help::find(name) {
+                Some(cmd) => help::text(cmd),
+                None => {
+                    return Err(PinError::Usage(format!(
+                        "no command named {name}, type --help to list all commands."
+                    )));
+                }
+            },
             // Show summary of all commands
             None => {
                 let mut table = Table::new();
 
                 table.add_row(row!["Command", "Description", "Usage"]);
-                table.add_row(row![Pin::NAME, Pin::DESC, Pin::USAGE]);
-                table.add_row(row![Add::NAME, Add::DESC, Add::USAGE]);
-                table.add_row(row![Delete::NAME, Delete::DESC, Delete::USAGE]);
-                table.add_row(row![Help::NAME, Help::DESC, Help::USAGE]);
-                table.add_row(row![List::NAME, List::DESC, List::USAGE]);
-                table.add_row(row![Update::NAME, Update::DESC, Update::USAGE]);
-
-                table.to_string()
+                for cmd in help::COMMANDS {
+                    table.add_row(row![cmd.name, cmd.summary, cmd.usage.join("\n")]);
+                }
+
+                format!(
+                    "{table}\nUse pin --help [cmd] for the details of a command, or pin --help --all \
+                     for every one.\n"
+                )
             }
         };
 
-        Tty::new()
-            .expect("Error: unable to get access to tty.")
-            .write(help)
-            .unwrap();
-        return 0;
+        term.open()?.write(help)?;
+        Ok(Outcome::Done)
     }
 }
 
-// pin --list
+// pin --list [filter] [--columns a,b,...] [--sort key] [--reverse] [--color=when]
+// pin --list [filter] --tree | --group-by tag
 //
-// Used to list all alias path pairs
+// Used to list all alias path pairs, fitted to the terminal, or grouped by directory or tag. See
+// list.rs
 pub struct List {
     pub filter: Option<String>,
-}
-
-impl List {
-    const NAME: &str = "list";
-    const SHORT: &str = "-l";
-    const USAGE: &str = "pin --list [filter pattern](optional)";
-    const DESC: &str = "List all alias-path pairs, filtering paths by an optional pattern";
+    pub columns: Option<Vec<list::Column>>,
+    pub sort: list::Sort,
+    pub reverse: bool,
+    pub color: list::ColorChoice,
+    pub group: Option<list::Group>,
 }
 
 impl Cmd for List {
     // List all current aliases
-    fn execute(self: Box<Self>) -> i32 {
-        let mut table = Table::new();
-        table.add_row(row!["Alias", "Path"]);
-
-        let store = Store::init();
-        let list = store.list_all();
-
-        // If passed a path, filter by it
-        if let Some(path) = self.filter {
-            list.iter()
-                .filter(|(_, v)| v.contains(path.as_str()))
-                .map(|(k, v)| row![k, v])
-                .for_each(|r| {
-                    table.add_row(r);
-                });
-        } else {
-            list.iter().map(|(k, v)| row![k, v]).for_each(|r| {
-                table.add_row(r);
-            });
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let store = Store::init()?;
+        let history = History::load()?;
+
+        // The current directory, both with symlinks resolved and as the shell sees it
+        let current: Vec<String> = [
+            std::env::current_dir()
+                .ok()
+                .and_then(|dir| path_to_string(dir).ok()),
+            std::env::var("PWD").ok(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        let mut rows: Vec<list::Row> = store
+            .iter()
+            .map(|(alias, record)| list::Row::new(alias, record, history.usage(alias), &current))
+            // If passed a path, filter by it
+            .filter(|row| {
+                self.filter
+                    .as_deref()
+                    .is_none_or(|filter| row.matches(filter))
+            })
+            .collect();
+        list::sort(&mut rows, self.sort, self.reverse);
+
+        let mut tty = term.open()?;
+        let width = tty.columns();
+        let color = self.color.enabled(width.is_some());
+        let mut output = match self.group {
+            None => list::table(&rows, self.columns.as_deref(), width, color),
+            Some(list::Group::Path) => list::tree(&rows, width, color),
+            Some(list::Group::Tag) => list::by_tag(&rows, width, color),
+        };
+        if !store.expired().is_empty() {
+            output.push_str(&format!(
+                "Removed expired pins: {}\n",
+                store.expired().join(", ")
+            ));
         }
 
-        Tty::new()
-            .expect("Error: unable to get access to tty.")
-            .write(table.to_string())
-            .unwrap();
-        return 0;
+        tty.write(output)?;
+        Ok(Outcome::Done)
     }
 }
 
@@ -281,169 +695,832 @@ impl Cmd for List {
 // Used to update an alias path pair
 pub struct Update {
     pub alias: String,
-}
-
-impl Update {
-    const NAME: &str = "update";
-    const SHORT: &str = "-u";
-    const USAGE: &str = "pin --update [alias]";
-    const DESC: &str = "Update an alias-path pair";
+    pub force: bool,
 }
 
 impl Cmd for Update {
     // Update a pair, with some tui
-    fn execute(self: Box<Self>) -> i32 {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
         // Check store to make sure the alias is valid
-        let mut store = Store::init();
+        let mut store = Store::lock()?;
 
-        let path = match store.get(&self.alias) {
-            Some(path) => path,
-            None => {
-                Tty::new()
-                    .expect("Error: unable to get tty access.")
-                    .write(
-                        "Error: alias not in store. You can't update an alias that doesn't exist."
-                            .to_string(),
-                    )
-                    .unwrap();
-
-                return 1;
+        let Some(record) = store.get(&self.alias) else {
+            return Err(PinError::AliasNotFound(self.alias));
+        };
+        let (path, policy, note) = (record.path.clone(), record.policy, record.note.clone());
+        check_unlocked(&store, &self.alias, self.force)?;
+
+        // Intercept inputs before they go to the terminal so we can handle them manually
+        let mut tty = term.open()?;
+
+        let options = [("alias", 'a'), ("path", 'p'), ("note", 'n')];
+        let Some(selected) = tty.select(&options)? else {
+            return Ok(Outcome::Done);
+        };
+
+        // Handle choice made
+        let option = format!("{} ({})", options[selected].0, options[selected].1);
+        let (kind, input) = match selected {
+            0 => {
+                let aliases = store.iter().map(|(alias, _)| alias.clone()).collect();
+                let input = LineInput::new(&self.alias).completing(Complete::Words(aliases));
+                ("alias", input)
             }
+            1 => ("path", path_input(&path, policy)),
+            _ => ("note", LineInput::new(&note)),
+        };
+        let Some(input) = tty.read_line(&option, input.with_history(load_history(kind)))? else {
+            return Ok(Outcome::Done);
         };
+        drop(tty);
+        remember(kind, &input);
 
-        // Try get access to writing directly to terminal
-        use std::io::Write;
-        let mut tty = match std::fs::OpenOptions::new().write(true).open("/dev/tty") {
-            Ok(tty) => tty,
-            Err(_) => {
-                eprintln!("Error: unable to get tty access.");
-                return 1;
+        match selected {
+            0 => {
+                // Move the whole record so hooks follow the rename. Nothing is saved unless the
+                // new alias is added
+                if let Some(record) = store.delete(&self.alias)
+                    && !add_confirmed(term, &mut store, input, record, self.force)?
+                {
+                    return Ok(Outcome::Done);
+                }
+            }
+            1 => {
+                let input = parse_for_store(&input, policy)?;
+                if let Some(record) = store.get_mut(&self.alias) {
+                    record.path = input;
+                    record.stamp();
+                }
             }
+            _ => {
+                if let Some(record) = store.get_mut(&self.alias) {
+                    record.note = input.trim().to_string();
+                }
+            }
+        }
+        store.save()?;
+
+        Ok(Outcome::Done)
+    }
+}
+
+// An editor for a pin's path, completing paths and checking the path as it is typed
+pub fn path_input(path: &str, policy: PathPolicy) -> LineInput {
+    LineInput::new(path)
+        .completing(Complete::Paths)
+        .validating(move |text| match parse_for_store(text, policy) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        })
+}
+
+// pin --rename <alias> <new alias> [--force]
+//
+// Used to give a pin a new name, keeping everything else about it
+pub struct Rename {
+    pub alias: String,
+    pub new: String,
+    pub force: bool,
+}
+
+impl Cmd for Rename {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let mut store = Store::lock()?;
+        if store.get(&self.alias).is_none() {
+            return Err(PinError::AliasNotFound(self.alias));
+        }
+        check_unlocked(&store, &self.alias, self.force)?;
+        if self.alias == self.new {
+            return Ok(Outcome::Done);
+        }
+
+        // Nothing is saved unless the new alias is added
+        if let Some(record) = store.delete(&self.alias)
+            && add_confirmed(term, &mut store, self.new, record, self.force)?
+        {
+            store.save()?;
+        }
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --prune [--force]
+//
+// Used to remove pins whose path no longer exists. Locked pins are kept unless forced, and pins
+// added with --allow-missing or whose variables aren't set are left alone
+pub struct Prune {
+    pub force: bool,
+}
+
+impl Cmd for Prune {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let mut store = Store::lock()?;
+
+        let missing: Vec<String> = store
+            .iter()
+            .filter(|(_, record)| !record.policy.allow_missing)
+            .filter(|(_, record)| record.resolve().is_ok_and(|p| !Path::new(&p).exists()))
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        let (missing, kept) = split_locked(&store, missing, self.force);
+
+        if missing.is_empty() {
+            let mut tty = term.open()?;
+            for alias in &kept {
+                tty.write(format!("Keeping \"{alias}\" as it is locked.\n"))?;
+            }
+            tty.write("No pins to prune.\n".to_string())?;
+            return Ok(Outcome::Done);
+        }
+
+        // Every missing pin starts ticked, and any can be unticked to keep it
+        let width = missing.iter().map(|alias| alias.width()).max().unwrap_or(0);
+        let options: Vec<String> = missing
+            .iter()
+            .map(|alias| {
+                let path = store.get(alias).map_or("", |r| r.path.as_str());
+                format!("{alias}{}  {path}", " ".repeat(width - alias.width()))
+            })
+            .collect();
+        let mut tty = term.open()?;
+        for alias in &kept {
+            tty.write(format!("Keeping \"{alias}\" as it is locked.\n"))?;
+        }
+        tty.write("Pins to remove:\n".to_string())?;
+        let all: Vec<usize> = (0..missing.len()).collect();
+        let Some(chosen) = tty.multi_select(&options, &all)? else {
+            return Ok(Outcome::Done);
         };
+        drop(tty);
 
-        // Intercept inputs before they go to the terminal so we can handle them manually
-        enable_raw_mode().expect("Error: unable to get raw tty access.");
-
-        let options = ["alias (a)", "path (p)"];
-        let mut selected = 0;
-
-        // Loop till choice is made or escape
-        loop {
-            // Handle output
-            write!(tty, "\r\x1B[2K").unwrap();
-            for (i, option) in options.iter().enumerate() {
-                if i == selected {
-                    write!(tty, "\x1b[32:5:41m[{}]\x1b[0m", option).unwrap();
-                } else {
-                    write!(tty, " {} ", option).unwrap();
+        for i in chosen {
+            store.delete(&missing[i]);
+        }
+        store.save()?;
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --retarget <old root> <new root> [--force]
+//
+// Used after moving a whole tree of pinned directories, e.g. ~/code to ~/src. Every pin under the
+// old root is pointed at the same place under the new one. Raw pins are left as they are, since
+// they are usually relative to a variable that can be changed instead
+pub struct Retarget {
+    pub from: String,
+    pub to: String,
+    pub force: bool,
+}
+
+impl Cmd for Retarget {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        // The old root has usually gone by now
+        let policy = PathPolicy {
+            allow_missing: true,
+            ..Default::default()
+        };
+        let from = path_to_string(parse_path(&self.from, policy)?)?;
+        let to = path_to_string(parse_path(&self.to, policy)?)?;
+
+        let mut store = Store::lock()?;
+        // Path::strip_prefix compares whole directories, so /code doesn't take in /codex
+        let moved: Vec<(String, String)> = store
+            .iter()
+            .filter(|(_, record)| !record.policy.raw)
+            .filter_map(|(alias, record)| {
+                let rest = Path::new(&record.path).strip_prefix(&from).ok()?;
+                // Collecting the components drops the trailing / when the pin is the root itself
+                let path = Path::new(&to).join(rest).components().collect();
+                Some((alias.clone(), path_to_string(path).ok()?))
+            })
+            .collect();
+        if moved.is_empty() {
+            return Err(PinError::InvalidPath(format!("no pins are under {from}.")));
+        }
+
+        let (moved, kept): (Vec<_>, Vec<_>) = moved.into_iter().partition(|(alias, _)| {
+            self.force || store.get(alias).is_none_or(|record| !record.locked)
+        });
+        let kept: Vec<String> = kept.into_iter().map(|(alias, _)| alias).collect();
+        if moved.is_empty() {
+            return Err(PinError::Locked(kept.join(", ")));
+        }
+
+        let rows = moved
+            .iter()
+            .filter_map(|(alias, path)| {
+                Some(vec![
+                    alias.clone(),
+                    store.get(alias)?.path.clone(),
+                    path.clone(),
+                ])
+            })
+            .collect();
+        let question = format!("Retarget {} pin(s)?", moved.len());
+        if !confirm_bulk(term, &["Alias", "Path", "New path"], rows, &kept, question)? {
+            return Ok(Outcome::Done);
+        }
+
+        for (alias, path) in moved {
+            if let Some(record) = store.get_mut(&alias) {
+                record.path = path;
+                record.stamp();
+            }
+        }
+        store.save()?;
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --lock <alias>
+// pin --unlock <alias>
+//
+// Used to protect important pins from being overwritten, renamed or deleted by accident
+pub struct Lock {
+    pub alias: String,
+    pub lock: bool,
+}
+
+impl Cmd for Lock {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let mut store = Store::lock()?;
+        let aliases = match select(&store, &self.alias)? {
+            None => vec![self.alias],
+            Some(aliases) => {
+                let rows = aliases
+                    .iter()
+                    .filter_map(|alias| Some(vec![alias.clone(), store.get(alias)?.path.clone()]))
+                    .collect();
+                let verb = if self.lock { "Lock" } else { "Unlock" };
+                let question = format!("{verb} {} pin(s)?", aliases.len());
+                if !confirm_bulk(term, &["Alias", "Path"], rows, &[], question)? {
+                    return Ok(Outcome::Done);
                 }
+                aliases
             }
-            tty.flush().unwrap();
-
-            // Handle input
-            if let Event::Key(key_event) = event::read().expect("Error: unable to read key events.")
-            {
-                match key_event.code {
-                    KeyCode::Enter => break,
-                    KeyCode::Char('a') | KeyCode::Char('A') => {
-                        selected = 0;
-                        break;
-                    }
-                    KeyCode::Char('p') | KeyCode::Char('P') => {
-                        selected = 1;
-                        break;
-                    }
-                    KeyCode::Left | KeyCode::Char('h') => selected = 0,
-                    KeyCode::Right | KeyCode::Char('l') => selected = 1,
-                    KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
-                        write!(tty, "\r\n").unwrap();
-                        disable_raw_mode().unwrap();
-                        return 0;
-                    }
-                    _ => {}
+        };
+
+        for alias in aliases {
+            let Some(record) = store.get_mut(&alias) else {
+                return Err(PinError::AliasNotFound(alias));
+            };
+            record.locked = self.lock;
+        }
+        store.save()?;
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --hook <alias> [add <cmd> | env <NAME[=value]> | rm <n> | clear]
+//
+// Used to view and edit the commands run and variables exported after jumping to an alias. Any
+// change means the alias has to be trusted again before they run
+pub struct Hook {
+    pub alias: String,
+    pub action: HookAction,
+}
+
+pub enum HookAction {
+    Show,
+    Add(String),
+    // Set a variable, or remove it when there's no value
+    Env(String, Option<String>),
+    Remove(usize),
+    Clear,
+}
+
+impl Cmd for Hook {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let mut store = Store::lock()?;
+        let Some(record) = store.get_mut(&self.alias) else {
+            return Err(PinError::AliasNotFound(self.alias));
+        };
+
+        match self.action {
+            HookAction::Show => {
+                let msg = describe_hooks(&self.alias, record);
+                term.open()?.write(msg)?;
+                return Ok(Outcome::Done);
+            }
+            HookAction::Add(cmd) => record.hooks.push(cmd),
+            HookAction::Env(name, value) => {
+                if !directive::valid_env_name(&name) {
+                    return Err(PinError::Usage(format!(
+                        "\"{name}\" is not a valid environment variable name."
+                    )));
+                }
+                match value {
+                    Some(value) => record.env.insert(name, value),
+                    None => record.env.remove(&name),
+                };
+            }
+            HookAction::Remove(n) => {
+                if n == 0 || n > record.hooks.len() {
+                    return Err(PinError::Usage(format!(
+                        "\"{}\" has no hook number {n}.",
+                        self.alias
+                    )));
                 }
+                record.hooks.remove(n - 1);
+            }
+            HookAction::Clear => {
+                record.hooks.clear();
+                record.env.clear();
             }
         }
-        write!(tty, "\r\n").unwrap();
 
-        // Handle choice made
-        let option = options[selected];
-        let mut input = if selected == 0 {
-            self.alias.clone()
+        let needs_trust = record.has_hooks();
+        record.trusted = false;
+        store.save()?;
+
+        if needs_trust {
+            eprintln!(
+                "Hooks changed. Run \"pin --trust {}\" to allow them to run.",
+                self.alias
+            );
+        }
+        Ok(Outcome::Done)
+    }
+}
+
+// Human readable summary of an alias' hooks
+fn describe_hooks(alias: &str, record: &PinAlias) -> String {
+    if !record.has_hooks() {
+        return format!("\"{alias}\" has no hooks.\n");
+    }
+
+    let mut msg = format!(
+        "Hooks for \"{alias}\" ({}):\n",
+        if record.trusted {
+            "trusted"
         } else {
-            path.clone()
+            "not trusted"
+        }
+    );
+    for (i, hook) in record.hooks.iter().enumerate() {
+        msg += &format!("  {}. {}\n", i + 1, hook);
+    }
+    if !record.env.is_empty() {
+        msg += "Environment:\n";
+        for (name, value) in &record.env {
+            msg += &format!("  {name}={value}\n");
+        }
+    }
+    msg
+}
+
+// pin --trust <alias>
+// pin --untrust <alias>
+//
+// Used to allow, or stop, an alias' hooks running when it is jumped to
+pub struct Trust {
+    pub alias: String,
+    pub trust: bool,
+}
+
+impl Cmd for Trust {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let mut store = Store::lock()?;
+        let Some(record) = store.get_mut(&self.alias) else {
+            return Err(PinError::AliasNotFound(self.alias));
         };
-        let mut cursor_position = input.len();
-        let offset = option.len() + 3;
-
-        loop {
-            // Handle output
-            write!(tty, "\r\x1B[2K").unwrap();
-            write!(tty, "{}: {}", option, input).unwrap();
-            write!(tty, "\r\x1B[{}G", offset + cursor_position).unwrap();
-            tty.flush().unwrap();
-
-            //Handle Input
-            if let Event::Key(key_event) = event::read().expect("Error: unable to read key events.")
-            {
-                match key_event.code {
-                    KeyCode::Enter => break,
-                    KeyCode::Esc => {
-                        write!(tty, "\r\n").unwrap();
-                        disable_raw_mode().unwrap();
-                        return 0;
-                    }
-                    KeyCode::Backspace => {
-                        if cursor_position > 0 {
-                            input.remove(cursor_position - 1);
-                            cursor_position -= 1;
-                        }
-                    }
-                    KeyCode::Left => {
-                        if cursor_position > 0 {
-                            cursor_position -= 1;
-                        }
-                    }
-                    KeyCode::Right => {
-                        if cursor_position < input.len() {
-                            cursor_position += 1;
-                        }
-                    }
-                    KeyCode::Char(char) => {
-                        input.insert(cursor_position, char);
-                        cursor_position += 1;
+
+        // Show exactly what will run before asking
+        if self.trust {
+            let mut tty = term.open()?;
+            tty.write(describe_hooks(&self.alias, record))?;
+            if !record.has_hooks() || !tty.confirm("Trust these hooks?")? {
+                return Ok(Outcome::Done);
+            }
+        }
+
+        record.trusted = self.trust;
+        store.save()?;
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --doctor [--fix]
+//
+// Used to check every pin still points somewhere, and find the ones that moved
+pub struct Doctor {
+    pub fix: bool,
+}
+
+impl Cmd for Doctor {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let search = heal::Search::from_config(&Config::load()?)?;
+        let mut store = Store::lock()?;
+
+        let mut table = Table::new();
+        table.add_row(row!["Alias", "Path", "Status"]);
+        let mut moved = Vec::new();
+        let mut unstamped = Vec::new();
+        let mut changed = false;
+
+        for (alias, record) in store.iter() {
+            let status = match record.resolve() {
+                Err(e) => e.to_string(),
+                Ok(path) if Path::new(&path).exists() => {
+                    if record.identity.is_none() {
+                        unstamped.push(alias.clone());
                     }
-                    _ => {}
+                    "ok".to_string()
                 }
+                Ok(_) if record.policy.raw => "missing".to_string(),
+                Ok(_) => match search.find(record) {
+                    Some(found) => {
+                        let path = path_to_string(found.path)?;
+                        let status = match found.sure {
+                            true => format!("moved to {path}"),
+                            false => format!("maybe moved to {path} (same inode only)"),
+                        };
+                        moved.push((alias.clone(), path, found.sure));
+                        status
+                    }
+                    None => "missing".to_string(),
+                },
+            };
+            table.add_row(row![alias, record.path, status]);
+        }
+
+        // Pins from before identities were recorded get one now
+        for alias in unstamped {
+            if let Some(record) = store.get_mut(&alias) {
+                record.stamp();
+                changed |= record.identity.is_some();
             }
         }
-        write!(tty, "\r\n").unwrap();
 
-        disable_raw_mode().unwrap();
+        let mut tty = term.open()?;
+        tty.write(table.to_string())?;
 
-        if selected == 0 {
-            if let Err(_) = store.delete(self.alias) {
-                eprintln!("Error: alias not found in store.");
-                return 1;
+        let (sure, unsure): (Vec<_>, Vec<_>) = moved.into_iter().partition(|(.., sure)| *sure);
+        let mut update = Vec::new();
+        if !sure.is_empty()
+            && (self.fix
+                || search.auto
+                || tty.confirm(&format!("Update {} moved pin(s)?", sure.len()))?)
+        {
+            update.extend(sure);
+        }
+        // Inodes are reused, so a match on one alone is asked about even with --fix
+        if !unsure.is_empty()
+            && tty.confirm(&format!(
+                "Update {} pin(s) matched only by inode?",
+                unsure.len()
+            ))?
+        {
+            update.extend(unsure);
+        }
+        for (alias, found, _) in update {
+            if let Some(record) = store.get_mut(&alias) {
+                record.path = found;
+                record.stamp();
+                changed = true;
             }
-            let _ = store.add(input, path);
-            store.save();
-        } else {
-            let input = match parse_path(&input) {
-                Ok(path) => path
-                    .into_os_string()
-                    .into_string()
-                    .expect("Error: unable to parse path as string."),
-                Err(msg) => {
-                    eprintln!("{msg}");
-                    return 1;
+        }
+        drop(tty);
+
+        if changed {
+            store.save()?;
+        }
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --tag <alias> <tags...>
+// pin --untag <alias> <tags...>
+//
+// Used to tag pins so they can be worked on together, e.g. pin --exec @work -- git pull
+pub struct Tag {
+    pub alias: String,
+    pub tags: Vec<String>,
+    pub remove: bool,
+}
+
+impl Cmd for Tag {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        check_tags(&self.tags)?;
+
+        let mut store = Store::lock()?;
+        let aliases = match select(&store, &self.alias)? {
+            None => vec![self.alias],
+            Some(aliases) => {
+                let rows = aliases
+                    .iter()
+                    .filter_map(|alias| {
+                        let record = store.get(alias)?;
+                        Some(vec![
+                            alias.clone(),
+                            record.path.clone(),
+                            record.tags.join(", "),
+                        ])
+                    })
+                    .collect();
+                let tags = self.tags.join(", ");
+                let question = if self.remove {
+                    format!("Remove {tags} from {} pin(s)?", aliases.len())
+                } else {
+                    format!("Tag {} pin(s) with {tags}?", aliases.len())
+                };
+                if !confirm_bulk(term, &["Alias", "Path", "Tags"], rows, &[], question)? {
+                    return Ok(Outcome::Done);
                 }
+                aliases
+            }
+        };
+
+        for alias in aliases {
+            let Some(record) = store.get_mut(&alias) else {
+                return Err(PinError::AliasNotFound(alias));
+            };
+            if self.remove {
+                record.tags.retain(|t| !self.tags.contains(t));
+            } else {
+                record.tags.extend(self.tags.iter().cloned());
+                record.tags.sort();
+                record.tags.dedup();
+            }
+        }
+        store.save()?;
+        Ok(Outcome::Done)
+    }
+}
+
+// Tags are written as lists separated by spaces and commas, and @ marks a tag in place of an alias
+pub fn check_tags(tags: &[String]) -> Result<(), PinError> {
+    match tags
+        .iter()
+        .find(|t| t.is_empty() || t.starts_with('@') || t.contains([' ', ',']))
+    {
+        Some(bad) => Err(PinError::Usage(format!(
+            "\"{bad}\" can't be used as a tag. Tags can't contain spaces or commas, or start with @."
+        ))),
+        None => Ok(()),
+    }
+}
+
+// pin --note <alias> [text...]
+//
+// Used to say what a pin is for. Without text the current note is shown, and an empty note
+// removes it
+pub struct Note {
+    pub alias: String,
+    pub text: Option<String>,
+}
+
+impl Cmd for Note {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let Some(text) = self.text else {
+            let store = Store::init()?;
+            let Some(record) = store.get(&self.alias) else {
+                return Err(PinError::AliasNotFound(self.alias));
             };
-            let _ = store.add(self.alias, input);
-            store.save();
+            if !record.note.is_empty() {
+                term.open()?.write(format!("{}\n", record.note))?;
+            }
+            return Ok(Outcome::Done);
+        };
+
+        let mut store = Store::lock()?;
+        let Some(record) = store.get_mut(&self.alias) else {
+            return Err(PinError::AliasNotFound(self.alias));
+        };
+        record.note = text.trim().to_string();
+
+        store.save()?;
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --search <text...>
+//
+// Used to find a pin when the alias has been forgotten. Matches aliases, paths, tags and notes,
+// ignoring case, with matches in aliases listed first and matches in notes last
+pub struct Search {
+    pub text: String,
+}
+
+impl Cmd for Search {
+    fn execute(self: Box<Self>, term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let store = Store::init()?;
+        let matches = store.search(&self.text);
+
+        if matches.is_empty() {
+            eprintln!("No pins match \"{}\".", self.text);
+            return Ok(Outcome::Done);
+        }
+
+        let mut table = Table::new();
+        table.add_row(row!["Alias", "Path", "Tags", "Note"]);
+        for (alias, record) in matches {
+            table.add_row(row![
+                alias,
+                record.path,
+                record.tags.join(", "),
+                record.note
+            ]);
+        }
+
+        term.open()?.write(table.to_string())?;
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --exec <alias|@tag> [-j n] -- <cmd...>
+// pin <alias|@tag> -- <cmd...>
+//
+// Used to run a command inside a pinned directory without changing the shell's directory. With
+// @tag the command runs in every pin with that tag
+pub struct Exec {
+    pub target: String,
+    pub jobs: usize,
+    pub cmd: Vec<String>,
+}
+
+impl Cmd for Exec {
+    fn execute(self: Box<Self>, _term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let store = Store::init()?;
+
+        let Some(tag) = self.target.strip_prefix('@') else {
+            let Some(record) = store.get(&self.target) else {
+                return Err(PinError::AliasNotFound(self.target));
+            };
+            let dir = exec_dir(&self.target, record)?;
+            return exec::run_one(&dir, &self.cmd).map(Outcome::Exited);
+        };
+
+        let mut targets = Vec::new();
+        for (alias, record) in store
+            .iter()
+            .filter(|(_, r)| r.tags.iter().any(|t| t == tag))
+        {
+            // One broken pin shouldn't stop the rest
+            match exec_dir(alias, record) {
+                Ok(dir) => targets.push((alias.clone(), dir)),
+                Err(e) => eprintln!("[{alias}] {e}"),
+            }
+        }
+        if targets.is_empty() {
+            return Err(PinError::Usage(format!(
+                "no usable pins are tagged \"{tag}\"."
+            )));
         }
 
-        return 0;
+        Ok(Outcome::Exited(exec::run_many(
+            targets, &self.cmd, self.jobs,
+        )))
+    }
+}
+
+// The directory to run a command in for a pin. File pins use the directory they're in
+fn exec_dir(alias: &str, record: &PinAlias) -> Result<String, PinError> {
+    let path = record.resolve()?;
+    let dir = if record.file {
+        Path::new(&path)
+            .parent()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or(path)
+    } else {
+        path
+    };
+
+    if !Path::new(&dir).is_dir() {
+        return Err(PinError::PathMissing {
+            alias: alias.to_string(),
+            path: dir,
+        });
+    }
+    Ok(dir)
+}
+
+// pin --export <file>
+//
+// Used to copy every pin to another store, with pin --import
+pub struct Export {
+    pub file: String,
+}
+
+impl Cmd for Export {
+    fn execute(self: Box<Self>, _term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let store = Store::init()?;
+        let file = std::fs::File::create(&self.file)
+            .map_err(|e| PinError::Transfer(format!("unable to create {}: {e}", self.file)))?;
+
+        let count = transfer::export(&store, file)?;
+        eprintln!("Exported {count} pin(s) to {}.", self.file);
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --import <file> [--replace] [--force]
+//
+// Used to add the pins from pin --export. Aliases already in the store are kept unless --replace
+// is given, and locked ones need --force as well
+pub struct Import {
+    pub file: String,
+    pub replace: bool,
+    pub force: bool,
+}
+
+impl Cmd for Import {
+    fn execute(self: Box<Self>, _term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let text = std::fs::read_to_string(&self.file)
+            .map_err(|e| PinError::Transfer(format!("unable to read {}: {e}", self.file)))?;
+        let conflict = if self.replace {
+            Conflict::Replace
+        } else {
+            Conflict::Keep
+        };
+
+        let mut store = Store::lock()?;
+        let imported = transfer::import(&mut store, &text, conflict, self.force)?;
+        store.save()?;
+
+        eprintln!(
+            "Added {} pin(s) and replaced {}.",
+            imported.added.len(),
+            imported.replaced.len()
+        );
+        if !imported.skipped.is_empty() {
+            eprintln!(
+                "Already pinned, so kept: {}. Use --replace to overwrite them.",
+                imported.skipped.join(", ")
+            );
+        }
+        if !imported.locked.is_empty() {
+            eprintln!(
+                "Locked, so kept: {}. Use --force as well to overwrite them.",
+                imported.locked.join(", ")
+            );
+        }
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --migrate-store --to <backend>
+//
+// Used to move every pin to another kind of storage, e.g. to edit them by hand as toml. The old
+// store is left where it was in case something goes wrong
+pub struct MigrateStore {
+    pub to: Backend,
+}
+
+impl Cmd for MigrateStore {
+    fn execute(self: Box<Self>, _term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let from = Backend::configured()?;
+        if from == self.to {
+            eprintln!("Pins are already stored with {}.", self.to.name());
+            return Ok(Outcome::Done);
+        }
+
+        // Held until the config points at the new store, so no pins are written to the old one
+        // in between
+        let store = Store::lock()?;
+        let pins: Pins = store
+            .iter()
+            .filter(|(_, record)| !record.session)
+            .map(|(alias, record)| (alias.clone(), record.clone()))
+            .collect();
+
+        self.to.open()?.save(&pins)?;
+        Config::set("store", self.to.name())?;
+        drop(store);
+
+        eprintln!(
+            "Moved {} pin(s) to {}. The old store is still at {}.",
+            pins.len(),
+            self.to.path()?.display(),
+            from.path()?.display()
+        );
+        Ok(Outcome::Done)
+    }
+}
+
+// pin --init <shell>
+//
+// Used to print the wrapper function for a shell
+pub struct Init {
+    pub shell: String,
+}
+
+impl Cmd for Init {
+    // Write the wrapper to stdout so it can be eval'd
+    fn execute(self: Box<Self>, _term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        let Some(shell) = Shell::parse(&self.shell) else {
+            return Err(PinError::Usage(format!(
+                "unknown shell \"{}\". Supported shells are {}.",
+                self.shell,
+                Shell::NAMES
+            )));
+        };
+
+        // Fall back to finding _pin on $PATH if we can't tell where we are
+        let exe = std::env::current_exe()
+            .map(|exe| exe.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "_pin".to_string());
+        Ok(Outcome::Output(shell.snippet(&exe)))
     }
 }
 
@@ -455,10 +1532,315 @@ pub struct ParseErr {
 }
 
 impl Cmd for ParseErr {
-    fn execute(self: Box<Self>) -> i32 {
-        eprintln!("{}", self.msg);
-        return 1;
+    fn execute(self: Box<Self>, _term: &mut dyn Terminal) -> Result<Outcome, PinError> {
+        Err(PinError::Usage(self.msg))
     }
 }
 
 //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crossterm::event::KeyCode;
+    use tempfile::TempDir;
+
+    use crate::store::TEST_DIR;
+    use crate::tty::Script;
+
+    // A store of its own for one test, with a directory to pin. Dropping it deletes both
+    struct Sandbox {
+        dir: TempDir,
+    }
+
+    impl Sandbox {
+        fn new() -> Sandbox {
+            let dir = TempDir::new().unwrap();
+            std::fs::create_dir(dir.path().join("pins")).unwrap();
+            TEST_DIR.set(Some(dir.path().join(".pin")));
+            Sandbox { dir }
+        }
+
+        // Make a directory to pin, returning its path
+        fn mkdir(&self, name: &str) -> String {
+            let path = self.dir.path().join("pins").join(name);
+            std::fs::create_dir(&path).unwrap();
+            path_to_string(path.canonicalize().unwrap()).unwrap()
+        }
+
+        fn path_of(&self, alias: &str) -> Option<String> {
+            Store::init().unwrap().get(alias).map(|r| r.path.clone())
+        }
+    }
+
+    impl Drop for Sandbox {
+        fn drop(&mut self) {
+            TEST_DIR.set(None);
+        }
+    }
+
+    fn add(alias: &str, path: &str) -> Box<Add> {
+        Box::new(Add {
+            alias: alias.to_string(),
+            path: path.to_string(),
+            policy: PathPolicy::default(),
+            note: String::new(),
+            expires: None,
+            session: false,
+            force: false,
+        })
+    }
+
+    fn update(alias: &str) -> Box<Update> {
+        Box::new(Update {
+            alias: alias.to_string(),
+            force: false,
+        })
+    }
+
+    #[test]
+    fn adding_a_new_alias_asks_nothing() {
+        let sandbox = Sandbox::new();
+        let api = sandbox.mkdir("api");
+
+        let mut script = Script::new();
+        assert!(matches!(
+            add("api", &api).execute(&mut script),
+            Ok(Outcome::Done)
+        ));
+
+        assert!(script.output.is_empty());
+        assert_eq!(sandbox.path_of("api"), Some(api));
+    }
+
+    #[test]
+    fn overwriting_an_alias_is_confirmed() {
+        let sandbox = Sandbox::new();
+        let (old, new) = (sandbox.mkdir("old"), sandbox.mkdir("new"));
+        add("api", &old).execute(&mut Script::new()).unwrap();
+
+        let mut script = Script::new().key(KeyCode::Char('y'));
+        add("api", &new).execute(&mut script).unwrap();
+
+        assert!(
+            script
+                .output
+                .contains(&format!("overwrite existing alias to {old}"))
+        );
+        assert!(script.finished());
+        assert_eq!(sandbox.path_of("api"), Some(new));
+    }
+
+    #[test]
+    fn declining_an_overwrite_keeps_the_alias() {
+        let sandbox = Sandbox::new();
+        let (old, new) = (sandbox.mkdir("old"), sandbox.mkdir("new"));
+        add("api", &old).execute(&mut Script::new()).unwrap();
+
+        for key in [KeyCode::Char('n'), KeyCode::Esc] {
+            let mut script = Script::new().key(key);
+            assert!(matches!(
+                add("api", &new).execute(&mut script),
+                Ok(Outcome::Done)
+            ));
+            assert_eq!(sandbox.path_of("api"), Some(old.clone()));
+        }
+    }
+
+    #[test]
+    fn overwriting_a_locked_alias_needs_force() {
+        let sandbox = Sandbox::new();
+        let (old, new) = (sandbox.mkdir("old"), sandbox.mkdir("new"));
+        add("api", &old).execute(&mut Script::new()).unwrap();
+        let lock = Lock {
+            alias: "api".to_string(),
+            lock: true,
+        };
+        Box::new(lock).execute(&mut Script::new()).unwrap();
+
+        let err = add("api", &new).execute(&mut Script::new()).unwrap_err();
+        assert_eq!(err.code(), 22);
+
+        let mut forced = add("api", &new);
+        forced.force = true;
+        forced
+            .execute(&mut Script::new().key(KeyCode::Char('y')))
+            .unwrap();
+        assert_eq!(sandbox.path_of("api"), Some(new));
+    }
+
+    #[test]
+    fn cancelling_a_delete_keeps_the_alias() {
+        let sandbox = Sandbox::new();
+        let api = sandbox.mkdir("api");
+        add("api", &api).execute(&mut Script::new()).unwrap();
+
+        let delete = |script: &mut Script| {
+            let delete = Delete {
+                alias: "api".to_string(),
+                force: false,
+            };
+            Box::new(delete).execute(script)
+        };
+
+        let mut script = Script::new().key(KeyCode::Char('n'));
+        assert!(matches!(delete(&mut script), Ok(Outcome::Done)));
+        assert!(script.output.contains("Delete \"api\"?"));
+        assert_eq!(sandbox.path_of("api"), Some(api));
+
+        let err = delete(&mut Script::new().ctrl('c')).unwrap_err();
+        assert_eq!(err.code(), 130);
+        assert!(sandbox.path_of("api").is_some());
+
+        delete(&mut Script::new().key(KeyCode::Char('y'))).unwrap();
+        assert_eq!(sandbox.path_of("api"), None);
+    }
+
+    #[test]
+    fn deleting_a_missing_alias_asks_nothing() {
+        let _sandbox = Sandbox::new();
+        let delete = Delete {
+            alias: "nope".to_string(),
+            force: false,
+        };
+
+        let err = Box::new(delete).execute(&mut Script::new()).unwrap_err();
+        assert_eq!(err.code(), 11);
+    }
+
+    #[test]
+    fn update_renames_an_alias() {
+        let sandbox = Sandbox::new();
+        let api = sandbox.mkdir("api");
+        add("api", &api).execute(&mut Script::new()).unwrap();
+
+        // Pick the alias, clear the old one and type the new one
+        let mut script = Script::new()
+            .key(KeyCode::Char('a'))
+            .ctrl('u')
+            .text("backend")
+            .key(KeyCode::Enter);
+        update("api").execute(&mut script).unwrap();
+
+        assert!(script.finished());
+        assert_eq!(sandbox.path_of("api"), None);
+        assert_eq!(sandbox.path_of("backend"), Some(api));
+    }
+
+    #[test]
+    fn update_changes_the_path() {
+        let sandbox = Sandbox::new();
+        let (old, new) = (sandbox.mkdir("old"), sandbox.mkdir("new"));
+        add("api", &old).execute(&mut Script::new()).unwrap();
+
+        // Enter on an invalid path is refused, so the first Enter does nothing
+        let mut script = Script::new()
+            .key(KeyCode::Right)
+            .key(KeyCode::Enter)
+            .ctrl('u')
+            .text(&format!("{new}/missing"))
+            .key(KeyCode::Enter)
+            .ctrl('w')
+            .key(KeyCode::Enter);
+        update("api").execute(&mut script).unwrap();
+
+        assert!(script.output.contains("path (p)"));
+        assert!(script.finished());
+        assert_eq!(sandbox.path_of("api"), Some(new));
+    }
+
+    #[test]
+    fn update_sets_the_note() {
+        let sandbox = Sandbox::new();
+        let api = sandbox.mkdir("api");
+        add("api", &api).execute(&mut Script::new()).unwrap();
+
+        let mut script = Script::new()
+            .key(KeyCode::Char('n'))
+            .text(" the backend ")
+            .key(KeyCode::Enter);
+        update("api").execute(&mut script).unwrap();
+
+        let store = Store::init().unwrap();
+        assert_eq!(store.get("api").unwrap().note, "the backend");
+    }
+
+    #[test]
+    fn backing_out_of_an_update_changes_nothing() {
+        let sandbox = Sandbox::new();
+        let api = sandbox.mkdir("api");
+        add("api", &api).execute(&mut Script::new()).unwrap();
+
+        // Out of the menu, then out of the line editor
+        update("api")
+            .execute(&mut Script::new().key(KeyCode::Esc))
+            .unwrap();
+        let mut script = Script::new()
+            .key(KeyCode::Char('a'))
+            .text("-new")
+            .key(KeyCode::Esc);
+        update("api").execute(&mut script).unwrap();
+
+        assert!(script.finished());
+        assert_eq!(sandbox.path_of("api"), Some(api));
+        assert_eq!(sandbox.path_of("api-new"), None);
+    }
+
+    #[test]
+    fn the_manager_deletes_and_undoes() {
+        let sandbox = Sandbox::new();
+        let (api, web) = (sandbox.mkdir("api"), sandbox.mkdir("web"));
+        add("api", &api).execute(&mut Script::new()).unwrap();
+        add("web", &web).execute(&mut Script::new()).unwrap();
+
+        // Down to web, delete it, then quit
+        let mut script = Script::new().text("jdyq");
+        assert!(matches!(
+            Box::new(Tui).execute(&mut script),
+            Ok(Outcome::Done)
+        ));
+        assert!(script.finished());
+        assert!(script.output.contains("pin: 2 of 2 pins"));
+        assert!(script.output.contains("Delete \"web\"? (y/n)"));
+        assert_eq!(sandbox.path_of("web"), None);
+
+        // The manager forgets its undo history when it closes, so undo straight away this time
+        let mut script = Script::new().text("dyuq");
+        Box::new(Tui).execute(&mut script).unwrap();
+        assert!(script.output.contains("Undone."));
+        assert_eq!(sandbox.path_of("api"), Some(api));
+    }
+
+    #[test]
+    fn renaming_onto_an_alias_is_confirmed() {
+        let sandbox = Sandbox::new();
+        let (api, web) = (sandbox.mkdir("api"), sandbox.mkdir("web"));
+        add("api", &api).execute(&mut Script::new()).unwrap();
+        add("web", &web).execute(&mut Script::new()).unwrap();
+
+        // Rename api to web, then decline to overwrite web
+        let rename = || {
+            Script::new()
+                .key(KeyCode::Char('a'))
+                .ctrl('u')
+                .text("web")
+                .key(KeyCode::Enter)
+        };
+        let mut script = rename().key(KeyCode::Char('n'));
+        update("api").execute(&mut script).unwrap();
+        assert!(script.finished());
+        assert!(
+            script
+                .output
+                .contains(&format!("overwrite existing alias to {web}"))
+        );
+        assert_eq!(sandbox.path_of("api"), Some(api.clone()));
+        assert_eq!(sandbox.path_of("web"), Some(web));
+
+        let mut script = rename().key(KeyCode::Char('y'));
+        update("api").execute(&mut script).unwrap();
+        assert_eq!(sandbox.path_of("api"), None);
+        assert_eq!(sandbox.path_of("web"), Some(api));
+    }
+}
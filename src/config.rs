@@ -0,0 +1,122 @@
+// User configuration
+//
+// Read from ~/.pin/config, one `key = value` per line. Blank lines and lines starting with # are
+// ignored. A missing file is the same as an empty one, so every key needs a sensible default.
+//
+//   # Where to look for pinned directories that have moved
+//   search_roots = ~/code, ~/work
+//   search_depth = 4
+//   search_budget = 200
+//   auto_heal = false
+//   store = toml
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+
+use crate::error::PinError;
+use crate::store::{pin_dir, write_atomic};
+
+pub struct Config {
+    values: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn load() -> Result<Config, PinError> {
+        Config::parse(&read()?)
+    }
+
+    // Set a key in the config file, replacing the line it is on so comments and the rest of the
+    // file are kept as they are
+    pub fn set(key: &str, value: &str) -> Result<(), PinError> {
+        let text = read()?;
+        Config::parse(&text)?;
+
+        let line = format!("{key} = {value}");
+        let mut found = false;
+        let mut lines: Vec<String> = text
+            .lines()
+            .map(|old| match old.split_once('=') {
+                Some((name, _)) if name.trim() == key && !old.trim().starts_with('#') => {
+                    found = true;
+                    line.clone()
+                }
+                _ => old.to_string(),
+            })
+            .collect();
+        if !found {
+            lines.push(line);
+        }
+
+        write_atomic(
+            &pin_dir()?.join("config"),
+            (lines.join("\n") + "\n").as_bytes(),
+        )
+    }
+
+    pub fn parse(text: &str) -> Result<Config, PinError> {
+        let mut values = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(PinError::Config(format!(
+                    "line {} should look like \"key = value\".",
+                    i + 1
+                )));
+            };
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+
+        Ok(Config { values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn get_bool(&self, key: &str, default: bool) -> Result<bool, PinError> {
+        match self.get(key) {
+            None => Ok(default),
+            Some("true" | "yes" | "on") => Ok(true),
+            Some("false" | "no" | "off") => Ok(false),
+            Some(other) => Err(PinError::Config(format!(
+                "{key} should be true or false, not \"{other}\"."
+            ))),
+        }
+    }
+
+    pub fn get_usize(&self, key: &str, default: usize) -> Result<usize, PinError> {
+        match self.get(key) {
+            None => Ok(default),
+            Some(value) => value.parse().map_err(|_| {
+                PinError::Config(format!("{key} should be a number, not \"{value}\"."))
+            }),
+        }
+    }
+
+    // A comma separated list
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        self.get(key)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+// The config file, which is empty if it doesn't exist
+fn read() -> Result<String, PinError> {
+    match std::fs::read_to_string(pin_dir()?.join("config")) {
+        Ok(text) => Ok(text),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(PinError::StoreIo(e)),
+    }
+}
@@ -0,0 +1,149 @@
+// Directives sent from _pin to the shell wrapper
+//
+// The original protocol only had exit code 2 with a path on stdout. A wrapper that sets
+// PIN_PROTOCOL to the supported version instead gets exit code 3 and a list of directives, one per
+// line:
+//
+//   pin-directives 1
+//   cd<TAB>/home/me/code
+//   setenv<TAB>NAME<TAB>value
+//   open<TAB>/home/me/.ssh/config<TAB>
+//
+// Arguments are percent-encoded so they can't contain the separators, and so each shell can
+// decode them without interpreting anything else. When PIN_PROTOCOL isn't set the old exit code
+// mode is used, and directives it can't express are reported on stderr.
+
+use std::env;
+
+use crate::error::{EXIT_CD, EXIT_OK, EXIT_OPEN, PinError};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const EXIT_DIRECTIVES: i32 = 3;
+
+/// Something for the shell wrapper to do after _pin exits.
+#[derive(Debug, PartialEq)]
+pub enum Directive {
+    /// Change directory.
+    Cd(String),
+    /// Change directory, pushing the old one on the directory stack.
+    Pushd(String),
+    /// Export an environment variable.
+    SetEnv(String, String),
+    /// Evaluate a command in the user's shell.
+    Run(String),
+    /// Print a message.
+    Echo(String),
+    /// Open a file, with the given command or else `$VISUAL` or `$EDITOR`.
+    Open(String, Option<String>),
+}
+
+impl Directive {
+    // Render the directive as a protocol line
+    fn render(&self) -> String {
+        match self {
+            Directive::Cd(path) => format!("cd\t{}", encode(path)),
+            Directive::Pushd(path) => format!("pushd\t{}", encode(path)),
+            Directive::SetEnv(name, value) => {
+                format!("setenv\t{}\t{}", encode(name), encode(value))
+            }
+            Directive::Run(cmd) => format!("run\t{}", encode(cmd)),
+            Directive::Echo(msg) => format!("echo\t{}", encode(msg)),
+            Directive::Open(path, opener) => format!(
+                "open\t{}\t{}",
+                encode(path),
+                encode(opener.as_deref().unwrap_or_default())
+            ),
+        }
+    }
+}
+
+// Whether the wrapper understands directives
+fn directive_mode() -> bool {
+    env::var("PIN_PROTOCOL").is_ok_and(|v| v == PROTOCOL_VERSION.to_string())
+}
+
+// Hand a list of directives to the wrapper, returning the exit code to use
+pub fn emit(directives: Vec<Directive>) -> Result<i32, PinError> {
+    if directives.is_empty() {
+        return Ok(EXIT_OK);
+    }
+
+    for directive in &directives {
+        if let Directive::SetEnv(name, _) = directive
+            && !valid_env_name(name)
+        {
+            return Err(PinError::Usage(format!(
+                "\"{name}\" is not a valid environment variable name."
+            )));
+        }
+    }
+
+    if directive_mode() {
+        println!("pin-directives {PROTOCOL_VERSION}");
+        for directive in &directives {
+            println!("{}", directive.render());
+        }
+        return Ok(EXIT_DIRECTIVES);
+    }
+
+    // Exit code mode can only cd to, or open, a single path
+    let mut code = EXIT_OK;
+    for directive in directives {
+        match directive {
+            Directive::Cd(path) | Directive::Pushd(path) if code == EXIT_OK => {
+                println!("{path}");
+                code = EXIT_CD;
+            }
+            // The old wrapper always uses the editor
+            Directive::Open(path, _) if code == EXIT_OK => {
+                println!("{path}");
+                code = EXIT_OPEN;
+            }
+            Directive::Echo(msg) => eprintln!("{msg}"),
+            _ => {
+                eprintln!(
+                    "Warning: this shell wrapper can't run everything pin asked for. Run \"_pin --init <shell>\" to get the latest wrapper."
+                );
+                break;
+            }
+        }
+    }
+
+    Ok(code)
+}
+
+// Environment variable names the shells will accept
+pub fn valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+// Percent-encode the characters that would break the line format or be interpreted by a shell
+// while decoding
+pub fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '%' | '\\' | '+' => out += &format!("%{:02X}", c as u32),
+            c if c.is_ascii_control() => out += &format!("%{:02X}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Undo encode. None if a % isn't followed by two hex digits
+pub fn decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'%' {
+            out.push(b);
+            continue;
+        }
+        let hex = [bytes.next()?, bytes.next()?];
+        out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+    }
+    String::from_utf8(out).ok()
+}
@@ -0,0 +1,124 @@
+// Errors and exit codes
+//
+// Every failure a command can hit is a variant of PinError. Each variant maps to a stable exit
+// code so the shell wrapper (and anyone scripting around _pin) can react to the specific failure
+// instead of parsing messages. The codes are documented in the README and must not be reused.
+
+use std::fmt;
+use std::io;
+
+// Success codes understood by the wrapper
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_CD: i32 = 2;
+pub const EXIT_OPEN: i32 = 4;
+
+/// Every way a command can fail.
+#[derive(Debug)]
+pub enum PinError {
+    /// The command line could not be parsed.
+    Usage(String),
+    /// The alias does not exist in the store.
+    AliasNotFound(String),
+    /// A path given by the user could not be used.
+    InvalidPath(String),
+    /// The alias exists but the path it points at is gone.
+    PathMissing {
+        /// The alias being used.
+        alias: String,
+        /// Where it points.
+        path: String,
+    },
+    /// $HOME is not set so the store can't be located.
+    NoHome,
+    /// The store could not be read or written.
+    StoreIo(io::Error),
+    /// The store exists but could not be decoded.
+    StoreDecode(String),
+    /// Interactive commands need /dev/tty and raw mode.
+    TtyUnavailable(io::Error),
+    /// Another pin process held the store lock for too long.
+    LockTimeout(String),
+    /// A path refers to an environment variable that isn't set.
+    UnsetVar {
+        /// The variable that isn't set.
+        var: String,
+        /// The path that uses it.
+        path: String,
+    },
+    /// ~/.pin/config couldn't be understood.
+    Config(String),
+    /// A command given to --exec couldn't be started.
+    Spawn(String, io::Error),
+    /// The alias is locked against changes and --force wasn't given.
+    Locked(String),
+    /// The user pressed Ctrl-C at a prompt.
+    Interrupted,
+    /// An export couldn't be written, or an import couldn't be read or understood.
+    Transfer(String),
+}
+
+impl PinError {
+    /// The exit code returned to the shell for this error.
+    pub fn code(&self) -> i32 {
+        match self {
+            PinError::Usage(_) => 10,
+            PinError::AliasNotFound(_) => 11,
+            PinError::InvalidPath(_) => 12,
+            PinError::PathMissing { .. } => 13,
+            PinError::NoHome => 14,
+            PinError::StoreIo(_) => 15,
+            PinError::StoreDecode(_) => 16,
+            PinError::TtyUnavailable(_) => 17,
+            PinError::LockTimeout(_) => 18,
+            PinError::UnsetVar { .. } => 19,
+            PinError::Config(_) => 20,
+            PinError::Spawn(..) => 21,
+            PinError::Locked(_) => 22,
+            // As a shell reports a process killed by SIGINT
+            PinError::Interrupted => 130,
+            PinError::Transfer(_) => 23,
+        }
+    }
+}
+
+impl fmt::Display for PinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PinError::Usage(msg) => write!(f, "Error: {msg}"),
+            PinError::AliasNotFound(alias) => write!(
+                f,
+                "Error: alias \"{alias}\" not found in store. Type \"pin --help\" for help."
+            ),
+            PinError::InvalidPath(msg) => write!(f, "Error: {msg}"),
+            PinError::PathMissing { alias, path } => write!(
+                f,
+                "Error: \"{alias}\" points to {path}, which no longer exists."
+            ),
+            PinError::NoHome => write!(f, "Error: unable to find home directory."),
+            PinError::StoreIo(e) => write!(f, "Error: unable to access the store: {e}"),
+            PinError::StoreDecode(e) => write!(
+                f,
+                "Error: the store is corrupt and could not be read ({e}). It has been left untouched."
+            ),
+            PinError::TtyUnavailable(e) => write!(f, "Error: unable to get tty access: {e}"),
+            PinError::LockTimeout(lock) => write!(
+                f,
+                "Error: timed out waiting for the store lock. If no other pin is running, remove {lock}."
+            ),
+            PinError::UnsetVar { var, path } => write!(
+                f,
+                "Error: ${var} is not set, so {path} can't be expanded. Set it in your shell first."
+            ),
+            PinError::Config(msg) => write!(f, "Error: in ~/.pin/config, {msg}"),
+            PinError::Spawn(cmd, e) => write!(f, "Error: unable to run \"{cmd}\": {e}"),
+            PinError::Locked(alias) => write!(
+                f,
+                "Error: \"{alias}\" is locked. Use --force to change it anyway, or \"pin --unlock {alias}\" first."
+            ),
+            PinError::Interrupted => write!(f, "Cancelled."),
+            PinError::Transfer(msg) => write!(f, "Error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for PinError {}
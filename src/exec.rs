@@ -0,0 +1,127 @@
+// Running commands inside pinned directories
+//
+// A single pin runs the command with the terminal passed straight through. Several pins (a tag)
+// run it in each directory, up to `jobs` at a time, with every line of output prefixed by the
+// alias so interleaved output can still be told apart.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::thread;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::error::PinError;
+
+// Run a command in one directory, returning its exit code
+pub fn run_one(dir: &str, cmd: &[String]) -> Result<i32, PinError> {
+    let status = command(dir, cmd)
+        .status()
+        .map_err(|e| PinError::Spawn(cmd[0].clone(), e))?;
+
+    Ok(exit_code(status))
+}
+
+// Run a command in each (alias, directory) pair. Returns 0 if every run succeeded, or else the
+// exit code of the first pin that failed
+pub fn run_many(targets: Vec<(String, String)>, cmd: &[String], jobs: usize) -> i32 {
+    let width = targets
+        .iter()
+        .map(|(alias, _)| alias.width())
+        .max()
+        .unwrap_or(0);
+    let queue = Mutex::new(targets.into_iter().enumerate().collect::<VecDeque<_>>());
+    let results = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let Some((i, (alias, dir))) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+                    // Padded by hand, as format! pads by chars rather than columns
+                    let pad = " ".repeat(width - alias.width());
+                    let prefix = format!("[{pad}{alias}] ");
+                    let code = run_prefixed(&dir, cmd, &prefix);
+                    results.lock().unwrap().push((i, alias, code));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _, _)| *i);
+    let failed: Vec<&(usize, String, i32)> = results.iter().filter(|r| r.2 != 0).collect();
+
+    if failed.is_empty() {
+        return 0;
+    }
+    let names: Vec<&str> = failed.iter().map(|r| r.1.as_str()).collect();
+    eprintln!(
+        "{} of {} failed: {}",
+        failed.len(),
+        results.len(),
+        names.join(", ")
+    );
+    failed[0].2
+}
+
+// Run a command with its output prefixed, returning its exit code. Failing to start counts as a
+// failure of that pin rather than stopping the others
+fn run_prefixed(dir: &str, cmd: &[String], prefix: &str) -> i32 {
+    let child = command(dir, cmd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            let e = PinError::Spawn(cmd[0].clone(), e);
+            eprintln!("{prefix}{e}");
+            return e.code();
+        }
+    };
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    thread::scope(|scope| {
+        if let Some(out) = stdout {
+            scope.spawn(|| copy_prefixed(out, std::io::stdout(), prefix));
+        }
+        if let Some(err) = stderr {
+            scope.spawn(|| copy_prefixed(err, std::io::stderr(), prefix));
+        }
+    });
+
+    child.wait().map(exit_code).unwrap_or(1)
+}
+
+// Copy lines from a child to our output with the prefix in front of each
+fn copy_prefixed(from: impl Read, to: impl Write, prefix: &str) {
+    let mut to = to;
+    for line in BufReader::new(from).split(b'\n').map_while(Result::ok) {
+        let mut out = prefix.as_bytes().to_vec();
+        out.extend(line);
+        out.push(b'\n');
+        // Whole lines at a time so pins running together don't split each other's lines
+        let _ = to.write_all(&out);
+    }
+}
+
+fn command(dir: &str, cmd: &[String]) -> Command {
+    let mut command = Command::new(&cmd[0]);
+    command.args(&cmd[1..]).current_dir(dir);
+    command
+}
+
+// The exit code a shell would report, including 128 + n for a signal
+fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|sig| 128 + sig))
+        .unwrap_or(1)
+}
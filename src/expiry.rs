@@ -0,0 +1,141 @@
+// Temporary pins
+//
+// A pin can be given an expiry time with --ttl or --until, after which it is dropped the next
+// time the store is read. Session pins are kept in ~/.pin/sessions/<pid>.bin instead of the
+// store, keyed by the pid of the shell that made them, and go once that shell has exited.
+
+use std::path::PathBuf;
+
+use crate::error::PinError;
+use crate::history::now;
+use crate::store::pin_dir;
+
+// Parse a time to live such as 90s, 30m, 2h, 1d or 1w into seconds
+pub fn parse_ttl(ttl: &str) -> Option<u64> {
+    let split = ttl.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = ttl.split_at(split);
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    count.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+// Parse a local date as YYYY-MM-DD, YYYY-MM-DD HH:MM or YYYY-MM-DDTHH:MM into seconds since the
+// epoch. A date on its own lasts until the end of that day
+pub fn parse_until(until: &str) -> Option<u64> {
+    let (date, time) = match until.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (until, None),
+    };
+
+    let mut date = date.split('-').map(|n| n.parse::<i32>().ok());
+    let (Some(Some(year)), Some(Some(month)), Some(Some(day)), None) =
+        (date.next(), date.next(), date.next(), date.next())
+    else {
+        return None;
+    };
+    // mktime would roll a day past the end of the month into the next one, so check it here
+    if !(1970..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+    {
+        return None;
+    }
+    let (hour, min, day) = match time {
+        Some(time) => {
+            let (hour, min) = time.split_once(':')?;
+            (hour.parse().ok()?, min.parse().ok()?, day)
+        }
+        None => (0, 0, day + 1),
+    };
+    if !(0..24).contains(&hour) || !(0..60).contains(&min) {
+        return None;
+    }
+
+    // mktime works in local time and normalises the day after the end of a month
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = year - 1900;
+    tm.tm_mon = month - 1;
+    tm.tm_mday = day;
+    tm.tm_hour = hour;
+    tm.tm_min = min;
+    tm.tm_isdst = -1;
+    let time = unsafe { libc::mktime(&mut tm) };
+
+    u64::try_from(time).ok()
+}
+
+fn days_in_month(year: i32, month: i32) -> i32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// How long until a timestamp, e.g. "in 5m"
+pub fn fmt_left(time: u64) -> String {
+    let secs = time.saturating_sub(now());
+    match secs {
+        0 => "now".to_string(),
+        1..60 => format!("in {secs}s"),
+        60..3600 => format!("in {}m", secs / 60),
+        3600..86400 => format!("in {}h", secs / 3600),
+        _ => format!("in {}d", secs / 86400),
+    }
+}
+
+// The pid of the shell session pins belong to. The wrappers pass it as PIN_SESSION, since they
+// run _pin from a subshell. Without one _pin was run straight from the shell
+pub fn session_id() -> u32 {
+    std::env::var("PIN_SESSION")
+        .ok()
+        .and_then(|id| id.parse().ok())
+        .unwrap_or_else(std::os::unix::process::parent_id)
+}
+
+pub fn sessions_dir() -> Result<PathBuf, PinError> {
+    Ok(pin_dir()?.join("sessions"))
+}
+
+pub fn session_path(id: u32) -> Result<PathBuf, PinError> {
+    Ok(sessions_dir()?.join(format!("{id}.bin")))
+}
+
+// Whether the shell that owns a session is still running
+pub fn session_alive(id: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(id) else {
+        return false;
+    };
+    // Signal 0 only checks the process exists. EPERM means it exists but belongs to someone else
+    let found = unsafe { libc::kill(pid, 0) } == 0;
+    found || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// Remove the files of sessions whose shell has exited
+pub fn sweep_sessions() -> Result<(), PinError> {
+    let Ok(entries) = std::fs::read_dir(sessions_dir()?) else {
+        return Ok(());
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let id = name
+            .to_str()
+            .and_then(|name| name.strip_suffix(".bin"))
+            .and_then(|id| id.parse().ok());
+        if let Some(id) = id
+            && !session_alive(id)
+        {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,212 @@
+// Finding pinned directories that have moved
+//
+// When a pin is added we remember the directory's device and inode, and its origin url if it is a
+// git repository. If the path later disappears, the search roots from the config are walked
+// looking for a directory with the same identity. A move within a filesystem keeps the inode, and
+// the origin url catches moves across filesystems and fresh clones.
+//
+// A jump only searches for as long as search_budget allows, as walking a big home directory can
+// take seconds. pin --doctor searches everything.
+
+use std::collections::VecDeque;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::alias::PinAlias;
+use crate::config::Config;
+use crate::error::PinError;
+use crate::path::{PathPolicy, normalize};
+
+// Device and inode of a directory
+pub fn identity(path: &Path) -> Option<(u64, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.dev(), meta.ino()))
+}
+
+// The url of the origin remote, if the directory is the root of a git repository
+pub fn git_origin(path: &Path) -> Option<String> {
+    let config = std::fs::read_to_string(path.join(".git/config")).ok()?;
+
+    let mut in_origin = false;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_origin = line == "[remote \"origin\"]";
+        } else if in_origin
+            && let Some((key, value)) = line.split_once('=')
+            && key.trim() == "url"
+        {
+            return Some(value.trim().to_string());
+        }
+    }
+    None
+}
+
+// Where and how deep to search, from the config
+pub struct Search {
+    pub roots: Vec<PathBuf>,
+    pub depth: usize,
+    // Update pins without asking
+    pub auto: bool,
+    // How long a jump may spend searching. None searches everything
+    pub budget: Option<Duration>,
+}
+
+impl Search {
+    pub fn from_config(config: &Config) -> Result<Search, PinError> {
+        let mut roots = config.get_list("search_roots");
+        if roots.is_empty() {
+            roots.push("~".to_string());
+        }
+
+        // Roots that don't exist on this machine are skipped rather than being an error
+        let policy = PathPolicy {
+            allow_missing: true,
+            ..Default::default()
+        };
+        let var = |name: &str| std::env::var(name).ok();
+        let roots = roots
+            .iter()
+            .filter_map(|root| normalize(root, policy, Path::new("/"), &var).ok())
+            .filter(|root| root.is_dir())
+            .collect();
+
+        Ok(Search {
+            roots,
+            depth: config.get_usize("search_depth", 4)?,
+            auto: config.get_bool("auto_heal", false)?,
+            budget: None,
+        })
+    }
+
+    // The search a jump does, giving up after search_budget milliseconds
+    pub fn for_jump(config: &Config) -> Result<Search, PinError> {
+        let budget = config.get_usize("search_budget", 200)?;
+        Ok(Search {
+            budget: Some(Duration::from_millis(budget as u64)),
+            ..Search::from_config(config)?
+        })
+    }
+
+    // Look for the directory a pin used to point at. Out of budget, the best match so far is
+    // returned
+    pub fn find(&self, record: &PinAlias) -> Option<Found> {
+        if record.identity.is_none() && record.origin.is_none() {
+            return None;
+        }
+
+        let deadline = self.budget.map(|budget| Instant::now() + budget);
+        let mut by_origin = None;
+        let mut queue: VecDeque<(PathBuf, usize)> =
+            self.roots.iter().map(|root| (root.clone(), 0)).collect();
+
+        while let Some((dir, depth)) = queue.pop_front() {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break;
+            }
+            let same_origin = record.origin.is_some() && git_origin(&dir) == record.origin;
+            // Inodes are reused once a directory is deleted, so one only counts if the origin
+            // agrees too. Without an origin to check, it is a guess
+            if record.identity.is_some() && identity(&dir) == record.identity {
+                match &record.origin {
+                    Some(_) if same_origin => {
+                        return Some(Found {
+                            path: dir,
+                            sure: true,
+                        });
+                    }
+                    Some(_) => {}
+                    None => {
+                        return Some(Found {
+                            path: dir,
+                            sure: false,
+                        });
+                    }
+                }
+            }
+            if by_origin.is_none() && same_origin {
+                by_origin = Some(dir.clone());
+            }
+
+            if depth >= self.depth {
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                // Don't follow symlinks, or we could walk in circles
+                let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+                if is_dir && entry.file_name() != ".git" {
+                    queue.push_back((entry.path(), depth + 1));
+                }
+            }
+        }
+
+        by_origin.map(|path| Found { path, sure: true })
+    }
+}
+
+// A directory a pin may have moved to
+pub struct Found {
+    pub path: PathBuf,
+    // Whether it matched on git origin. A match on inode alone is always confirmed, even with
+    // auto_heal
+    pub sure: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    fn search(root: &Path) -> Search {
+        Search {
+            roots: vec![root.to_path_buf()],
+            depth: 2,
+            auto: true,
+            budget: None,
+        }
+    }
+
+    fn repo(dir: &Path, origin: &str) {
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        let config = format!("[remote \"origin\"]\n\turl = {origin}\n");
+        std::fs::write(dir.join(".git/config"), config).unwrap();
+    }
+
+    #[test]
+    fn a_reused_inode_with_another_origin_is_not_a_match() {
+        let root = TempDir::new().unwrap();
+        let dir = root.path().join("other");
+        repo(&dir, "git@example.com:me/other.git");
+
+        let record = PinAlias {
+            path: "/gone/api".to_string(),
+            identity: identity(&dir),
+            origin: Some("git@example.com:me/api.git".to_string()),
+            ..Default::default()
+        };
+        assert!(search(root.path()).find(&record).is_none());
+
+        repo(&dir, "git@example.com:me/api.git");
+        let found = search(root.path()).find(&record).unwrap();
+        assert_eq!((found.path, found.sure), (dir, true));
+    }
+
+    #[test]
+    fn an_inode_alone_is_not_sure() {
+        let root = TempDir::new().unwrap();
+        let dir = root.path().join("api");
+        std::fs::create_dir(&dir).unwrap();
+
+        let record = PinAlias {
+            path: "/gone/api".to_string(),
+            identity: identity(&dir),
+            ..Default::default()
+        };
+        let found = search(root.path()).find(&record).unwrap();
+        assert_eq!((found.path, found.sure), (dir, false));
+    }
+}
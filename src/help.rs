@@ -0,0 +1,665 @@
+// Command help
+//
+// Everything pin says about its commands lives here: `pin --help`, `pin --help <cmd>`,
+// `pin --help --all`, and the pin(1) man page and markdown reference that build.rs renders from
+// the same tables. This file is also compiled into build.rs, so it can't use anything else from
+// the crate.
+
+// The help for one command
+pub struct CmdHelp {
+    // The name used in `pin --help <name>`
+    pub name: &'static str,
+    // The flags that run the command, which also work as names for --help
+    pub flags: &'static [&'static str],
+    pub usage: &'static [&'static str],
+    // One line for the summary table
+    pub summary: &'static str,
+    pub details: &'static str,
+    pub options: &'static [(&'static str, &'static str)],
+    // Each example and what it does
+    pub examples: &'static [(&'static str, &'static str)],
+    // The exit codes particular to this command, described by EXIT_CODES
+    pub exits: &'static [i32],
+}
+
+// Every exit code pin uses, which are stable so scripts can rely on them
+pub const EXIT_CODES: &[(i32, &str)] = &[
+    (0, "Success, nothing for the shell to do"),
+    (2, "Success, the path to cd to is on stdout"),
+    (
+        3,
+        "Success, directives for the wrapper are on stdout (PIN_PROTOCOL is set)",
+    ),
+    (
+        4,
+        "Success, a pinned file to open in $VISUAL or $EDITOR is on stdout",
+    ),
+    (10, "Usage error, the command line couldn't be parsed"),
+    (11, "Alias not found in the store"),
+    (12, "Invalid path given"),
+    (13, "The alias points to a path that no longer exists"),
+    (14, "$HOME is not set"),
+    (15, "The store couldn't be read or written"),
+    (16, "The store is corrupt and couldn't be decoded"),
+    (17, "No tty available for an interactive command"),
+    (
+        18,
+        "Timed out waiting for another pin process to release the store lock",
+    ),
+    (19, "A path uses an environment variable that isn't set"),
+    (
+        20,
+        "~/.pin/config couldn't be read, or sets a value pin doesn't understand",
+    ),
+    (21, "The command given to --exec couldn't be started"),
+    (22, "The alias is locked, and --force wasn't given"),
+    (
+        23,
+        "An export couldn't be written, or an import couldn't be read",
+    ),
+    (130, "Cancelled with Ctrl-C at a prompt"),
+];
+
+// Codes any command that reads the store or config can return
+pub const COMMON_EXITS: &[i32] = &[10, 14, 15, 16, 18, 20];
+
+pub const COMMANDS: &[CmdHelp] = &[
+    CmdHelp {
+        name: "pin",
+        flags: &["--push", "-p"],
+        usage: &[
+            "pin [alias]",
+            "pin --push [alias]",
+            "pin [alias] -- [cmd...]",
+        ],
+        summary: "Go to the path aliased, optionally with pushd",
+        details: "Changes the shell's directory to the pinned path. File pins are opened in \
+                  $VISUAL or $EDITOR instead. If the directory has moved, pin looks for it under \
+                  search_roots, for up to search_budget milliseconds, and offers to update the \
+                  pin. Trusted hooks run after the jump. Given a command after --, it is run \
+                  inside the pin instead, like pin --exec.",
+        options: &[(
+            "--push, -p",
+            "Keep the old directory on the shell's directory stack",
+        )],
+        examples: &[
+            ("pin api", "Go to the directory pinned as api"),
+            ("pin --push api", "Go to api, so popd comes back here"),
+            (
+                "pin api -- make test",
+                "Run make test in api without leaving this directory",
+            ),
+        ],
+        exits: &[2, 3, 4, 11, 13, 17, 19],
+    },
+    CmdHelp {
+        name: "-",
+        flags: &["-", "previous"],
+        usage: &["pin -"],
+        summary: "Go to the previous pin",
+        details: "Goes back to the pin jumped to before the current one, like cd -.",
+        options: &[],
+        examples: &[("pin -", "Go back to the last pin")],
+        exits: &[2, 3, 11, 13],
+    },
+    CmdHelp {
+        name: "back",
+        flags: &["--back", "-b", "--forward"],
+        usage: &["pin --back [n(optional)]", "pin --forward [n(optional)]"],
+        summary: "Go back or forward n jumps in the history",
+        details: "Moves through the jump history like a browser, one jump at a time unless \
+                  given a number. Jumping somewhere new after going back drops the jumps ahead.",
+        options: &[("--forward", "Go forward again after going back")],
+        examples: &[
+            ("pin --back", "Go to the pin before this one"),
+            ("pin -b 3", "Go back three jumps"),
+            ("pin --forward", "Undo the last --back"),
+        ],
+        exits: &[2, 3, 11, 13],
+    },
+    CmdHelp {
+        name: "history",
+        flags: &["--history"],
+        usage: &["pin --history"],
+        summary: "List recent jumps, newest first",
+        details: "Shows the recent jumps with the time of each, newest first. The current \
+                  position, which --back and --forward move, is marked with *.",
+        options: &[],
+        examples: &[("pin --history", "Show where pin has been")],
+        exits: &[17],
+    },
+    CmdHelp {
+        name: "add",
+        flags: &["--add", "-a"],
+        usage: &["pin --add [alias] [path] [options...]"],
+        summary: "Add the supplied alias to the supplied path",
+        details: "Pins a directory or file under an alias. The path can be relative, start with \
+                  ~ or use $VAR, and is stored as an absolute path with symlinks resolved. It \
+                  has to exist unless --allow-missing is given. Adding an alias that is already \
+                  pinned asks before replacing it.",
+        options: &[
+            (
+                "--keep-symlinks",
+                "Keep symlinks in the path instead of resolving them",
+            ),
+            ("--allow-missing", "Accept a path that doesn't exist yet"),
+            (
+                "--literal",
+                "Don't expand $VAR, for paths that really contain a $",
+            ),
+            (
+                "--raw",
+                "Store ~ and $VAR as typed and expand them when jumping",
+            ),
+            ("--note text", "Describe what the pin is for"),
+            (
+                "--ttl time",
+                "Expire the pin after a time such as 30m, 2h or 7d",
+            ),
+            (
+                "--until date",
+                "Expire the pin at YYYY-MM-DD or \"YYYY-MM-DD HH:MM\"",
+            ),
+            ("--session", "Keep the pin only until the shell exits"),
+            ("--force, -f", "Overwrite a locked alias"),
+        ],
+        examples: &[
+            ("pin --add api ~/code/api", "Pin ~/code/api as api"),
+            (
+                "pin -a notes ~/notes.md",
+                "Pin a file, which jumping opens in $EDITOR",
+            ),
+            (
+                "pin --add src '$PROJECTS/src' --raw",
+                "Expand $PROJECTS each time it is used",
+            ),
+            (
+                "pin --add debug . --ttl 2h",
+                "Pin this directory for two hours",
+            ),
+        ],
+        exits: &[12, 19, 22],
+    },
+    CmdHelp {
+        name: "here",
+        flags: &["--here"],
+        usage: &["pin --here [alias(optional)]"],
+        summary: "Pin the current directory",
+        details: "Pins the current directory. Without an alias it is named after the directory, \
+                  or after the git repository and the path inside it.",
+        options: &[],
+        examples: &[
+            ("pin --here", "Pin this directory under its own name"),
+            ("pin --here web", "Pin this directory as web"),
+        ],
+        exits: &[12, 22],
+    },
+    CmdHelp {
+        name: "delete",
+        flags: &["--delete", "-d"],
+        usage: &["pin --delete [alias|pattern] [--force]"],
+        summary: "Delete an alias, or every alias matching a pattern",
+        details: "Removes a pin. A glob such as 'tmp-*' or a regular expression after re: \
+                  deletes every matching alias, after listing them and asking once. Patterns \
+                  match the whole alias.",
+        options: &[("--force, -f", "Delete locked aliases too")],
+        examples: &[
+            ("pin --delete api", "Delete the api pin"),
+            ("pin -d 'tmp-*'", "Delete every alias starting with tmp-"),
+            (
+                "pin -d 're:^client-(a|b)-'",
+                "Delete the aliases matching a regular expression",
+            ),
+        ],
+        exits: &[11, 17, 22, 130],
+    },
+    CmdHelp {
+        name: "tui",
+        flags: &["--tui"],
+        usage: &["pin --tui"],
+        summary: "Browse, filter, edit and jump to pins in a full-screen manager",
+        details: "Lists every pin with the selected one's path, tags, note, recent jumps and \
+                  flags below. Filter with /, change the sort with s, and rename, edit, tag, note \
+                  or delete pins in place. Every change is saved straight away and can be undone \
+                  with u. Enter jumps to the selected pin, and ? lists the keys.",
+        options: &[],
+        examples: &[("pin --tui", "Open the manager")],
+        exits: &[2, 3, 17],
+    },
+    CmdHelp {
+        name: "help",
+        flags: &["--help", "-h"],
+        usage: &["pin --help [cmd(optional)]", "pin --help --all"],
+        summary: "List all commands or show the details of one",
+        details: "Without a command, lists every command. A command can be named as it is \
+                  here or by its flag, so pin --help add, pin --help --add and pin --help -a are \
+                  the same. _pin --help --man prints the pin(1) man page and _pin --help \
+                  --markdown the same reference as markdown.",
+        options: &[
+            ("--all", "Show the details of every command"),
+            ("--man", "Print the man page, as roff"),
+            ("--markdown", "Print the command reference as markdown"),
+        ],
+        examples: &[
+            ("pin --help --add", "Show how to add a pin"),
+            ("pin --help --all | less", "Read the help for everything"),
+        ],
+        exits: &[17],
+    },
+    CmdHelp {
+        name: "list",
+        flags: &["--list", "-l"],
+        usage: &[
+            "pin --list [filter(optional)] [--columns a,b,...] [--sort key] [--reverse] \
+             [--color=when]",
+            "pin --list [filter(optional)] --tree | --group-by tag",
+        ],
+        summary: "List all alias-path pairs",
+        details: "Shows every pin in a table, only listing the paths containing the filter if \
+                  one is given. Tags, notes, locks, expiry and what raw pins expand to get their \
+                  own columns when any pin uses them. Paths under $HOME are shown from ~, and long \
+                  paths lose their middle to fit the terminal. Pins whose path is missing are red, \
+                  and the pin for the current directory is highlighted. --tree draws the pins as a \
+                  tree of their directories instead, merging directories that only lead to one \
+                  other and counting the pins under each branch.",
+        options: &[
+            (
+                "--columns a,b,...",
+                "Show these columns: alias, path, tags, uses, last-used, exists, note, locked, \
+                 expires, created or expanded",
+            ),
+            (
+                "--sort key",
+                "Sort by alias (the default), path, frecency, recent or created. The last three \
+                 put the most used, latest or newest first",
+            ),
+            ("--reverse", "Reverse the order"),
+            (
+                "--tree",
+                "Group the pins by directory, the same as --group-by path",
+            ),
+            ("--group-by tag", "Group the pins under each of their tags"),
+            (
+                "--color=when",
+                "Colour the list: auto (on a terminal, unless NO_COLOR is set), always or never",
+            ),
+        ],
+        examples: &[
+            ("pin --list", "List every pin"),
+            ("pin -l code", "List the pins with code in their path"),
+            ("pin --list --sort frecency", "Put the most used pins first"),
+            (
+                "pin --list --columns alias,uses,last-used",
+                "Show how much each pin is used",
+            ),
+            ("pin --list --tree", "See where pins cluster"),
+        ],
+        exits: &[17],
+    },
+    CmdHelp {
+        name: "update",
+        flags: &["--update", "-u"],
+        usage: &["pin --update [alias] [--force]"],
+        summary: "Update the alias, path or note of a pin",
+        details: "Asks which part of the pin to change and prompts for the new value, with the \
+                  old one to edit. The path is checked as it is typed, and tab completes paths.",
+        options: &[("--force, -f", "Update a locked alias")],
+        examples: &[("pin --update api", "Change the api pin")],
+        exits: &[11, 12, 17, 22, 130],
+    },
+    CmdHelp {
+        name: "rename",
+        flags: &["--rename"],
+        usage: &["pin --rename [alias] [new alias] [--force]"],
+        summary: "Rename an alias, keeping its hooks, tags and note",
+        details: "Moves a pin to a new alias, asking first if the new alias is already \
+                  pinned.",
+        options: &[("--force, -f", "Rename a locked alias")],
+        examples: &[("pin --rename api backend", "Call the api pin backend")],
+        exits: &[11, 22],
+    },
+    CmdHelp {
+        name: "prune",
+        flags: &["--prune"],
+        usage: &["pin --prune [--force]"],
+        summary: "Remove pins whose path no longer exists",
+        details: "Lists the pins whose paths have gone with each one ticked. Space unticks a \
+                  pin to keep it, a ticks or clears them all, and enter removes the ticked ones. \
+                  Try pin --doctor first to find pins whose directory moved.",
+        options: &[("--force, -f", "Offer locked pins as well")],
+        examples: &[("pin --prune", "Choose which dead pins to remove")],
+        exits: &[17, 130],
+    },
+    CmdHelp {
+        name: "retarget",
+        flags: &["--retarget"],
+        usage: &["pin --retarget [old root] [new root] [--force]"],
+        summary: "Move every pin under one directory to the same place under another",
+        details: "For after a whole tree has moved. Lists the pins it will change and asks \
+                  once before changing them.",
+        options: &[("--force, -f", "Move locked pins too")],
+        examples: &[(
+            "pin --retarget ~/code ~/src",
+            "Point the pins under ~/code at ~/src",
+        )],
+        exits: &[12, 17, 22, 130],
+    },
+    CmdHelp {
+        name: "lock",
+        flags: &["--lock", "--unlock"],
+        usage: &["pin --lock [alias|pattern]", "pin --unlock [alias|pattern]"],
+        summary: "Lock an alias so changing or deleting it needs --force",
+        details: "Protects a pin from accidents. Adding over it, deleting, updating, renaming \
+                  and pruning it are refused unless given --force. Takes a glob or re: pattern \
+                  like --delete.",
+        options: &[("--unlock", "Remove the lock")],
+        examples: &[
+            ("pin --lock prod", "Protect the prod pin"),
+            ("pin --unlock 'client-*'", "Unlock every client- pin"),
+        ],
+        exits: &[11, 17, 130],
+    },
+    CmdHelp {
+        name: "hook",
+        flags: &["--hook"],
+        usage: &["pin --hook [alias] [add [cmd] | env [NAME=value] | rm [n] | clear]"],
+        summary: "Show or edit the commands and environment applied after jumping",
+        details: "Hooks run in the shell after jumping to the pin, once the pin has been \
+                  trusted with pin --trust. Editing them removes the trust again. Without an \
+                  action the hooks are listed, numbered for rm. Hooks need a wrapper generated \
+                  by _pin --init.",
+        options: &[
+            (
+                "add cmd",
+                "Run cmd after jumping. Its words are quoted as typed, and a single quoted \
+                 argument is run as shell code",
+            ),
+            (
+                "env NAME=value",
+                "Export a variable after jumping. env NAME unsets it",
+            ),
+            ("rm n", "Remove the nth hook"),
+            ("clear", "Remove every hook"),
+        ],
+        examples: &[
+            (
+                "pin --hook api add source .venv/bin/activate",
+                "Activate a virtualenv",
+            ),
+            (
+                "pin --hook api add 'git fetch && git status -s'",
+                "Run two commands as one hook",
+            ),
+            (
+                "pin --hook api env KUBECONFIG=~/.kube/api",
+                "Set KUBECONFIG for api",
+            ),
+            ("pin --hook api", "List api's hooks"),
+        ],
+        exits: &[11],
+    },
+    CmdHelp {
+        name: "trust",
+        flags: &["--trust", "--untrust"],
+        usage: &["pin --trust [alias]", "pin --untrust [alias]"],
+        summary: "Allow or stop an alias' hooks running after jumping",
+        details: "Hooks only run once their pin is trusted, so an imported or edited store \
+                  can't run commands by itself.",
+        options: &[("--untrust", "Stop the hooks running")],
+        examples: &[("pin --trust api", "Let api's hooks run")],
+        exits: &[11],
+    },
+    CmdHelp {
+        name: "doctor",
+        flags: &["--doctor"],
+        usage: &["pin --doctor [--fix(optional)]"],
+        summary: "Check every pin and find directories that moved",
+        details: "Shows whether each pin's path is there. Pins whose directory has gone are \
+                  looked for under search_roots by inode and git origin, and each one found is \
+                  offered as an update.",
+        options: &[(
+            "--fix",
+            "Update moved pins without asking, except ones only the inode matches",
+        )],
+        examples: &[
+            ("pin --doctor", "Check every pin"),
+            ("pin --doctor --fix", "Update every moved pin"),
+        ],
+        exits: &[17, 130],
+    },
+    CmdHelp {
+        name: "tag",
+        flags: &["--tag", "--untag"],
+        usage: &[
+            "pin --tag [alias|pattern] [tags...]",
+            "pin --untag [alias|pattern] [tags...]",
+        ],
+        summary: "Add or remove tags on an alias, or every alias matching a pattern",
+        details: "Tags group pins, for pin --exec @tag and pin --search. Takes a glob or re: \
+                  pattern like --delete.",
+        options: &[("--untag", "Remove the tags instead")],
+        examples: &[
+            ("pin --tag api work", "Tag api with work"),
+            (
+                "pin --untag 'tmp-*' work",
+                "Remove the work tag from every tmp- pin",
+            ),
+        ],
+        exits: &[11, 17, 130],
+    },
+    CmdHelp {
+        name: "note",
+        flags: &["--note"],
+        usage: &["pin --note [alias] [text...](optional)"],
+        summary: "Show or set the note on an alias",
+        details: "A note says what a pin is for. It is shown by pin --list and searched by pin \
+                  --search. Without text the note is printed, and \"\" removes it.",
+        options: &[],
+        examples: &[
+            ("pin --note api Backend for the dashboard", "Set api's note"),
+            ("pin --note api \"\"", "Remove it"),
+        ],
+        exits: &[11],
+    },
+    CmdHelp {
+        name: "search",
+        flags: &["--search", "-s"],
+        usage: &["pin --search [text...]"],
+        summary: "Search aliases, paths, tags and notes, best matches first",
+        details: "Alias matches are listed first and note matches last.",
+        options: &[],
+        examples: &[(
+            "pin --search dashboard",
+            "Find the pins to do with the dashboard",
+        )],
+        exits: &[17],
+    },
+    CmdHelp {
+        name: "exec",
+        flags: &["--exec", "-x"],
+        usage: &["pin --exec [alias|@tag] [-j n(optional)] -- [cmd...]"],
+        summary: "Run a command in a pin, or in every pin with a tag",
+        details: "Runs the command with the pin as its working directory, without changing the \
+                  shell's. With @tag it runs in every pin with that tag, n at a time, and each \
+                  line of output is prefixed with its alias. Returns the command's exit code, or \
+                  the first failing one when it ran in several pins.",
+        options: &[("-j n, --jobs n", "Run in n pins at once (default 1)")],
+        examples: &[
+            ("pin --exec api -- make test", "Run the tests in api"),
+            (
+                "pin -x @work -j 4 -- git pull",
+                "Pull every work pin, four at a time",
+            ),
+        ],
+        exits: &[11, 13, 21],
+    },
+    CmdHelp {
+        name: "export",
+        flags: &["--export"],
+        usage: &["pin --export [file]"],
+        summary: "Write every pin to a file for pin --import",
+        details: "Hooks are exported but trust in them isn't. Session pins stay with their \
+                  shell.",
+        options: &[],
+        examples: &[("pin --export pins.txt", "Save every pin to pins.txt")],
+        exits: &[23],
+    },
+    CmdHelp {
+        name: "import",
+        flags: &["--import"],
+        usage: &["pin --import [file] [--replace] [--force]"],
+        summary: "Add the pins written by pin --export",
+        details: "Aliases that are already pinned are kept and reported. Imported hooks need \
+                  trusting again with pin --trust.",
+        options: &[
+            ("--replace", "Replace aliases that are already pinned"),
+            ("--force, -f", "Replace locked aliases as well"),
+        ],
+        examples: &[
+            ("pin --import pins.txt", "Add the new pins from pins.txt"),
+            (
+                "pin --import pins.txt --replace",
+                "Make the store match pins.txt",
+            ),
+        ],
+        exits: &[22, 23],
+    },
+    CmdHelp {
+        name: "migrate-store",
+        flags: &["--migrate-store"],
+        usage: &["pin --migrate-store --to [bincode|toml|json|sqlite]"],
+        summary: "Copy every pin to another kind of store and use it from now on",
+        details: "Sets store in ~/.pin/config once every pin is copied. The old store is left \
+                  where it was, so it can be deleted once the new one works.",
+        options: &[("--to backend", "The store to move to")],
+        examples: &[(
+            "pin --migrate-store --to toml",
+            "Keep pins in ~/.pin/store.toml",
+        )],
+        exits: &[],
+    },
+    CmdHelp {
+        name: "init",
+        flags: &["--init"],
+        usage: &["_pin --init [bash|zsh|fish]"],
+        summary: "Print the shell wrapper",
+        details: "The wrapper defines the pin function, which runs _pin and changes directory, \
+                  opens files and runs hooks as it is told. Add it to the shell's startup file.",
+        options: &[],
+        examples: &[
+            (
+                "eval \"$(~/.pin/_pin --init bash)\"",
+                "Load pin in bash or zsh",
+            ),
+            ("~/.pin/_pin --init fish | source", "Load pin in fish"),
+        ],
+        exits: &[],
+    },
+];
+
+// The command named by a name from the table or one of its flags, with or without the dashes
+pub fn find(name: &str) -> Option<&'static CmdHelp> {
+    let flag = format!("--{name}");
+    COMMANDS
+        .iter()
+        .find(|cmd| cmd.name == name || cmd.flags.iter().any(|f| *f == name || *f == flag))
+}
+
+// The meaning of an exit code
+pub fn describe(code: i32) -> &'static str {
+    EXIT_CODES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map_or("", |(_, desc)| desc)
+}
+
+// Break text into lines of at most width characters
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// The full help for one command, for pin --help <cmd>
+pub fn text(cmd: &CmdHelp) -> String {
+    // Names that aren't flags, such as previous for -, still work but aren't shown
+    let flags: Vec<&str> = (cmd.flags.iter().copied())
+        .filter(|f| f.starts_with('-') && *f != cmd.name)
+        .collect();
+    let mut out = match flags.is_empty() {
+        true => format!("{}:\n", cmd.name),
+        false => format!("{} ({}):\n", cmd.name, flags.join(", ")),
+    };
+    for line in wrap(cmd.summary, 76) {
+        out += &format!("  {line}\n");
+    }
+
+    out += "\nUsage:\n";
+    for usage in cmd.usage {
+        out += &format!("  {usage}\n");
+    }
+
+    out += "\n";
+    for line in wrap(cmd.details, 76) {
+        out += &format!("  {line}\n");
+    }
+
+    if !cmd.options.is_empty() {
+        out += "\nOptions:\n";
+        let width = cmd.options.iter().map(|(o, _)| o.len()).max().unwrap_or(0);
+        for (option, desc) in cmd.options {
+            // Long descriptions carry on under the first line
+            let lines = wrap(desc, 76usize.saturating_sub(width + 2).max(30));
+            for (i, line) in lines.iter().enumerate() {
+                let option = if i == 0 { option } else { "" };
+                out += &format!("  {option:width$}  {line}\n");
+            }
+        }
+    }
+
+    out += "\nExamples:\n";
+    for (example, desc) in cmd.examples {
+        out += &format!("  {example}\n      {desc}\n");
+    }
+
+    if !cmd.exits.is_empty() {
+        out += "\nExit codes:\n";
+        for code in cmd.exits {
+            out += &format!("  {code:>3}  {}\n", describe(*code));
+        }
+    }
+    out
+}
+
+// Every command's help followed by the exit codes, for pin --help --all
+pub fn all() -> String {
+    let mut out = String::new();
+    for cmd in COMMANDS {
+        out += &text(cmd);
+        out += "\n";
+    }
+
+    out += "Exit codes:\n";
+    for (code, desc) in EXIT_CODES {
+        out += &format!("  {code:>3}  {desc}\n");
+    }
+    let common: Vec<String> = COMMON_EXITS.iter().map(|c| c.to_string()).collect();
+    out += &format!(
+        "\nAny command can also fail with {}. pin --exec returns the exit code of the command \
+         it ran instead.\n",
+        common.join(", ")
+    );
+    out
+}
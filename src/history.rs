@@ -0,0 +1,214 @@
+// Jump history
+//
+// Every jump made with pin is recorded in ~/.pin/history.bin, next to the store. The history works
+// like a browser's: jumping back and forward moves a cursor through it, and a new jump drops
+// anything ahead of the cursor. How often and when each alias was used is kept as well, past the
+// entries that are dropped, for sorting pins by frecency.
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::PinError;
+use crate::store::{Lock, pin_dir, write_atomic};
+
+// Oldest entries are dropped past this
+const MAX_ENTRIES: usize = 100;
+
+pub struct Entry {
+    pub alias: String,
+    pub path: String,
+    // Seconds since the unix epoch
+    pub time: u64,
+}
+
+// How much an alias has been jumped to
+#[derive(Clone, Copy, Default)]
+pub struct Usage {
+    pub count: u32,
+    // Seconds since the unix epoch
+    pub last: u64,
+}
+
+impl Usage {
+    // The count weighted by how recently the alias was used, like z and zoxide
+    pub fn frecency(&self) -> u64 {
+        let weight = match now().saturating_sub(self.last) {
+            0..3600 => 8,
+            3600..86400 => 4,
+            86400..604800 => 2,
+            _ => 1,
+        };
+        self.count as u64 * weight
+    }
+}
+
+pub struct History {
+    entries: Vec<Entry>,
+    // Index of the entry we are currently at
+    pos: usize,
+    usage: BTreeMap<String, Usage>,
+}
+
+type OnDisk = (u32, Vec<(String, String, u64)>);
+// Written after the entries, so older versions of pin still read the history
+type UsageOnDisk = Vec<(String, u32, u64)>;
+
+impl History {
+    // Read in the history or start a fresh one
+    pub fn load() -> Result<History, PinError> {
+        let bytes = match std::fs::read(pin_dir()?.join("history.bin")) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(History {
+                    entries: Vec::new(),
+                    pos: 0,
+                    usage: BTreeMap::new(),
+                });
+            }
+            Err(e) => return Err(PinError::StoreIo(e)),
+        };
+
+        let ((pos, entries), read): (OnDisk, _) =
+            bincode::decode_from_slice(&bytes, bincode::config::standard())
+                .map_err(|e| PinError::StoreDecode(format!("history: {e}")))?;
+        let entries: Vec<Entry> = entries
+            .into_iter()
+            .map(|(alias, path, time)| Entry { alias, path, time })
+            .collect();
+        let pos = (pos as usize).min(entries.len().saturating_sub(1));
+
+        // Histories from before usage was kept start from the jumps they still have
+        let usage = match read < bytes.len() {
+            true => {
+                let (usage, _): (UsageOnDisk, _) =
+                    bincode::decode_from_slice(&bytes[read..], bincode::config::standard())
+                        .map_err(|e| PinError::StoreDecode(format!("history: {e}")))?;
+                usage
+                    .into_iter()
+                    .map(|(alias, count, last)| (alias, Usage { count, last }))
+                    .collect()
+            }
+            false => {
+                let mut usage = BTreeMap::<String, Usage>::new();
+                for entry in &entries {
+                    let used = usage.entry(entry.alias.clone()).or_default();
+                    used.count += 1;
+                    used.last = used.last.max(entry.time);
+                }
+                usage
+            }
+        };
+
+        Ok(History {
+            entries,
+            pos,
+            usage,
+        })
+    }
+
+    // Change the history as it is on disk, holding its lock so jumps from other shells aren't lost
+    // and the cursor isn't moved from under them
+    pub fn update<T>(change: impl FnOnce(&mut History) -> T) -> Result<T, PinError> {
+        let _lock = Lock::acquire("history.lock")?;
+        let mut history = History::load()?;
+        let changed = change(&mut history);
+        history.save()?;
+        Ok(changed)
+    }
+
+    pub fn save(&self) -> Result<(), PinError> {
+        let entries = self
+            .entries
+            .iter()
+            .map(|e| (e.alias.clone(), e.path.clone(), e.time))
+            .collect();
+        let on_disk: OnDisk = (self.pos as u32, entries);
+        let usage: UsageOnDisk = self
+            .usage
+            .iter()
+            .map(|(alias, used)| (alias.clone(), used.count, used.last))
+            .collect();
+        let bytes = bincode::encode_to_vec((on_disk, usage), bincode::config::standard())
+            .map_err(|e| PinError::StoreIo(std::io::Error::other(e)))?;
+
+        write_atomic(&pin_dir()?.join("history.bin"), &bytes)
+    }
+
+    // Record a new jump, dropping anything we had gone back past
+    pub fn record(&mut self, alias: &str, path: &str) {
+        let time = now();
+        self.entries.truncate(self.pos + 1);
+        self.entries.push(Entry {
+            alias: alias.to_string(),
+            path: path.to_string(),
+            time,
+        });
+        let used = self.usage.entry(alias.to_string()).or_default();
+        used.count += 1;
+        used.last = time;
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.pos = self.entries.len() - 1;
+    }
+
+    // Move the cursor back n jumps, returning the entry landed on
+    pub fn back(&mut self, n: usize) -> Option<&Entry> {
+        if n == 0 || n > self.pos || self.entries.is_empty() {
+            return None;
+        }
+        self.pos -= n;
+        self.entries.get(self.pos)
+    }
+
+    // Move the cursor forward n jumps, returning the entry landed on
+    pub fn forward(&mut self, n: usize) -> Option<&Entry> {
+        if n == 0 || self.pos + n >= self.entries.len() {
+            return None;
+        }
+        self.pos += n;
+        self.entries.get(self.pos)
+    }
+
+    // The most recent pin other than the current one, like `cd -`
+    pub fn previous(&self) -> Option<&Entry> {
+        let current = self.entries.get(self.pos)?;
+        self.entries[..self.pos]
+            .iter()
+            .rev()
+            .find(|e| e.alias != current.alias)
+    }
+
+    // How often and when an alias has been jumped to
+    pub fn usage(&self, alias: &str) -> Usage {
+        self.usage.get(alias).copied().unwrap_or_default()
+    }
+
+    // Entries from newest to oldest, with whether each is the current one
+    pub fn recent(&self) -> impl Iterator<Item = (bool, &Entry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .rev()
+            .map(move |(i, e)| (i == self.pos, e))
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// How long ago a timestamp was, e.g. "5m ago"
+pub fn fmt_ago(time: u64) -> String {
+    let secs = now().saturating_sub(time);
+    match secs {
+        0..60 => format!("{secs}s ago"),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
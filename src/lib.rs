@@ -0,0 +1,65 @@
+//! Pin keeps short aliases for directories and files, so `pin api` jumps to wherever the api
+//! project lives. This crate is everything behind the `_pin` binary, for tools that want to read
+//! and change pins themselves, such as launchers and editor plugins.
+//!
+//! Pins live in a [`Store`], which maps each alias to a [`PinAlias`] record:
+//!
+//! ```no_run
+//! let store = pin::Store::init()?;
+//! for (alias, record) in store.iter() {
+//!     println!("{alias} -> {}", record.resolve()?);
+//! }
+//! # Ok::<(), pin::PinError>(())
+//! ```
+//!
+//! Changing the store takes its lock first, so two processes can't overwrite each other:
+//!
+//! ```no_run
+//! use pin::{PathPolicy, PinAlias, Store};
+//!
+//! let mut store = Store::lock()?;
+//! store.add("notes".to_string(), PinAlias::new("/home/me/notes".to_string(), PathPolicy::default()));
+//! store.save()?;
+//! # Ok::<(), pin::PinError>(())
+//! ```
+//!
+//! Whole commands can be run as the binary runs them. [`parse_args`] turns a command line into a
+//! [`Cmd`], which asks the user anything it needs to through a [`Terminal`] and returns an
+//! [`Outcome`] saying what is left for the shell to do.
+//!
+//! The pins in the store are kept in `~/.pin`, as `_pin` keeps them.
+
+#![warn(missing_docs)]
+
+mod alias;
+mod cli;
+mod cmd;
+mod config;
+mod directive;
+mod error;
+mod exec;
+mod expiry;
+mod heal;
+mod help;
+mod history;
+mod list;
+mod path;
+mod pattern;
+mod shell;
+mod storage;
+mod store;
+mod transfer;
+mod tty;
+mod tui;
+
+pub use alias::PinAlias;
+pub use cli::parse_args;
+pub use cmd::{Cmd, Outcome};
+pub use directive::Directive;
+pub use error::PinError;
+pub use path::PathPolicy;
+pub use pattern::Pattern;
+pub use storage::{Backend, Pins, Storage};
+pub use store::Store;
+pub use transfer::{Conflict, Imported, export, import};
+pub use tty::{Complete, DevTty, Edited, LineInput, Prompt, Terminal, Validate};
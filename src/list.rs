@@ -0,0 +1,607 @@
+// Listing pins
+//
+// pin --list shows the pins as a table that fits the terminal. Paths under $HOME are shown from ~,
+// and paths too long for the space left lose their middle rather than wrapping, since both ends
+// of a path say the most about it. The columns and order can be picked, and pins whose path is
+// missing or that point at the current directory stand out in colour.
+//
+// Instead of the table, pins can be grouped by tag, or drawn as a tree of the directories they
+// are in. The tree merges directories that only lead to one other, so `~`, `code` and `work`
+// become one `~/code/work` branch, and each branch shows how many pins are under it.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::alias::PinAlias;
+use crate::error::PinError;
+use crate::expiry::fmt_left;
+use crate::history::{Usage, fmt_ago};
+
+// Columns that shrink when the table is too wide, in order, with how narrow each can get
+const SHRINK: &[(Column, usize)] = &[
+    (Column::Path, 16),
+    (Column::Expanded, 16),
+    (Column::Note, 12),
+    (Column::Tags, 12),
+];
+
+const RED: &str = "\x1b[31m";
+const HIGHLIGHT: &str = "\x1b[1;36m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Column {
+    Alias,
+    Path,
+    Tags,
+    Uses,
+    LastUsed,
+    Exists,
+    Note,
+    Locked,
+    Expires,
+    Created,
+    Expanded,
+}
+
+impl Column {
+    pub const NAMES: &str =
+        "alias, path, tags, uses, last-used, exists, note, locked, expires, created or expanded";
+
+    pub fn parse(name: &str) -> Option<Column> {
+        Some(match name {
+            "alias" => Column::Alias,
+            "path" => Column::Path,
+            "tags" => Column::Tags,
+            "uses" => Column::Uses,
+            "last-used" => Column::LastUsed,
+            "exists" => Column::Exists,
+            "note" => Column::Note,
+            "locked" => Column::Locked,
+            "expires" => Column::Expires,
+            "created" => Column::Created,
+            "expanded" => Column::Expanded,
+            _ => return None,
+        })
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            Column::Alias => "Alias",
+            Column::Path => "Path",
+            Column::Tags => "Tags",
+            Column::Uses => "Uses",
+            Column::LastUsed => "Last used",
+            Column::Exists => "Exists",
+            Column::Note => "Note",
+            Column::Locked => "Locked",
+            Column::Expires => "Expires",
+            Column::Created => "Created",
+            Column::Expanded => "Expanded",
+        }
+    }
+
+    // The text for one pin
+    fn cell(self, row: &Row) -> String {
+        let record = row.record;
+        match self {
+            Column::Alias => row.alias.to_string(),
+            Column::Path => tilde(&record.path),
+            Column::Tags => record.tags.join(", "),
+            Column::Uses => row.usage.count.to_string(),
+            Column::LastUsed => match row.usage.last {
+                0 => "never".to_string(),
+                last => fmt_ago(last),
+            },
+            Column::Exists => if row.exists { "yes" } else { "no" }.to_string(),
+            Column::Note => record.note.clone(),
+            Column::Locked => if record.locked { "locked" } else { "" }.to_string(),
+            Column::Expires => match (record.session, record.expires) {
+                (true, Some(time)) => format!("{} or with shell", fmt_left(time)),
+                (true, None) => "with shell".to_string(),
+                (false, Some(time)) => fmt_left(time),
+                (false, None) => String::new(),
+            },
+            Column::Created => record.created.map(fmt_ago).unwrap_or_default(),
+            // Raw pins show what they expand to as well
+            Column::Expanded if record.policy.raw => tilde(&row.expanded),
+            Column::Expanded => String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sort {
+    Alias,
+    Path,
+    Frecency,
+    Recent,
+    Created,
+}
+
+impl Sort {
+    pub const NAMES: &str = "alias, path, frecency, recent or created";
+
+    pub fn parse(name: &str) -> Option<Sort> {
+        Some(match name {
+            "alias" => Sort::Alias,
+            "path" => Sort::Path,
+            "frecency" => Sort::Frecency,
+            "recent" => Sort::Recent,
+            "created" => Sort::Created,
+            _ => return None,
+        })
+    }
+}
+
+// How to group pins instead of showing a table
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Group {
+    Path,
+    Tag,
+}
+
+impl Group {
+    pub fn parse(name: &str) -> Option<Group> {
+        Some(match name {
+            "path" => Group::Path,
+            "tag" => Group::Tag,
+            _ => return None,
+        })
+    }
+}
+
+// When to colour the list
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(name: &str) -> Option<ColorChoice> {
+        Some(match name {
+            "auto" => ColorChoice::Auto,
+            "always" => ColorChoice::Always,
+            "never" => ColorChoice::Never,
+            _ => return None,
+        })
+    }
+
+    // Auto colours a real terminal unless NO_COLOR is set, see https://no-color.org
+    pub fn enabled(self, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        }
+    }
+}
+
+// A pin with everything the list shows about it
+pub struct Row<'a> {
+    pub alias: &'a str,
+    pub record: &'a PinAlias,
+    // The path to jump to, or why it can't be worked out
+    pub expanded: String,
+    pub exists: bool,
+    // Whether the pin points at the current directory
+    pub current: bool,
+    pub usage: Usage,
+}
+
+impl<'a> Row<'a> {
+    pub fn new(alias: &'a str, record: &'a PinAlias, usage: Usage, current: &[String]) -> Row<'a> {
+        let (expanded, exists) = match record.resolve() {
+            Ok(path) => {
+                let exists = Path::new(&path).exists();
+                (path, exists)
+            }
+            Err(PinError::UnsetVar { var, .. }) => (format!("(${var} is not set)"), false),
+            Err(_) => ("(unable to expand)".to_string(), false),
+        };
+
+        Row {
+            alias,
+            record,
+            current: current.contains(&expanded),
+            expanded,
+            exists,
+            usage,
+        }
+    }
+
+    // Whether the stored or expanded path contains the filter
+    pub fn matches(&self, filter: &str) -> bool {
+        self.record.path.contains(filter) || self.expanded.contains(filter)
+    }
+}
+
+// Put the rows in order. Frecency, recent and created put the most used, latest or newest first
+pub fn sort(rows: &mut [Row], sort: Sort, reverse: bool) {
+    rows.sort_by(|a, b| a.alias.cmp(b.alias));
+    match sort {
+        Sort::Alias => {}
+        Sort::Path => rows.sort_by(|a, b| a.expanded.cmp(&b.expanded)),
+        Sort::Frecency => rows.sort_by_key(|row| std::cmp::Reverse(row.usage.frecency())),
+        Sort::Recent => rows.sort_by_key(|row| std::cmp::Reverse(row.usage.last)),
+        Sort::Created => rows.sort_by_key(|row| std::cmp::Reverse(row.record.created)),
+    }
+    if reverse {
+        rows.reverse();
+    }
+}
+
+// The columns shown when none are asked for: the alias and path, and any others some pin uses
+fn default_columns(rows: &[Row]) -> Vec<Column> {
+    let mut columns = vec![Column::Alias, Column::Path];
+    let records = || rows.iter().map(|row| row.record);
+    if records().any(|record| record.locked) {
+        columns.push(Column::Locked);
+    }
+    if records().any(|record| record.policy.raw) {
+        columns.push(Column::Expanded);
+    }
+    if records().any(|record| !record.tags.is_empty()) {
+        columns.push(Column::Tags);
+    }
+    if records().any(|record| !record.note.is_empty()) {
+        columns.push(Column::Note);
+    }
+    if records().any(|record| record.session || record.expires.is_some()) {
+        columns.push(Column::Expires);
+    }
+    columns
+}
+
+// Render the rows as a table no wider than width, if it is known
+pub fn table(
+    rows: &[Row],
+    columns: Option<&[Column]>,
+    width: Option<usize>,
+    color: bool,
+) -> String {
+    let columns = match columns {
+        Some(columns) => columns.to_vec(),
+        None => default_columns(rows),
+    };
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|column| column.cell(row)).collect())
+        .collect();
+
+    // Each column is as wide as its widest cell, plus a border and a space either side
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.header().width()).collect();
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    if let Some(max) = width {
+        let mut total = 1 + widths.iter().map(|w| w + 3).sum::<usize>();
+        for (shrink, least) in SHRINK {
+            let Some(i) = columns.iter().position(|c| c == shrink) else {
+                continue;
+            };
+            let cut = total
+                .saturating_sub(max)
+                .min(widths[i].saturating_sub(*least));
+            widths[i] -= cut;
+            total -= cut;
+        }
+    }
+
+    // Drawn by hand rather than with prettytable, which miscounts the width of coloured cells
+    let border = widths.iter().fold("+".to_string(), |line, width| {
+        line + &"-".repeat(width + 2) + "+"
+    }) + "\n";
+    let line = |cells: Vec<String>, style: Option<&str>| {
+        let mut line = "|".to_string();
+        for (cell, width) in cells.iter().zip(&widths) {
+            let pad = " ".repeat(width.saturating_sub(cell.width()));
+            match style {
+                Some(style) if !cell.is_empty() => line += &format!(" {style}{cell}{RESET}{pad} |"),
+                _ => line += &format!(" {cell}{pad} |"),
+            }
+        }
+        line + "\n"
+    };
+
+    let mut table = border.clone();
+    table += &line(
+        columns.iter().map(|c| c.header().to_string()).collect(),
+        None,
+    );
+    table += &border;
+    for (row, cells) in rows.iter().zip(cells) {
+        let style = match (color, row.exists, row.current) {
+            (false, _, _) => None,
+            (true, false, _) => Some(RED),
+            (true, true, true) => Some(HIGHLIGHT),
+            (true, true, false) => None,
+        };
+        let cells = (cells.iter().zip(&columns).zip(&widths))
+            .map(|((cell, column), &width)| match column {
+                Column::Path | Column::Expanded => ellipsize_middle(cell, width),
+                _ => ellipsize_end(cell, width),
+            })
+            .collect();
+        table += &line(cells, style);
+        table += &border;
+    }
+    table
+}
+
+// A directory in the tree, with the pins that point straight at it
+#[derive(Default)]
+struct Node<'a> {
+    children: BTreeMap<String, Node<'a>>,
+    pins: Vec<&'a Row<'a>>,
+}
+
+impl<'a> Node<'a> {
+    fn insert(&mut self, parts: &[&str], row: &'a Row<'a>) {
+        match parts.split_first() {
+            Some((part, rest)) => {
+                (self.children.entry(part.to_string()).or_default()).insert(rest, row)
+            }
+            None => self.pins.push(row),
+        }
+    }
+
+    // How many pins are under this directory
+    fn count(&self) -> usize {
+        self.pins.len() + self.children.values().map(Node::count).sum::<usize>()
+    }
+
+    // Merge each directory that only leads to one other into it
+    fn collapse(mut self, mut name: String) -> (String, Node<'a>) {
+        while self.pins.is_empty() && self.children.len() == 1 {
+            let (child, node) = self.children.pop_first().expect("there is one child");
+            name = match name.as_str() {
+                "/" => format!("/{child}"),
+                _ => format!("{name}/{child}"),
+            };
+            self = node;
+        }
+        self.children = (std::mem::take(&mut self.children).into_iter())
+            .map(|(child, node)| node.collapse(child))
+            .collect();
+        (name, self)
+    }
+}
+
+// Draw a branch of the tree and everything under it
+fn draw(out: &mut String, name: &str, node: &Node, lead: (&str, &str), style: &Style) {
+    let (prefix, branch) = lead;
+    let aliases: Vec<String> = node.pins.iter().map(|row| style.alias(row)).collect();
+    let suffix = match (node.children.is_empty(), aliases.is_empty()) {
+        (true, _) => format!(": {}", aliases.join(", ")),
+        (false, true) => format!(" ({})", node.count()),
+        (false, false) => format!(" ({}): {}", node.count(), aliases.join(", ")),
+    };
+    let name = style.fit(
+        name,
+        prefix.width() + branch.width() + visible_width(&suffix),
+    );
+    *out += &format!("{prefix}{branch}{name}{suffix}\n");
+
+    // Children line up under this branch's name
+    let prefix = match branch {
+        "├── " => format!("{prefix}│   "),
+        "└── " => format!("{prefix}    "),
+        _ => prefix.to_string(),
+    };
+    let last = node.children.len().saturating_sub(1);
+    for (i, (child, node)) in node.children.iter().enumerate() {
+        let branch = if i == last {
+            "└── "
+        } else {
+            "├── "
+        };
+        draw(out, child, node, (&prefix, branch), style);
+    }
+}
+
+// Draw the pins as a tree of the directories they are in
+pub fn tree(rows: &[Row], width: Option<usize>, color: bool) -> String {
+    let mut root = Node::default();
+    let paths: Vec<String> = rows.iter().map(|row| tilde(&row.expanded)).collect();
+    for (row, path) in rows.iter().zip(&paths) {
+        // The leading / of an absolute path is a directory of its own
+        let parts: Vec<&str> = match path.strip_prefix('/') {
+            Some(rest) => std::iter::once("/").chain(rest.split('/')).collect(),
+            None => path.split('/').collect(),
+        };
+        let parts: Vec<&str> = (parts.into_iter().enumerate())
+            .filter(|(i, part)| *i == 0 || !part.is_empty())
+            .map(|(_, part)| part)
+            .collect();
+        root.insert(&parts, row);
+    }
+
+    let style = Style { width, color };
+    let mut out = String::new();
+    for (name, node) in root.children {
+        let (name, node) = node.collapse(name);
+        draw(&mut out, &name, &node, ("", ""), &style);
+    }
+    out
+}
+
+// List the pins under each of their tags, with untagged pins last
+pub fn by_tag(rows: &[Row], width: Option<usize>, color: bool) -> String {
+    let mut tags: BTreeMap<&str, Vec<&Row>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for row in rows {
+        for tag in &row.record.tags {
+            tags.entry(tag).or_default().push(row);
+        }
+        if row.record.tags.is_empty() {
+            untagged.push(row);
+        }
+    }
+
+    let style = Style { width, color };
+    let mut groups: Vec<(&str, Vec<&Row>)> = tags.into_iter().collect();
+    if !untagged.is_empty() {
+        groups.push(("(untagged)", untagged));
+    }
+    let mut out = String::new();
+    for (tag, rows) in groups {
+        out += &format!("{tag} ({})\n", rows.len());
+        let aliases = rows.iter().map(|row| row.alias.width()).max().unwrap_or(0);
+        let last = rows.len() - 1;
+        for (i, row) in rows.into_iter().enumerate() {
+            let branch = if i == last {
+                "└── "
+            } else {
+                "├── "
+            };
+            let pad = " ".repeat(aliases - row.alias.width());
+            let path = style.fit(&tilde(&row.expanded), branch.width() + aliases + 2);
+            out += &format!("{branch}{}{pad}  {path}\n", style.alias(row));
+        }
+    }
+    out
+}
+
+// How grouped pins are drawn
+struct Style {
+    width: Option<usize>,
+    color: bool,
+}
+
+impl Style {
+    // An alias, coloured like its row in the table
+    fn alias(&self, row: &Row) -> String {
+        match (self.color, row.exists, row.current) {
+            (true, false, _) => format!("{RED}{}{RESET}", row.alias),
+            (true, true, true) => format!("{HIGHLIGHT}{}{RESET}", row.alias),
+            _ => row.alias.to_string(),
+        }
+    }
+
+    // Shorten a path to fit on a line next to the given number of other columns
+    fn fit(&self, path: &str, used: usize) -> String {
+        match self.width {
+            Some(width) => ellipsize_middle(path, width.saturating_sub(used).max(12)),
+            None => path.to_string(),
+        }
+    }
+}
+
+// The width of text on screen, without its colours
+fn visible_width(text: &str) -> usize {
+    [RED, HIGHLIGHT, RESET]
+        .iter()
+        .fold(text.to_string(), |text, code| text.replace(code, ""))
+        .width()
+}
+
+// Show a path under $HOME from ~
+pub fn tilde(path: &str) -> String {
+    let Ok(home) = std::env::var("HOME") else {
+        return path.to_string();
+    };
+    match path.strip_prefix(home.trim_end_matches('/')) {
+        Some("") => "~".to_string(),
+        Some(rest) if rest.starts_with('/') && home != "/" => format!("~{rest}"),
+        _ => path.to_string(),
+    }
+}
+
+// As much of the text as fits in width columns
+pub fn take_width(chars: impl Iterator<Item = char>, width: usize) -> String {
+    let mut used = 0;
+    chars
+        .take_while(|c| {
+            used += c.width().unwrap_or(0);
+            used <= width
+        })
+        .collect()
+}
+
+// Cut the middle out of text wider than width, keeping both ends
+fn ellipsize_middle(text: &str, width: usize) -> String {
+    if text.width() <= width || width == 0 {
+        return text.to_string();
+    }
+    let keep = width - 1;
+    let head = take_width(text.chars(), keep / 2);
+    let tail: String = take_width(text.chars().rev(), keep - keep / 2);
+    format!("{head}…{}", tail.chars().rev().collect::<String>())
+}
+
+// Cut the end off text wider than width
+fn ellipsize_end(text: &str, width: usize) -> String {
+    if text.width() <= width || width == 0 {
+        return text.to_string();
+    }
+    format!("{}…", take_width(text.chars(), width - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ellipsis_keeps_both_ends() {
+        assert_eq!(ellipsize_middle("~/code/api", 10), "~/code/api");
+        assert_eq!(ellipsize_middle("~/code/work/api", 9), "~/co…/api");
+        assert_eq!(ellipsize_end("The backend", 6), "The b…");
+        // Wide characters count as two columns
+        assert_eq!(ellipsize_middle("~/写真/写真/api", 10), "~/写…/api");
+    }
+
+    #[test]
+    fn missing_paths_are_red_only_with_color() {
+        let record = PinAlias::new("/does/not/exist".to_string(), Default::default());
+        let rows = [Row::new("gone", &record, Usage::default(), &[])];
+
+        let table = table(&rows, Some(&[Column::Alias, Column::Exists]), None, true);
+        assert!(table.contains(&format!("| {RED}gone{RESET}  | {RED}no{RESET}     |")));
+        let table = super::table(&rows, Some(&[Column::Alias]), None, false);
+        assert!(!table.contains('\x1b'));
+    }
+
+    #[test]
+    fn tables_shrink_to_fit() {
+        let path = format!("/{}", "dir/".repeat(30));
+        let record = PinAlias::new(path, Default::default());
+        let rows = [Row::new("api", &record, Usage::default(), &[])];
+
+        let table = table(&rows, None, Some(40), false);
+        assert!(table.lines().all(|line| line.width() == 40), "{table}");
+        let table = super::table(&rows, None, None, false);
+        assert!(table.lines().all(|line| line.width() > 100), "{table}");
+    }
+
+    #[test]
+    fn trees_merge_single_children_and_fit() {
+        let long = format!("/srv/{}", "deep/".repeat(10));
+        let records = [
+            PinAlias::new("/srv/www/a".to_string(), Default::default()),
+            PinAlias::new("/srv/www/b".to_string(), Default::default()),
+            PinAlias::new(long, Default::default()),
+        ];
+        let rows: Vec<Row> = (["a", "b", "deep"].into_iter().zip(&records))
+            .map(|(alias, record)| Row::new(alias, record, Usage::default(), &[]))
+            .collect();
+
+        let tree = tree(&rows, None, false);
+        assert!(tree.starts_with("/srv (3)\n├── deep/deep/"), "{tree}");
+        assert!(
+            tree.ends_with("└── www (2)\n    ├── a: a\n    └── b: b\n"),
+            "{tree}"
+        );
+
+        let tree = super::tree(&rows, Some(30), false);
+        assert!(tree.contains("├── deep/deep…/deep/deep: deep\n"), "{tree}");
+    }
+}
@@ -1,103 +1,17 @@
-mod cmd;
-mod store;
-mod tty;
+// The _pin binary, run by the shell wrapper. Everything it does is in the pin library
 
-use cmd::*;
-
-use std::env::{self, Args, args};
-use std::path::PathBuf;
-
-//Check if a path is a valid path
-fn parse_path(path: &String) -> Result<PathBuf, &str> {
-    if path == &String::from("~") {
-        // If path is home
-        if let Ok(home) = env::var("HOME") {
-            let home = PathBuf::from(home);
-            Ok(home)
-        } else {
-            Err("Error: unable to find home directory")
-        }
-    } else if path.starts_with("~/") && path.len() > 2 {
-        // if path starts with home
-        if let Ok(home) = env::var("HOME") {
-            let mut home = PathBuf::from(home);
-            home.push((path.as_str()[2..]).to_string());
-            Ok(home)
-        } else {
-            Err("Error: unable to find home directory")
-        }
-    } else {
-        std::fs::canonicalize(path)
-            .map_err(|_| "Error: unable to canonicalise path. Check path exists")
-    }
-}
-
-// Return a parse error message
-fn err_parse_msg(missing: &str, cmd: &str) -> Box<ParseErr> {
-    Box::new(ParseErr {
-        msg: format!("Error: missing {missing} from command. Please use \"{cmd}\"."),
-    })
-}
-
-// Parse the arguments to the correct command struct, or a parse error
-// Maybe in the future limit argument numbers for each command
-fn parse_args(mut args: Args) -> Box<dyn Cmd> {
-    let _ = args.next();
-
-    match args.next().unwrap_or_default().as_str() {
-        "--add" | "-a" => {
-            // Get the alias or return ParseErr
-            let Some(alias) = args.next() else {
-                return err_parse_msg("alias", "pin --add [alias] [path]");
-            };
-
-            // Get the path or return ParseErr
-            let Some(path) = args.next() else {
-                return err_parse_msg("alias", "pin --add [alias] [path]");
-            };
-
-            // Return an add command
-            Box::new(Add { alias, path })
-        }
-        "--delete" | "-d" => {
-            // Get the alias or return ParseErr
-            let Some(alias) = args.next() else {
-                return err_parse_msg("alias", "pin --delete [alias]");
-            };
-
-            //
-            Box::new(Delete { alias })
-        }
-        "--help" | "-h" => Box::new(Help { cmd: args.next() }),
-        "--list" | "-l" => Box::new(List {
-            filter: args.next(),
-        }),
-        "--update" | "-u" => {
-            // Get the alias or return ParseErr
-            let Some(alias) = args.next() else {
-                return err_parse_msg("alias", "pin --update [alias]");
-            };
-
-            Box::new(Update { alias })
-        }
-        catch if catch.starts_with("-") => Box::new(ParseErr {
-            msg: format!(
-                "No function named {}, type --help to list all commands.",
-                catch
-            ),
-        }),
-        alias => Box::new(Pin {
-            alias: alias.to_string(),
-        }),
-    }
-}
+use pin::{DevTty, parse_args};
 
 // Entry point for the program
 fn main() {
-    let cmd = parse_args(args());
-    let code = cmd.execute();
+    let cmd = parse_args(std::env::args());
+    let code = match cmd.execute(&mut DevTty).and_then(|outcome| outcome.emit()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{e}");
+            e.code()
+        }
+    };
 
     std::process::exit(code);
 }
-
-//
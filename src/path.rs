@@ -0,0 +1,520 @@
+// Path parsing
+//
+// Every path given to pin goes through the same steps, so `~/x`, `./x` and `/abs/x` behave the
+// same way:
+//   1. `~`, `~/x` and `~user/x` are expanded, then `$VAR` and `${VAR}` unless the pin is literal
+//   2. Relative paths are made absolute from the current directory
+//   3. Symlinks are resolved (the default), or kept with `..` removed lexically, like `cd -L`
+//   4. The path has to exist, unless the pin allows missing paths
+//
+// The policy is stored with each pin so editing its path later follows the same rules. Raw pins
+// store the path as it was typed, e.g. `$PROJECTS/api`, and run these steps each time they are
+// used, so the same store works on machines with different layouts.
+
+use std::env;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::PinError;
+
+/// How a path given by the user is turned into the path that is stored.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct PathPolicy {
+    /// Keep symlinks in the path instead of resolving them.
+    pub keep_symlinks: bool,
+    /// Accept paths that don't exist yet.
+    pub allow_missing: bool,
+    /// Don't expand $VAR, for paths that really contain a $.
+    pub literal: bool,
+    /// Store the path unexpanded and expand it at jump time.
+    pub raw: bool,
+}
+
+impl PathPolicy {
+    /// The command line flags for each option, for usage messages.
+    pub const FLAGS: &str = "--keep-symlinks, --allow-missing, --literal, --raw";
+
+    /// Turn on the policy option for a command line flag. Returns false if it isn't a policy flag.
+    pub fn set_flag(&mut self, flag: &str) -> bool {
+        match flag {
+            "--keep-symlinks" => self.keep_symlinks = true,
+            "--allow-missing" => self.allow_missing = true,
+            "--literal" => self.literal = true,
+            "--raw" => self.raw = true,
+            _ => return false,
+        }
+        true
+    }
+}
+
+//Check if a path is a valid path
+pub fn parse_path(path: &str, policy: PathPolicy) -> Result<PathBuf, PinError> {
+    let cwd = env::current_dir()
+        .map_err(|e| PinError::InvalidPath(format!("unable to read current directory: {e}")))?;
+
+    normalize(path, policy, &cwd, &|name| env::var(name).ok())
+}
+
+// Parse a path and return the string to store for it. Raw pins keep what was typed once it has
+// been checked, so long as it doesn't depend on the current directory
+pub fn parse_for_store(path: &str, policy: PathPolicy) -> Result<String, PinError> {
+    let parsed = path_to_string(parse_path(path, policy)?)?;
+    if !policy.raw {
+        return Ok(parsed);
+    }
+
+    if !(path.starts_with(['~', '$', '/'])) {
+        return Err(PinError::InvalidPath(format!(
+            "raw path \"{path}\" has to start with ~, $VAR or /."
+        )));
+    }
+    Ok(path.to_string())
+}
+
+// Turn a stored path into the real path. Only raw pins need any work. A missing path isn't an
+// error here, so the caller can report it with the alias
+pub fn expand_stored(path: &str, policy: PathPolicy) -> Result<String, PinError> {
+    if !policy.raw {
+        return Ok(path.to_string());
+    }
+
+    let policy = PathPolicy {
+        allow_missing: true,
+        ..policy
+    };
+    let expanded = normalize(path, policy, Path::new("/"), &|name| env::var(name).ok()).map_err(
+        |e| match e {
+            PinError::UnsetVar { var, .. } => PinError::UnsetVar {
+                var,
+                path: path.to_string(),
+            },
+            e => e,
+        },
+    )?;
+    path_to_string(expanded)
+}
+
+// Convert a parsed path into the string stored in the store
+pub fn path_to_string(path: PathBuf) -> Result<String, PinError> {
+    path.into_os_string()
+        .into_string()
+        .map_err(|p| PinError::InvalidPath(format!("path {p:?} is not valid UTF-8.")))
+}
+
+// Apply the policy to a path. The current directory and environment are passed in so this can be
+// tested without touching the real ones
+pub fn normalize(
+    path: &str,
+    policy: PathPolicy,
+    cwd: &Path,
+    var: &dyn Fn(&str) -> Option<String>,
+) -> Result<PathBuf, PinError> {
+    if path.is_empty() {
+        return Err(PinError::InvalidPath("path is empty.".to_string()));
+    }
+
+    let mut expanded = expand_tilde(path, var)?;
+    if !policy.literal {
+        expanded = expand_vars(&expanded, var)?;
+    }
+
+    let absolute = cwd.join(expanded);
+    let lexical = lexical_normalize(&absolute);
+
+    if policy.keep_symlinks {
+        if !policy.allow_missing && !lexical.exists() {
+            return Err(missing(path));
+        }
+        return Ok(lexical);
+    }
+
+    match std::fs::canonicalize(&absolute) {
+        Ok(path) => Ok(path),
+        Err(e) if e.kind() == ErrorKind::NotFound && policy.allow_missing => {
+            // Resolve as much of the path as exists and keep the rest as written
+            let mut existing = lexical.as_path();
+            let mut rest = Vec::new();
+            while !existing.exists() {
+                rest.push(existing.file_name().unwrap_or_default().to_owned());
+                existing = existing.parent().unwrap_or(Path::new("/"));
+            }
+            let mut resolved = std::fs::canonicalize(existing).map_err(|_| missing(path))?;
+            resolved.extend(rest.iter().rev());
+            Ok(resolved)
+        }
+        Err(_) => Err(missing(path)),
+    }
+}
+
+fn missing(path: &str) -> PinError {
+    PinError::InvalidPath(format!(
+        "unable to canonicalise path \"{path}\". Check path exists."
+    ))
+}
+
+// Expand a leading `~` or `~user`
+fn expand_tilde(path: &str, var: &dyn Fn(&str) -> Option<String>) -> Result<String, PinError> {
+    let Some(rest) = path.strip_prefix('~') else {
+        return Ok(path.to_string());
+    };
+
+    let (user, rest) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let home = if user.is_empty() {
+        var("HOME").ok_or(PinError::NoHome)?
+    } else {
+        user_home(user).ok_or_else(|| PinError::InvalidPath(format!("unknown user \"{user}\".")))?
+    };
+
+    Ok(format!("{home}{rest}"))
+}
+
+// Look up a user's home directory through the system's user database, so users from LDAP and
+// the like are found as well as those in /etc/passwd
+fn user_home(user: &str) -> Option<String> {
+    let name = std::ffi::CString::new(user).ok()?;
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+
+    loop {
+        // SAFETY: getpwnam_r only writes to pwd and buf, and result points at pwd or is null
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        let err = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        match err {
+            // The entry didn't fit, so try again with more room
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            0 if !result.is_null() && !pwd.pw_dir.is_null() => {
+                // SAFETY: pw_dir is a nul terminated string in buf
+                let dir = unsafe { std::ffi::CStr::from_ptr(pwd.pw_dir) };
+                return dir.to_str().ok().map(String::from);
+            }
+            _ => return None,
+        }
+    }
+}
+
+// Expand `$VAR` and `${VAR}`. A `$` not followed by a name is kept as is
+pub fn expand_vars(path: &str, var: &dyn Fn(&str) -> Option<String>) -> Result<String, PinError> {
+    let mut out = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(i) = rest.find('$') {
+        out += &rest[..i];
+        rest = &rest[i + 1..];
+
+        let (name, after) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => {
+                    return Err(PinError::InvalidPath(format!(
+                        "unclosed \"${{\" in \"{path}\"."
+                    )));
+                }
+            }
+        } else {
+            let end = rest
+                .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+
+        if name.is_empty() {
+            out.push('$');
+            continue;
+        }
+        match var(name) {
+            Some(value) => out += &value,
+            None => {
+                return Err(PinError::UnsetVar {
+                    var: name.to_string(),
+                    path: path.to_string(),
+                });
+            }
+        }
+        rest = after;
+    }
+    out += rest;
+
+    Ok(out)
+}
+
+// Remove `.` and `..` without looking at the filesystem
+fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    // A fresh directory for each test, with its canonical path. It is deleted when the TempDir
+    // drops
+    fn scratch() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().canonicalize().unwrap();
+        (dir, path)
+    }
+
+    fn vars(home: &Path) -> impl Fn(&str) -> Option<String> {
+        let home = home.to_string_lossy().into_owned();
+        move |name| match name {
+            "HOME" => Some(home.clone()),
+            "PROJECTS" => Some(format!("{home}/projects")),
+            _ => None,
+        }
+    }
+
+    fn parse(path: &str, policy: PathPolicy, dir: &Path) -> Result<PathBuf, PinError> {
+        normalize(path, policy, dir, &vars(dir))
+    }
+
+    const MISSING: PathPolicy = PathPolicy {
+        keep_symlinks: false,
+        allow_missing: true,
+        literal: false,
+        raw: false,
+    };
+
+    const KEEP: PathPolicy = PathPolicy {
+        keep_symlinks: true,
+        allow_missing: false,
+        literal: false,
+        raw: false,
+    };
+
+    #[test]
+    fn home_is_expanded() {
+        let (_scratch, dir) = scratch();
+        std::fs::create_dir(dir.join("code")).unwrap();
+
+        let policy = PathPolicy::default();
+        assert_eq!(parse("~", policy, &dir).unwrap(), dir);
+        assert_eq!(parse("~/code", policy, &dir).unwrap(), dir.join("code"));
+        assert_eq!(parse("~/code/", policy, &dir).unwrap(), dir.join("code"));
+    }
+
+    #[test]
+    fn home_and_relative_paths_are_checked_the_same() {
+        let (_scratch, dir) = scratch();
+
+        let policy = PathPolicy::default();
+        assert!(matches!(
+            parse("~/nonexistent", policy, &dir),
+            Err(PinError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            parse("./nonexistent", policy, &dir),
+            Err(PinError::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn missing_home_is_an_error() {
+        let (_scratch, dir) = scratch();
+        let result = normalize("~/x", PathPolicy::default(), &dir, &|_| None);
+        assert!(matches!(result, Err(PinError::NoHome)));
+    }
+
+    #[test]
+    fn user_home_is_expanded() {
+        let (_scratch, dir) = scratch();
+        if let Some(root) = user_home("root").filter(|home| Path::new(home).exists()) {
+            let expected = Path::new(&root).canonicalize().unwrap();
+            assert_eq!(
+                parse("~root", PathPolicy::default(), &dir).unwrap(),
+                expected
+            );
+        }
+        assert!(matches!(
+            parse("~no-such-user-here/x", PathPolicy::default(), &dir),
+            Err(PinError::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn variables_are_expanded() {
+        let (_scratch, dir) = scratch();
+        std::fs::create_dir_all(dir.join("projects/api")).unwrap();
+
+        let expected = dir.join("projects/api");
+        let policy = PathPolicy::default();
+        assert_eq!(parse("$PROJECTS/api", policy, &dir).unwrap(), expected);
+        assert_eq!(parse("${PROJECTS}/api", policy, &dir).unwrap(), expected);
+        assert_eq!(parse("$HOME/projects/api", policy, &dir).unwrap(), expected);
+    }
+
+    #[test]
+    fn unset_variables_are_an_error() {
+        let (_scratch, dir) = scratch();
+        assert!(matches!(
+            parse("$NOPE/x", MISSING, &dir),
+            Err(PinError::UnsetVar { var, .. }) if var == "NOPE"
+        ));
+        assert!(matches!(
+            parse("${PROJECTS/x", MISSING, &dir),
+            Err(PinError::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn literal_paths_keep_dollars() {
+        let (_scratch, dir) = scratch();
+        std::fs::create_dir(dir.join("$NOPE")).unwrap();
+
+        let policy = PathPolicy {
+            literal: true,
+            ..Default::default()
+        };
+        assert_eq!(parse("$NOPE", policy, &dir).unwrap(), dir.join("$NOPE"));
+        // A lone dollar isn't a variable
+        assert_eq!(expand_vars("a$/b$", &|_| None).unwrap(), "a$/b$");
+    }
+
+    #[test]
+    fn relative_paths_are_made_absolute() {
+        let (_scratch, dir) = scratch();
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+
+        let policy = PathPolicy::default();
+        assert_eq!(parse("a/b", policy, &dir).unwrap(), dir.join("a/b"));
+        assert_eq!(parse("./a/./b", policy, &dir).unwrap(), dir.join("a/b"));
+        assert_eq!(parse(".", policy, &dir).unwrap(), dir);
+    }
+
+    #[test]
+    fn symlinks_are_resolved_by_default() {
+        let (_scratch, dir) = scratch();
+        std::fs::create_dir_all(dir.join("real/inner")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real/inner"), dir.join("link")).unwrap();
+
+        let policy = PathPolicy::default();
+        assert_eq!(parse("link", policy, &dir).unwrap(), dir.join("real/inner"));
+        // `..` is physical, so it goes to the parent of the target
+        assert_eq!(parse("link/..", policy, &dir).unwrap(), dir.join("real"));
+    }
+
+    #[test]
+    fn symlinks_can_be_kept() {
+        let (_scratch, dir) = scratch();
+        std::fs::create_dir_all(dir.join("real/inner")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real/inner"), dir.join("link")).unwrap();
+
+        assert_eq!(parse("link", KEEP, &dir).unwrap(), dir.join("link"));
+        // `..` is lexical, so it goes back to where the link is
+        assert_eq!(parse("link/..", KEEP, &dir).unwrap(), dir);
+        assert!(parse("link/nope", KEEP, &dir).is_err());
+    }
+
+    #[test]
+    fn missing_paths_can_be_allowed() {
+        let (_scratch, dir) = scratch();
+        std::fs::create_dir(dir.join("real")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+
+        // The existing part is still resolved
+        assert_eq!(
+            parse("link/new/../later", MISSING, &dir).unwrap(),
+            dir.join("real/later")
+        );
+        assert_eq!(
+            parse("~/not/yet", MISSING, &dir).unwrap(),
+            dir.join("not/yet")
+        );
+
+        let keep_missing = PathPolicy {
+            allow_missing: true,
+            ..KEEP
+        };
+        assert_eq!(
+            parse("link/later", keep_missing, &dir).unwrap(),
+            dir.join("link/later")
+        );
+    }
+
+    #[test]
+    fn parent_of_root_is_root() {
+        let (_scratch, dir) = scratch();
+        assert_eq!(
+            parse("/../..", PathPolicy::default(), &dir).unwrap(),
+            PathBuf::from("/")
+        );
+        assert_eq!(parse("/../..", KEEP, &dir).unwrap(), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn empty_path_is_an_error() {
+        let (_scratch, dir) = scratch();
+        assert!(parse("", PathPolicy::default(), &dir).is_err());
+    }
+
+    #[test]
+    fn policy_flags() {
+        let mut policy = PathPolicy::default();
+        assert!(policy.set_flag("--keep-symlinks"));
+        assert!(policy.set_flag("--allow-missing"));
+        assert!(!policy.set_flag("--nope"));
+        assert_eq!(
+            policy,
+            PathPolicy {
+                keep_symlinks: true,
+                allow_missing: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn raw_paths_are_stored_as_typed() {
+        let raw = PathPolicy {
+            raw: true,
+            allow_missing: true,
+            ..Default::default()
+        };
+
+        assert_eq!(parse_for_store("~/later", raw).unwrap(), "~/later");
+        assert_eq!(parse_for_store("/nope/x", raw).unwrap(), "/nope/x");
+        assert!(matches!(
+            parse_for_store("relative", raw),
+            Err(PinError::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn stored_paths_are_only_expanded_when_raw() {
+        let plain = PathPolicy::default();
+        assert_eq!(expand_stored("/a/$X", plain).unwrap(), "/a/$X");
+
+        let raw = PathPolicy {
+            raw: true,
+            ..Default::default()
+        };
+        assert_eq!(expand_stored("/nope/./x", raw).unwrap(), "/nope/x");
+        assert!(matches!(
+            expand_stored("$PIN_TEST_SURELY_UNSET/x", raw),
+            Err(PinError::UnsetVar { var, path })
+                if var == "PIN_TEST_SURELY_UNSET" && path == "$PIN_TEST_SURELY_UNSET/x"
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+// Selecting many aliases at once
+//
+// Commands that work on aliases also take a pattern. A glob uses *, ? and [...] as a shell does,
+// e.g. tmp-*, and anything starting with re: is a regular expression, e.g. re:^client-(a|b)-.
+// Both have to match the whole alias, not just part of it.
+
+use regex::Regex;
+
+use crate::alias::PinAlias;
+use crate::error::PinError;
+
+/// A glob or regular expression matching whole aliases.
+pub struct Pattern(Regex);
+
+impl Pattern {
+    /// Parse a pattern, or None if the text is just an alias.
+    pub fn parse(text: &str) -> Result<Option<Pattern>, PinError> {
+        let regex = if let Some(regex) = text.strip_prefix("re:") {
+            format!("^(?:{regex})$")
+        } else if text.contains(['*', '?', '[']) {
+            glob_to_regex(text)
+        } else {
+            return Ok(None);
+        };
+
+        Regex::new(&regex)
+            .map(|regex| Some(Pattern(regex)))
+            .map_err(|e| PinError::Usage(format!("\"{text}\" is not a valid pattern: {e}")))
+    }
+
+    /// Whether the pattern matches the whole alias.
+    pub fn matches(&self, alias: &str) -> bool {
+        self.0.is_match(alias)
+    }
+}
+
+// Where the text matches a pin, lower being better, or None if it doesn't. The text must already
+// be lowercase
+pub fn search_rank(alias: &str, record: &PinAlias, text: &str) -> Option<u8> {
+    let alias = alias.to_lowercase();
+    if alias == text {
+        Some(0)
+    } else if alias.starts_with(text) {
+        Some(1)
+    } else if alias.contains(text) {
+        Some(2)
+    } else if record.path.to_lowercase().contains(text) {
+        Some(3)
+    } else if record.tags.iter().any(|t| t.to_lowercase().contains(text)) {
+        Some(4)
+    } else if record.note.to_lowercase().contains(text) {
+        Some(5)
+    } else {
+        None
+    }
+}
+
+// Translate a glob into an anchored regular expression
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            // Without a closing ] the [ is just a character
+            '[' => match class(chars.as_str()) {
+                Some((class, rest)) => {
+                    regex.push_str(&class);
+                    chars = rest.chars();
+                }
+                None => regex.push_str(r"\["),
+            },
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+// Translate a class, given the text after its [, returning it with the text after its ]. ! or ^
+// first negates it as in the shell, and a ] straight after that is part of the class rather than
+// its end. None if the class never ends
+fn class(text: &str) -> Option<(String, &str)> {
+    let mut class = String::from("[");
+    let mut chars = text.char_indices().peekable();
+    if chars.next_if(|(_, c)| matches!(c, '!' | '^')).is_some() {
+        class.push('^');
+    }
+    if chars.next_if(|(_, c)| *c == ']').is_some() {
+        class.push_str(r"\]");
+    }
+
+    // A - between two characters is a range, anywhere else it is just a -
+    let mut can_range = class.ends_with(']');
+    while let Some((i, c)) = chars.next() {
+        match c {
+            ']' => {
+                class.push(']');
+                return Some((class, &text[i + 1..]));
+            }
+            '-' if can_range && chars.peek().is_some_and(|(_, c)| *c != ']') => {
+                class.push('-');
+                can_range = false;
+                continue;
+            }
+            _ => class.push_str(&regex::escape(&c.to_string())),
+        }
+        can_range = true;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Which of the aliases the pattern matches
+    fn matching<'a>(pattern: &str, aliases: &[&'a str]) -> Vec<&'a str> {
+        let pattern = Pattern::parse(pattern).unwrap().unwrap();
+        aliases
+            .iter()
+            .copied()
+            .filter(|alias| pattern.matches(alias))
+            .collect()
+    }
+
+    #[test]
+    fn plain_aliases_are_not_patterns() {
+        assert!(Pattern::parse("api").unwrap().is_none());
+        assert!(Pattern::parse("client-a.v2").unwrap().is_none());
+    }
+
+    #[test]
+    fn stars_and_question_marks() {
+        let aliases = ["tmp-", "tmp-a", "tmp-ab", "xtmp-a", "tmp"];
+        assert_eq!(matching("tmp-*", &aliases), ["tmp-", "tmp-a", "tmp-ab"]);
+        assert_eq!(matching("tmp-?", &aliases), ["tmp-a"]);
+        assert_eq!(matching("*a", &aliases), ["tmp-a", "xtmp-a"]);
+    }
+
+    #[test]
+    fn classes() {
+        let aliases = ["a", "b", "c", "x", "-", "]", "!"];
+        assert_eq!(matching("[a-b]", &aliases), ["a", "b"]);
+        assert_eq!(matching("[!x]", &aliases), ["a", "b", "c", "-", "]", "!"]);
+        assert_eq!(matching("[^a-c]", &aliases), ["x", "-", "]", "!"]);
+        assert_eq!(matching("[]a]", &aliases), ["a", "]"]);
+        assert_eq!(matching("[!]a]", &aliases), ["b", "c", "x", "-", "!"]);
+        assert_eq!(matching("[a-]", &aliases), ["a", "-"]);
+        assert_eq!(matching("[-x]", &aliases), ["x", "-"]);
+        assert_eq!(matching("[x!]", &aliases), ["x", "!"]);
+    }
+
+    #[test]
+    fn an_unclosed_bracket_is_literal() {
+        let aliases = ["a[b", "ab", "a[", "[!"];
+        assert_eq!(matching("a[b", &aliases), ["a[b"]);
+        assert_eq!(matching("a[*", &aliases), ["a[b", "a["]);
+        assert_eq!(matching("[!*", &aliases), ["[!"]);
+    }
+
+    #[test]
+    fn regex_matches_the_whole_alias() {
+        let aliases = ["api", "api2", "my-api", "a", "b", "ab"];
+        assert_eq!(matching("re:api", &aliases), ["api"]);
+        assert_eq!(matching("re:a|b", &aliases), ["a", "b"]);
+        assert_eq!(matching("re:.*api.*", &aliases), ["api", "api2", "my-api"]);
+    }
+
+    #[test]
+    fn invalid_patterns_are_usage_errors() {
+        for pattern in ["re:(", "re:[a", "[z-a]"] {
+            assert!(
+                matches!(Pattern::parse(pattern), Err(PinError::Usage(_))),
+                "{pattern}"
+            );
+        }
+    }
+}
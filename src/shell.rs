@@ -0,0 +1,180 @@
+// Shell wrappers
+//
+// _pin can't change the directory of the shell that runs it, so each shell gets a small wrapper
+// function that runs _pin and acts on its exit code and directives. `_pin --init <shell>` prints
+// the wrapper for a shell so it can be sourced or eval'd from the shell's rc file.
+
+use crate::directive::PROTOCOL_VERSION;
+
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub const NAMES: &str = "bash, zsh, fish";
+
+    pub fn parse(name: &str) -> Option<Shell> {
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+
+    // The wrapper for this shell, calling _pin at exe
+    pub fn snippet(&self, exe: &str) -> String {
+        let (name, template, exe) = match self {
+            Shell::Bash => ("bash", POSIX, posix_quote(exe)),
+            Shell::Zsh => ("zsh", POSIX, posix_quote(exe)),
+            Shell::Fish => ("fish", FISH, fish_quote(exe)),
+        };
+
+        template
+            .replace("{shell}", name)
+            .replace("{exe}", &exe)
+            .replace("{version}", &PROTOCOL_VERSION.to_string())
+    }
+}
+
+// Single quote a string for bash and zsh
+fn posix_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+// Single quote a string for fish, which allows escapes inside single quotes
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+// bash and zsh share the same wrapper
+const POSIX: &str = r#"# pin wrapper for {shell}, generated by `_pin --init {shell}`
+
+__pin_decode() {
+	printf -v "$1" '%b' "${2//\%/\\x}"
+}
+
+__pin_apply() {
+	local line op rest a b first=1
+	while IFS= read -r line; do
+		if [ "$first" = 1 ]; then
+			first=0
+			if [ "$line" != "pin-directives {version}" ]; then
+				echo "pin: unsupported directive output: $line" >&2
+				return 1
+			fi
+			continue
+		fi
+		op=${line%%$'\t'*}
+		rest=${line#*$'\t'}
+		__pin_decode a "${rest%%$'\t'*}"
+		__pin_decode b "${rest#*$'\t'}"
+		case "$op" in
+			cd) cd -- "$a" || return ;;
+			pushd) pushd -- "$a" >/dev/null || return ;;
+			setenv) export "$a=$b" ;;
+			run) eval "$a" ;;
+			echo) printf '%s\n' "$a" ;;
+			open) __pin_open "$a" "$b" ;;
+			*) echo "pin: unknown directive: $op" >&2 ;;
+		esac
+	done <<< "$1"
+}
+
+# Open a file with the given command, or the user's editor. The path is passed by reference so it
+# is never evaluated as code. The locals are prefixed as zsh ties path to PATH
+__pin_open() {
+	local __pin_file="$1" __pin_opener="${2:-${VISUAL:-${EDITOR:-vi}}}"
+	eval "$__pin_opener \"\$__pin_file\""
+}
+
+pin() {
+	local out code arg
+
+	# Commands run with `pin <alias> -- cmd` need the terminal, and their exit code is their own
+	for arg in "$@"; do
+		if [ "$arg" = "--" ]; then
+			PIN_PROTOCOL={version} PIN_SESSION=$$ {exe} "$@"
+			return
+		fi
+	done
+
+	out=$(PIN_PROTOCOL={version} PIN_SESSION=$$ {exe} "$@")
+	code=$?
+
+	case "$code" in
+		0) ;;
+		2) cd -- "$out" ;;
+		3) __pin_apply "$out" ;;
+		4) __pin_open "$out" ;;
+		*) [ -n "$out" ] && printf '%s\n' "$out"; return "$code" ;;
+	esac
+}
+"#;
+
+const FISH: &str = r#"# pin wrapper for fish, generated by `_pin --init fish`
+
+function __pin_apply
+    set -l lines (string split \n -- $argv[1])
+    if test "$lines[1]" != "pin-directives {version}"
+        echo "pin: unsupported directive output: $lines[1]" >&2
+        return 1
+    end
+    for line in $lines[2..-1]
+        set -l fields (string split \t -- $line)
+        set -l a (string unescape --style=url -- "$fields[2]" | string collect)
+        set -l b (string unescape --style=url -- "$fields[3]" | string collect)
+        switch $fields[1]
+            case cd
+                cd $a; or return
+            case pushd
+                pushd $a; or return
+            case setenv
+                set -gx $a $b
+            case run
+                eval $a
+            case echo
+                printf '%s\n' $a
+            case open
+                __pin_open "$a" "$b"
+            case '*'
+                echo "pin: unknown directive: $fields[1]" >&2
+        end
+    end
+end
+
+# Open a file with the given command, or the user's editor
+function __pin_open
+    set -l opener $argv[2]
+    test -z "$opener"; and set opener $VISUAL
+    test -z "$opener"; and set opener $EDITOR
+    test -z "$opener"; and set opener vi
+    eval $opener (string escape -- $argv[1])
+end
+
+function pin
+    # Commands run with `pin <alias> -- cmd` need the terminal, and their exit code is their own
+    if contains -- -- $argv
+        env PIN_PROTOCOL={version} PIN_SESSION=$fish_pid {exe} $argv
+        return
+    end
+
+    set -l out (env PIN_PROTOCOL={version} PIN_SESSION=$fish_pid {exe} $argv | string collect)
+    set -l code $pipestatus[1]
+
+    switch $code
+        case 0
+        case 2
+            cd $out
+        case 3
+            __pin_apply $out
+        case 4
+            __pin_open $out
+        case '*'
+            test -n "$out"; and printf '%s\n' $out
+            return $code
+    end
+end
+"#;
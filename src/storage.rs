@@ -0,0 +1,921 @@
+// Where the store keeps its pins
+//
+// The store reads its pins through a Storage when it opens and writes them back through it when
+// saved. Which backend is used is set with `store = ...` in ~/.pin/config:
+//
+//   bincode  ~/.pin/store.bin   the default, small and quick to read
+//   toml     ~/.pin/store.toml  for editing by hand
+//   json     ~/.pin/store.json  for editing by hand or with other tools
+//   sqlite   ~/.pin/store.db    for thousands of pins, changed a row at a time
+//
+// `pin --migrate-store --to <backend>` copies the pins into another backend and switches the
+// config over. Session pins are always kept in bincode files of their own, as they only last as
+// long as a shell.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::alias::{Fields, PinAlias};
+use crate::config::Config;
+use crate::error::PinError;
+use crate::path::PathPolicy;
+use crate::store::{pin_dir, write_atomic};
+
+/// Pins by alias.
+pub type Pins = BTreeMap<String, PinAlias>;
+
+/// Somewhere pins are kept. Only [`load`](Storage::load) and [`save`](Storage::save) have to be
+/// written for a new backend, as the rest default to reading and writing every pin. Nothing here
+/// stops two processes changing the pins at once, so hold the store lock, e.g. by taking the pins
+/// through [`Store::lock`](crate::Store::lock).
+pub trait Storage {
+    /// Read every pin. Storage that doesn't exist yet has none.
+    fn load(&mut self) -> Result<Pins, PinError>;
+
+    /// Replace every pin.
+    fn save(&mut self, pins: &Pins) -> Result<(), PinError>;
+
+    /// Read one pin.
+    fn get(&mut self, alias: &str) -> Result<Option<PinAlias>, PinError> {
+        Ok(self.load()?.remove(alias))
+    }
+
+    /// Add a pin, or replace the one with the same alias.
+    fn put(&mut self, alias: &str, record: &PinAlias) -> Result<(), PinError> {
+        self.transaction(&mut |pins| {
+            pins.insert(alias.to_string(), record.clone());
+            Ok(())
+        })
+    }
+
+    /// Remove a pin, returning it.
+    fn delete(&mut self, alias: &str) -> Result<Option<PinAlias>, PinError> {
+        let mut removed = None;
+        self.transaction(&mut |pins| {
+            removed = pins.remove(alias);
+            Ok(())
+        })?;
+        Ok(removed)
+    }
+
+    /// Every pin, in alias order.
+    fn iter(&mut self) -> Result<Box<dyn Iterator<Item = (String, PinAlias)>>, PinError> {
+        Ok(Box::new(self.load()?.into_iter()))
+    }
+
+    /// Change the pins all at once. If `change` fails nothing is written.
+    fn transaction(
+        &mut self,
+        change: &mut dyn FnMut(&mut Pins) -> Result<(), PinError>,
+    ) -> Result<(), PinError> {
+        let mut pins = self.load()?;
+        change(&mut pins)?;
+        self.save(&pins)
+    }
+}
+
+/// The kinds of storage a store can use.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Backend {
+    /// A compact binary file, `~/.pin/store.bin`. The default.
+    Bincode,
+    /// A TOML file, `~/.pin/store.toml`.
+    Toml,
+    /// A JSON file, `~/.pin/store.json`.
+    Json,
+    /// An SQLite database, `~/.pin/store.db`.
+    Sqlite,
+}
+
+impl Backend {
+    /// The names backends go by in the config and on the command line.
+    pub const NAMES: &str = "bincode, toml, json, sqlite";
+
+    /// The backend with this name.
+    pub fn parse(name: &str) -> Option<Backend> {
+        match name {
+            "bincode" => Some(Backend::Bincode),
+            "toml" => Some(Backend::Toml),
+            "json" => Some(Backend::Json),
+            "sqlite" => Some(Backend::Sqlite),
+            _ => None,
+        }
+    }
+
+    /// The name of the backend.
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Bincode => "bincode",
+            Backend::Toml => "toml",
+            Backend::Json => "json",
+            Backend::Sqlite => "sqlite",
+        }
+    }
+
+    /// Where the backend keeps the pins.
+    pub fn path(self) -> Result<PathBuf, PinError> {
+        let file = match self {
+            Backend::Bincode => "store.bin",
+            Backend::Toml => "store.toml",
+            Backend::Json => "store.json",
+            Backend::Sqlite => "store.db",
+        };
+        Ok(pin_dir()?.join(file))
+    }
+
+    /// The backend set by `store` in `~/.pin/config`, or bincode if it isn't set.
+    pub fn configured() -> Result<Backend, PinError> {
+        match Config::load()?.get("store") {
+            None => Ok(Backend::Bincode),
+            Some(name) => Backend::parse(name).ok_or_else(|| {
+                PinError::Config(format!(
+                    "store should be one of {}, not \"{name}\".",
+                    Backend::NAMES
+                ))
+            }),
+        }
+    }
+
+    /// Open the backend's storage in `~/.pin`.
+    pub fn open(self) -> Result<Box<dyn Storage>, PinError> {
+        let path = self.path()?;
+        Ok(match self {
+            Backend::Bincode => Box::new(Bincode { path }),
+            Backend::Toml => Box::new(Text {
+                path,
+                format: Format::Toml,
+            }),
+            Backend::Json => Box::new(Text {
+                path,
+                format: Format::Json,
+            }),
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => Box::new(sqlite::Sqlite::open(&path)?),
+            #[cfg(not(feature = "sqlite"))]
+            Backend::Sqlite => {
+                return Err(PinError::Config(
+                    "store = sqlite needs pin built with the sqlite feature.".to_string(),
+                ));
+            }
+        })
+    }
+}
+
+// The original store file. After a header, the records are sorted by alias behind an index of
+// where each one starts, so one pin can be found by binary search without decoding the others.
+// All numbers after the header are little endian u32s:
+//
+//   header   ("pin-store", version) in bincode
+//   count    how many records there are
+//   offsets  where each record starts, counted from the first record
+//   records  alias length, alias, fields length, fields
+//
+// The fields are the record's map of field name to encoded value, in bincode. Version 1 stores
+// kept every record in one bincode map, so the whole store had to be decoded to read any of it.
+// They, and the plain alias to path map from before the header, are still read and are rewritten
+// in the current format the next time the store is saved
+struct Bincode {
+    path: PathBuf,
+}
+
+const MAGIC: &str = "pin-store";
+const VERSION: u32 = 2;
+
+type Header = (String, u32);
+type V1 = (String, u32, BTreeMap<String, Fields>);
+
+impl Storage for Bincode {
+    fn load(&mut self) -> Result<Pins, PinError> {
+        read(&self.path)
+    }
+
+    fn save(&mut self, pins: &Pins) -> Result<(), PinError> {
+        write_atomic(&self.path, &encode(pins)?)
+    }
+
+    // Jumping only needs one pin, so only the index and that record are read
+    fn get(&mut self, alias: &str) -> Result<Option<PinAlias>, PinError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(PinError::StoreIo(e)),
+        };
+        let mut start = vec![0; HEADER_MAX];
+        let len = read_at(&file, &mut start, 0)?;
+        match header(&start[..len])? {
+            Some((VERSION, body)) => {
+                let at = (len - body.len()) as u64;
+                match find(&file, at, alias)? {
+                    Some(fields) => Ok(Some(decode_record(alias, &fields)?)),
+                    None => Ok(None),
+                }
+            }
+            _ => Ok(read(&self.path)?.remove(alias)),
+        }
+    }
+}
+
+// Read a bincode store file, which is empty if it doesn't exist yet
+pub fn read(path: &Path) -> Result<Pins, PinError> {
+    match std::fs::read(path) {
+        Ok(bytes) => decode(&bytes),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(PinError::StoreIo(e)),
+    }
+}
+
+pub fn encode(map: &Pins) -> Result<Vec<u8>, PinError> {
+    let config = bincode::config::standard();
+    let mut offsets = Vec::with_capacity(map.len() * 4);
+    let mut records = Vec::new();
+    for (alias, record) in map {
+        let fields = record.to_fields().map_err(encode_err)?;
+        let fields = bincode::encode_to_vec(&fields, config).map_err(encode_err)?;
+
+        push_u32(&mut offsets, records.len())?;
+        push_u32(&mut records, alias.len())?;
+        records.extend_from_slice(alias.as_bytes());
+        push_u32(&mut records, fields.len())?;
+        records.extend_from_slice(&fields);
+    }
+
+    let header: Header = (MAGIC.to_string(), VERSION);
+    let mut bytes = bincode::encode_to_vec(&header, config).map_err(encode_err)?;
+    push_u32(&mut bytes, map.len())?;
+    bytes.extend_from_slice(&offsets);
+    bytes.extend_from_slice(&records);
+    Ok(bytes)
+}
+
+fn push_u32(bytes: &mut Vec<u8>, n: usize) -> Result<(), PinError> {
+    let n = u32::try_from(n).map_err(encode_err)?;
+    bytes.extend_from_slice(&n.to_le_bytes());
+    Ok(())
+}
+
+fn encode_err(e: impl std::error::Error + Send + Sync + 'static) -> PinError {
+    PinError::StoreIo(std::io::Error::other(e))
+}
+
+// The version of the store and what follows its header, or None for a store from before the
+// header was added
+fn header(bytes: &[u8]) -> Result<Option<(u32, &[u8])>, PinError> {
+    match bincode::decode_from_slice::<Header, _>(bytes, bincode::config::standard()) {
+        Ok(((magic, version), len)) if magic == MAGIC => {
+            if version > VERSION {
+                return Err(PinError::StoreDecode(format!(
+                    "written by a newer version of pin (format {version})"
+                )));
+            }
+            Ok(Some((version, &bytes[len..])))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Decode the whole store, migrating it from an older format if needed
+fn decode(bytes: &[u8]) -> Result<Pins, PinError> {
+    let config = bincode::config::standard();
+
+    match header(bytes)? {
+        Some((VERSION, body)) => {
+            let index = Index::parse(body)?;
+            let mut map = BTreeMap::new();
+            for i in 0..index.count {
+                let (alias, fields) = index.record(i)?;
+                let alias = std::str::from_utf8(alias).map_err(|_| truncated())?;
+                map.insert(alias.to_string(), decode_record(alias, fields)?);
+            }
+            Ok(map)
+        }
+        Some(_) => {
+            let ((_, _, records), _) = bincode::decode_from_slice::<V1, _>(bytes, config)
+                .map_err(|e| PinError::StoreDecode(e.to_string()))?;
+            let mut map = BTreeMap::new();
+            for (alias, fields) in records {
+                let record = PinAlias::from_fields(&fields)
+                    .map_err(|e| PinError::StoreDecode(format!("alias \"{alias}\": {e}")))?;
+                map.insert(alias, record);
+            }
+            Ok(map)
+        }
+        None => {
+            let (legacy, _): (BTreeMap<String, String>, _) =
+                bincode::decode_from_slice(bytes, config)
+                    .map_err(|e| PinError::StoreDecode(e.to_string()))?;
+            // Not PinAlias::new, which would stat every path just to read the store. pin --doctor
+            // stamps them
+            Ok(legacy
+                .into_iter()
+                .map(|(alias, path)| {
+                    let record = PinAlias {
+                        path,
+                        ..Default::default()
+                    };
+                    (alias, record)
+                })
+                .collect())
+        }
+    }
+}
+
+fn decode_record(alias: &str, bytes: &[u8]) -> Result<PinAlias, PinError> {
+    let decode_err =
+        |e: bincode::error::DecodeError| PinError::StoreDecode(format!("alias \"{alias}\": {e}"));
+    let (fields, _): (Fields, _) =
+        bincode::decode_from_slice(bytes, bincode::config::standard()).map_err(decode_err)?;
+    PinAlias::from_fields(&fields).map_err(decode_err)
+}
+
+fn truncated() -> PinError {
+    PinError::StoreDecode("the index doesn't match the records".to_string())
+}
+
+// The records of a store, read in place
+struct Index<'a> {
+    count: usize,
+    offsets: &'a [u8],
+    records: &'a [u8],
+}
+
+impl<'a> Index<'a> {
+    fn parse(body: &'a [u8]) -> Result<Index<'a>, PinError> {
+        let count = u32_at(body, 0)? as usize;
+        let end = 4 + count * 4;
+        let offsets = body.get(4..end).ok_or_else(truncated)?;
+
+        Ok(Index {
+            count,
+            offsets,
+            records: &body[end..],
+        })
+    }
+
+    // The alias and encoded fields of the i'th record
+    fn record(&self, i: usize) -> Result<(&'a [u8], &'a [u8]), PinError> {
+        let start = u32_at(self.offsets, i * 4)? as usize;
+        let alias = bytes_at(self.records, start)?;
+        let fields = bytes_at(self.records, start + 4 + alias.len())?;
+        Ok((alias, fields))
+    }
+}
+
+// The header is a short string and a version, so is always within the first few bytes
+const HEADER_MAX: usize = 32;
+
+// Find the encoded fields of one record by binary search, reading only the offsets and the
+// records it lands on. The file is read rather than mapped, as a mapped file that is cut short by
+// someone editing it in place kills the reader with SIGBUS. Read this way it is just truncated
+fn find(file: &File, body: u64, alias: &str) -> Result<Option<Vec<u8>>, PinError> {
+    let mut count = [0; 4];
+    read_exact_at(file, &mut count, body)?;
+    let count = u32::from_le_bytes(count) as usize;
+    // A damaged count shouldn't make us allocate more than the file could hold
+    let len = file.metadata().map_err(PinError::StoreIo)?.len();
+    if (count * 4) as u64 > len {
+        return Err(truncated());
+    }
+    let mut offsets = vec![0; count * 4];
+    read_exact_at(file, &mut offsets, body + 4)?;
+    let records = body + 4 + offsets.len() as u64;
+
+    // A length followed by that many bytes, which a damaged length can't make more than the file
+    let bytes_at = |at: u64| -> Result<Vec<u8>, PinError> {
+        let mut bytes = [0; 4];
+        read_exact_at(file, &mut bytes, at)?;
+        let bytes = u64::from(u32::from_le_bytes(bytes));
+        if at + 4 + bytes > len {
+            return Err(truncated());
+        }
+        let mut bytes = vec![0; bytes as usize];
+        read_exact_at(file, &mut bytes, at + 4)?;
+        Ok(bytes)
+    };
+
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let start = records + u64::from(u32_at(&offsets, mid * 4)?);
+        let key = bytes_at(start)?;
+        match key.as_slice().cmp(alias.as_bytes()) {
+            Ordering::Equal => return bytes_at(start + 4 + key.len() as u64).map(Some),
+            Ordering::Less => low = mid + 1,
+            Ordering::Greater => high = mid,
+        }
+    }
+    Ok(None)
+}
+
+// Read as much of buf as the file has from an offset, returning how much that was
+fn read_at(file: &File, buf: &mut [u8], at: u64) -> Result<usize, PinError> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read_at(&mut buf[read..], at + read as u64) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(PinError::StoreIo(e)),
+        }
+    }
+    Ok(read)
+}
+
+fn read_exact_at(file: &File, buf: &mut [u8], at: u64) -> Result<(), PinError> {
+    match read_at(file, buf, at)? == buf.len() {
+        true => Ok(()),
+        false => Err(truncated()),
+    }
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32, PinError> {
+    let n = bytes.get(at..at + 4).ok_or_else(truncated)?;
+    Ok(u32::from_le_bytes([n[0], n[1], n[2], n[3]]))
+}
+
+// A length followed by that many bytes
+fn bytes_at(bytes: &[u8], at: usize) -> Result<&[u8], PinError> {
+    let len = u32_at(bytes, at)? as usize;
+    bytes.get(at + 4..at + 4 + len).ok_or_else(truncated)
+}
+
+// A file meant to be read and edited by people. Fields left out take their defaults, and fields
+// pin doesn't know are ignored
+struct Text {
+    path: PathBuf,
+    format: Format,
+}
+
+enum Format {
+    Toml,
+    Json,
+}
+
+const TEXT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    version: u32,
+    #[serde(default)]
+    pins: BTreeMap<String, Record>,
+}
+
+// A pin as it is written in a text file, with only the fields that aren't defaults
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Record {
+    path: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    note: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires: Option<u64>,
+    #[serde(skip_serializing_if = "is_false")]
+    locked: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hooks: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "is_false")]
+    trusted: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    keep_symlinks: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    allow_missing: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    literal: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    raw: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    file: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "identity")]
+    identity: Option<(u64, u64)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<u64>,
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+// Device and inode numbers can be above i64::MAX, which is as big as a TOML integer gets, so the
+// identity is written as "device:inode". Files from before kept it as a pair of numbers, which is
+// still read
+mod identity {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(
+        identity: &Option<(u64, u64)>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        match identity {
+            Some((dev, ino)) => s.serialize_str(&format!("{dev}:{ino}")),
+            None => s.serialize_none(),
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Identity {
+        Text(String),
+        Pair(u64, u64),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<(u64, u64)>, D::Error> {
+        let text = match Identity::deserialize(d)? {
+            Identity::Pair(dev, ino) => return Ok(Some((dev, ino))),
+            Identity::Text(text) => text,
+        };
+        let parsed = text
+            .split_once(':')
+            .and_then(|(dev, ino)| Some((dev.parse().ok()?, ino.parse().ok()?)));
+        match parsed {
+            Some(identity) => Ok(Some(identity)),
+            None => Err(D::Error::custom(format!(
+                "identity should be \"device:inode\", not \"{text}\""
+            ))),
+        }
+    }
+}
+
+impl From<&PinAlias> for Record {
+    fn from(record: &PinAlias) -> Record {
+        Record {
+            path: record.path.clone(),
+            tags: record.tags.clone(),
+            note: record.note.clone(),
+            expires: record.expires,
+            locked: record.locked,
+            hooks: record.hooks.clone(),
+            env: record.env.clone(),
+            trusted: record.trusted,
+            keep_symlinks: record.policy.keep_symlinks,
+            allow_missing: record.policy.allow_missing,
+            literal: record.policy.literal,
+            raw: record.policy.raw,
+            file: record.file,
+            origin: record.origin.clone(),
+            identity: record.identity,
+            created: record.created,
+        }
+    }
+}
+
+impl From<Record> for PinAlias {
+    fn from(record: Record) -> PinAlias {
+        PinAlias {
+            path: record.path,
+            hooks: record.hooks,
+            env: record.env,
+            trusted: record.trusted,
+            policy: PathPolicy {
+                keep_symlinks: record.keep_symlinks,
+                allow_missing: record.allow_missing,
+                literal: record.literal,
+                raw: record.raw,
+            },
+            identity: record.identity,
+            origin: record.origin,
+            file: record.file,
+            tags: record.tags,
+            note: record.note,
+            expires: record.expires,
+            session: false,
+            locked: record.locked,
+            created: record.created,
+        }
+    }
+}
+
+impl Storage for Text {
+    fn load(&mut self) -> Result<Pins, PinError> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(PinError::StoreIo(e)),
+        };
+        let file = self.path.display();
+        let document: Document = match self.format {
+            Format::Toml => toml::from_str(&text).map_err(|e| e.to_string()),
+            Format::Json => serde_json::from_str(&text).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| PinError::StoreDecode(format!("{file}: {}", e.trim_end())))?;
+
+        if document.version > TEXT_VERSION {
+            return Err(PinError::StoreDecode(format!(
+                "{file} was written by a newer version of pin (format {})",
+                document.version
+            )));
+        }
+
+        let mut pins = BTreeMap::new();
+        for (alias, record) in document.pins {
+            if record.path.is_empty() {
+                return Err(PinError::StoreDecode(format!(
+                    "{file}: \"{alias}\" has no path"
+                )));
+            }
+            pins.insert(alias, record.into());
+        }
+        Ok(pins)
+    }
+
+    fn save(&mut self, pins: &Pins) -> Result<(), PinError> {
+        let document = Document {
+            version: TEXT_VERSION,
+            pins: pins
+                .iter()
+                .map(|(alias, record)| (alias.clone(), record.into()))
+                .collect(),
+        };
+        let text = match self.format {
+            Format::Toml => toml::to_string_pretty(&document).map_err(encode_err)?,
+            Format::Json => serde_json::to_string_pretty(&document).map_err(encode_err)? + "\n",
+        };
+
+        write_atomic(&self.path, text.as_bytes())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    // A database with a row per pin. The record column holds the whole pin, encoded as in the
+    // bincode store. The other columns copy the parts worth querying by hand, e.g.
+    // `select alias, path from pins where tags like '%work%'`
+
+    use std::path::Path;
+
+    use rusqlite::{Connection, OptionalExtension, Transaction, params};
+
+    use super::{Pins, Storage, decode_record, encode_err};
+    use crate::alias::PinAlias;
+    use crate::error::PinError;
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS pins (
+            alias   TEXT PRIMARY KEY,
+            path    TEXT NOT NULL,
+            tags    TEXT NOT NULL DEFAULT '',
+            note    TEXT NOT NULL DEFAULT '',
+            locked  INTEGER NOT NULL DEFAULT 0,
+            expires INTEGER,
+            record  BLOB NOT NULL
+        );
+    ";
+
+    pub struct Sqlite {
+        conn: Connection,
+    }
+
+    fn db_err(e: rusqlite::Error) -> PinError {
+        encode_err(e)
+    }
+
+    impl Sqlite {
+        pub fn open(path: &Path) -> Result<Sqlite, PinError> {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(PinError::StoreIo)?;
+            }
+            let conn = Connection::open(path).map_err(db_err)?;
+            conn.execute_batch(SCHEMA).map_err(db_err)?;
+            Ok(Sqlite { conn })
+        }
+    }
+
+    fn to_blob(record: &PinAlias) -> Result<Vec<u8>, PinError> {
+        let fields = record.to_fields().map_err(encode_err)?;
+        bincode::encode_to_vec(&fields, bincode::config::standard()).map_err(encode_err)
+    }
+
+    fn load(conn: &Connection) -> Result<Pins, PinError> {
+        let mut query = conn
+            .prepare("SELECT alias, record FROM pins")
+            .map_err(db_err)?;
+        let rows = query
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(db_err)?;
+
+        let mut pins = Pins::new();
+        for row in rows {
+            let (alias, blob) = row.map_err(db_err)?;
+            let record = decode_record(&alias, &blob)?;
+            pins.insert(alias, record);
+        }
+        Ok(pins)
+    }
+
+    fn put(tx: &Transaction, alias: &str, record: &PinAlias) -> Result<(), PinError> {
+        tx.execute(
+            "INSERT OR REPLACE INTO pins (alias, path, tags, note, locked, expires, record)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                alias,
+                record.path,
+                record.tags.join(","),
+                record.note,
+                record.locked,
+                record.expires.map(|time| time as i64),
+                to_blob(record)?,
+            ],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    // Make the table match the pins, only touching the rows that changed
+    fn replace(tx: &Transaction, old: &Pins, pins: &Pins) -> Result<(), PinError> {
+        for alias in old.keys().filter(|alias| !pins.contains_key(*alias)) {
+            tx.execute("DELETE FROM pins WHERE alias = ?1", [alias])
+                .map_err(db_err)?;
+        }
+        for (alias, record) in pins {
+            let changed = old
+                .get(alias)
+                .is_none_or(|old| old.to_fields().ok() != record.to_fields().ok());
+            if changed {
+                put(tx, alias, record)?;
+            }
+        }
+        Ok(())
+    }
+
+    impl Storage for Sqlite {
+        fn load(&mut self) -> Result<Pins, PinError> {
+            load(&self.conn)
+        }
+
+        fn save(&mut self, pins: &Pins) -> Result<(), PinError> {
+            self.transaction(&mut |all| {
+                *all = pins.clone();
+                Ok(())
+            })
+        }
+
+        fn get(&mut self, alias: &str) -> Result<Option<PinAlias>, PinError> {
+            let blob: Option<Vec<u8>> = self
+                .conn
+                .query_row("SELECT record FROM pins WHERE alias = ?1", [alias], |row| {
+                    row.get(0)
+                })
+                .optional()
+                .map_err(db_err)?;
+            blob.map(|blob| decode_record(alias, &blob)).transpose()
+        }
+
+        fn put(&mut self, alias: &str, record: &PinAlias) -> Result<(), PinError> {
+            let tx = self.conn.transaction().map_err(db_err)?;
+            put(&tx, alias, record)?;
+            tx.commit().map_err(db_err)
+        }
+
+        fn delete(&mut self, alias: &str) -> Result<Option<PinAlias>, PinError> {
+            let old = self.get(alias)?;
+            self.conn
+                .execute("DELETE FROM pins WHERE alias = ?1", [alias])
+                .map_err(db_err)?;
+            Ok(old)
+        }
+
+        fn transaction(
+            &mut self,
+            change: &mut dyn FnMut(&mut Pins) -> Result<(), PinError>,
+        ) -> Result<(), PinError> {
+            // Dropping the transaction without committing rolls it back
+            let tx = self.conn.transaction().map_err(db_err)?;
+            let old = load(&tx)?;
+            let mut pins = old.clone();
+            change(&mut pins)?;
+            replace(&tx, &old, &pins)?;
+            tx.commit().map_err(db_err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::TempDir;
+
+    // A pin with every field set, the identity past what TOML can hold as a number
+    fn pins() -> Pins {
+        let record = PinAlias {
+            path: "/home/me/code/api".to_string(),
+            hooks: vec!["git fetch".to_string()],
+            env: BTreeMap::from([("STAGE".to_string(), "dev".to_string())]),
+            trusted: true,
+            policy: PathPolicy {
+                keep_symlinks: true,
+                allow_missing: true,
+                literal: false,
+                raw: false,
+            },
+            identity: Some((u64::MAX, u64::MAX - 1)),
+            origin: Some("git@example.com:me/api.git".to_string()),
+            file: false,
+            tags: vec!["work".to_string()],
+            note: "the \"api\" server".to_string(),
+            expires: Some(4_000_000_000),
+            session: false,
+            locked: true,
+            created: Some(1_700_000_000),
+        };
+        let plain = PinAlias {
+            path: "/tmp".to_string(),
+            ..Default::default()
+        };
+        BTreeMap::from([("api".to_string(), record), ("tmp".to_string(), plain)])
+    }
+
+    fn round_trip(storage: &mut dyn Storage) {
+        let pins = pins();
+        storage.save(&pins).unwrap();
+        let loaded = storage.load().unwrap();
+
+        assert_eq!(loaded.keys().collect::<Vec<_>>(), ["api", "tmp"]);
+        for (alias, record) in &pins {
+            let fields = record.to_fields().unwrap();
+            assert_eq!(loaded[alias].to_fields().unwrap(), fields, "{alias}");
+        }
+        let api = storage.get("api").unwrap().unwrap();
+        assert_eq!(api.identity, Some((u64::MAX, u64::MAX - 1)));
+        assert!(storage.get("web").unwrap().is_none());
+    }
+
+    #[test]
+    fn bincode_round_trips() {
+        let dir = TempDir::new().unwrap();
+        round_trip(&mut Bincode {
+            path: dir.path().join("store.bin"),
+        });
+    }
+
+    #[test]
+    fn bincode_lengths_past_the_end_are_corrupt() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = Bincode {
+            path: dir.path().join("store.bin"),
+        };
+        storage.save(&pins()).unwrap();
+
+        // Make the length of the alias "api" far more than the file holds
+        let mut bytes = std::fs::read(&storage.path).unwrap();
+        let at = bytes
+            .windows(7)
+            .position(|w| w == b"\x03\0\0\0api")
+            .unwrap();
+        bytes[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&storage.path, bytes).unwrap();
+
+        assert!(matches!(storage.get("api"), Err(PinError::StoreDecode(_))));
+    }
+
+    #[test]
+    fn toml_round_trips() {
+        let dir = TempDir::new().unwrap();
+        round_trip(&mut Text {
+            path: dir.path().join("store.toml"),
+            format: Format::Toml,
+        });
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let dir = TempDir::new().unwrap();
+        round_trip(&mut Text {
+            path: dir.path().join("store.json"),
+            format: Format::Json,
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_round_trips() {
+        let dir = TempDir::new().unwrap();
+        round_trip(&mut sqlite::Sqlite::open(&dir.path().join("store.db")).unwrap());
+    }
+
+    #[test]
+    fn identities_written_as_numbers_are_still_read() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("store.toml");
+        let text = "version = 1\n[pins.api]\npath = \"/tmp\"\nidentity = [2049, 131]\n";
+        std::fs::write(&path, text).unwrap();
+
+        let mut storage = Text {
+            path,
+            format: Format::Toml,
+        };
+        assert_eq!(storage.load().unwrap()["api"].identity, Some((2049, 131)));
+    }
+}
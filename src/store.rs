@@ -1,70 +1,293 @@
+//
 use std::collections::BTreeMap;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 
-use bincode;
+use crate::alias::PinAlias;
+use crate::error::PinError;
+use crate::expiry::{session_id, session_path, sessions_dir, sweep_sessions};
+use crate::history::now;
+use crate::pattern::{Pattern, search_rank};
+use crate::storage::{self, Backend, Storage};
+
+// How long to wait for another pin process to release the store
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
 
 // The Datastore
-// Tags: convert the value to a struct to add tags
 // maybe a created at??
+/// Every pin, read from the configured [`Backend`] along with the current shell's session pins.
 pub struct Store {
-    map: BTreeMap<String, String>,
+    map: BTreeMap<String, PinAlias>,
+    // Pins that only last as long as the current shell, kept in their own file
+    session: BTreeMap<String, PinAlias>,
+    session_id: u32,
+    // Aliases dropped because they had expired when the store was read
+    expired: Vec<String>,
+    storage: Box<dyn Storage>,
+    lock: Option<Lock>,
 }
 
 impl Store {
-    // Read in the store or make a fresh one
-    pub fn init() -> Store {
-        let map: BTreeMap<String, String> = if let Ok(file) = std::fs::File::open(get_pin_path()) {
-            let reader = std::io::BufReader::new(file);
-            bincode::decode_from_reader(reader, bincode::config::standard()).unwrap_or_default()
-        } else {
-            BTreeMap::new()
+    /// Read in the store or make a fresh one. Expired pins are dropped, and written back if the
+    /// lock is free. Otherwise whoever holds it will do it.
+    pub fn init() -> Result<Store, PinError> {
+        let store = Store::open(None)?;
+        if store.expired.is_empty() {
+            return Ok(store);
+        }
+
+        let Ok(lock) = Lock::acquire("store.lock") else {
+            return Ok(store);
         };
+        // Read again now nobody else can change it
+        let mut locked = Store::open(Some(lock))?;
+        locked.write()?;
+        locked.lock = None;
+        locked.expired = store.expired;
+
+        Ok(locked)
+    }
+
+    /// Take the store lock, then read the store. Used by commands that modify the store so that
+    /// two pins can't overwrite each other's changes. The lock is released when the store drops.
+    pub fn lock() -> Result<Store, PinError> {
+        let lock = Lock::acquire("store.lock")?;
+        let mut store = Store::open(Some(lock))?;
+        if !store.expired.is_empty() {
+            store.write()?;
+        }
+
+        Ok(store)
+    }
+
+    /// Look up one pin without reading the rest of the store, which is what jumping needs. An
+    /// expired pin isn't found, but is left for the next [`Store::init`] to drop.
+    pub fn find(alias: &str) -> Result<Option<PinAlias>, PinError> {
+        let mut session = storage::read(&session_path(session_id())?)?;
+        let record = match session.remove(alias) {
+            Some(record) => Some(PinAlias {
+                session: true,
+                ..record
+            }),
+            None => Backend::configured()?.open()?.get(alias)?,
+        };
+
+        let now = now();
+        Ok(record.filter(|record| record.expires.is_none_or(|time| time > now)))
+    }
+
+    fn open(lock: Option<Lock>) -> Result<Store, PinError> {
+        sweep_sessions()?;
+        let session_id = session_id();
+        let mut storage = Backend::configured()?.open()?;
+        let mut map = storage.load()?;
+        let mut session = storage::read(&session_path(session_id)?)?;
+        for record in session.values_mut() {
+            record.session = true;
+        }
+
+        let now = now();
+        let mut expired = Vec::new();
+        for map in [&mut map, &mut session] {
+            map.retain(|alias, record| {
+                let live = record.expires.is_none_or(|time| time > now);
+                if !live {
+                    expired.push(alias.clone());
+                }
+                live
+            });
+        }
+        expired.sort();
+
+        Ok(Store {
+            map,
+            session,
+            session_id,
+            expired,
+            storage,
+            lock,
+        })
+    }
+
+    /// Write the store, taking the lock first if it isn't held.
+    pub fn save(mut self) -> Result<(), PinError> {
+        if self.lock.is_none() {
+            self.lock = Some(Lock::acquire("store.lock")?);
+        }
+
+        self.write()
+    }
+
+    // Write the store and this shell's session pins. Needs the lock
+    fn write(&mut self) -> Result<(), PinError> {
+        self.storage.save(&self.map)?;
+
+        let session_path = session_path(self.session_id)?;
+        if self.session.is_empty() {
+            return match std::fs::remove_file(&session_path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(PinError::StoreIo(e)),
+                _ => Ok(()),
+            };
+        }
+        std::fs::create_dir_all(sessions_dir()?).map_err(PinError::StoreIo)?;
+        write_atomic(&session_path, &storage::encode(&self.session)?)
+    }
+
+    /// Aliases that expired and were removed when the store was read.
+    pub fn expired(&self) -> &[String] {
+        &self.expired
+    }
 
-        Store { map: map }
+    /// Add a record to the map, returning the record it replaced.
+    pub fn add(&mut self, key: String, val: PinAlias) -> Option<PinAlias> {
+        let old = self.delete(&key);
+        if val.session {
+            self.session.insert(key, val);
+        } else {
+            self.map.insert(key, val);
+        }
+        old
     }
 
-    // Write hashmap to file
-    pub fn save(self) {
-        let mut file = std::fs::File::create(get_pin_path()).expect(
-            "Error: Could not write store to file. Any additions this session will be lost.",
-        );
-        let _ = bincode::encode_into_std_write(
-            self.map.clone(),
-            &mut file,
-            bincode::config::standard(),
-        )
-        .expect("Error: Could not write store to file. Any additions this session will be lost.");
+    /// Get the record for the matching alias.
+    pub fn get(&self, key: &str) -> Option<&PinAlias> {
+        self.session.get(key).or_else(|| self.map.get(key))
     }
 
-    // Validate the input and add it to the map
-    pub fn add(&mut self, key: String, val: String) -> Result<(), String> {
-        match self.map.insert(key, val) {
-            None => Ok(()),
-            Some(path) => Err(path),
+    /// Get the record for the matching alias to change it.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut PinAlias> {
+        match self.session.get_mut(key) {
+            Some(record) => Some(record),
+            None => self.map.get_mut(key),
         }
     }
 
-    // Get the path for the matching alias
-    pub fn get(&self, key: &String) -> Option<String> {
-        self.map.get(key).cloned()
+    /// Remove an alias, returning its record.
+    pub fn delete(&mut self, alias: &str) -> Option<PinAlias> {
+        let session = self.session.remove(alias);
+        let stored = self.map.remove(alias);
+        session.or(stored)
+    }
+
+    /// Iterate over every alias in order, session pins included.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &PinAlias)> {
+        let mut all: BTreeMap<&String, &PinAlias> = self.map.iter().collect();
+        all.extend(self.session.iter());
+        all.into_iter()
     }
 
-    pub fn delete(&mut self, alias: String) -> Result<(), ()> {
-        match self.map.remove(&alias) {
-            Some(_) => Ok(()),
-            None => Err(()),
+    /// The path an alias jumps to, with raw paths expanded.
+    pub fn resolve(&self, alias: &str) -> Result<String, PinError> {
+        match self.get(alias) {
+            Some(record) => record.resolve(),
+            None => Err(PinError::AliasNotFound(alias.to_string())),
         }
     }
 
-    //return all key value pairs
-    pub fn list_all(&self) -> BTreeMap<String, String> {
-        self.map.clone()
+    /// Pins whose alias matches a pattern, in order.
+    pub fn matching<'a>(
+        &'a self,
+        pattern: &'a Pattern,
+    ) -> impl Iterator<Item = (&'a String, &'a PinAlias)> {
+        self.iter().filter(|(alias, _)| pattern.matches(alias))
+    }
+
+    /// Pins whose alias, path, tags or note contain the text, ignoring case. Matches in aliases
+    /// come first and matches in notes last.
+    pub fn search(&self, text: &str) -> Vec<(&String, &PinAlias)> {
+        let text = text.to_lowercase();
+        let mut matches: Vec<(u8, &String, &PinAlias)> = self
+            .iter()
+            .filter_map(|(alias, record)| Some((search_rank(alias, record, &text)?, alias, record)))
+            .collect();
+        // Best rank first, then alphabetical
+        matches.sort_by_key(|(rank, alias, _)| (*rank, *alias));
+
+        matches
+            .into_iter()
+            .map(|(_, alias, record)| (alias, record))
+            .collect()
     }
+
+    /// Every alias with its record, session pins included, taken out of the store without
+    /// copying them.
+    pub fn into_pins(self) -> BTreeMap<String, PinAlias> {
+        let mut all = self.map;
+        all.extend(self.session);
+        all
+    }
+}
+
+// An exclusive lock on a file in the pin directory, held as a lock file next to it
+pub struct Lock {
+    path: PathBuf,
 }
 
-// Gets the path of the _pins.store.bin
-fn get_pin_path() -> String {
-    format!(
-        "{}/.pin/store.bin",
-        std::env::var("HOME").expect("Error: unable to get home variable.")
-    )
+impl Lock {
+    pub fn acquire(name: &str) -> Result<Lock, PinError> {
+        let dir = pin_dir()?;
+        std::fs::create_dir_all(&dir).map_err(PinError::StoreIo)?;
+        let path = dir.join(name);
+
+        let start = Instant::now();
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(Lock { path }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if start.elapsed() > LOCK_TIMEOUT {
+                        return Err(PinError::LockTimeout(path.display().to_string()));
+                    }
+                    sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(PinError::StoreIo(e)),
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// Write a file in the pin directory. It is written to a temporary file and renamed over the old
+// one so a failed write never leaves a half written file behind
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), PinError> {
+    std::fs::create_dir_all(pin_dir()?).map_err(PinError::StoreIo)?;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = std::fs::File::create(&tmp).map_err(PinError::StoreIo)?;
+    file.write_all(bytes).map_err(PinError::StoreIo)?;
+    file.sync_all().map_err(PinError::StoreIo)?;
+    std::fs::rename(&tmp, path).map_err(PinError::StoreIo)
+}
+
+// Tests keep their data in a directory of their own instead of ~/.pin. It is set per thread so
+// tests can run side by side
+#[cfg(test)]
+thread_local! {
+    pub static TEST_DIR: std::cell::RefCell<Option<PathBuf>> = const { std::cell::RefCell::new(None) };
+}
+
+// Gets the directory pin keeps its data in, which is ~/.pin unless $PIN_STORE says otherwise
+pub fn pin_dir() -> Result<PathBuf, PinError> {
+    #[cfg(test)]
+    if let Some(dir) = TEST_DIR.with_borrow(|dir| dir.clone()) {
+        return Ok(dir);
+    }
+
+    if let Some(dir) = std::env::var_os("PIN_STORE").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    let home = std::env::var("HOME").map_err(|_| PinError::NoHome)?;
+
+    Ok(PathBuf::from(home).join(".pin"))
 }
@@ -0,0 +1,191 @@
+// Moving pins between stores
+//
+// `pin --export <file>` writes every pin as text and `pin --import <file>` adds them to another
+// store, e.g. on a new machine. After a header, each line is an alias and a path followed by the
+// rest of the pin as name=value fields, separated by tabs and percent-encoded like directives:
+//
+//   pin-export 1
+//   api<TAB>/home/me/code/api<TAB>tag=work<TAB>note=The backend<TAB>locked
+//
+// Hooks come across but their trust doesn't, so imported hooks never run until trusted again.
+// Session pins belong to their shell and aren't exported. Unknown fields are ignored so newer
+// exports can still be read.
+
+use std::io::Write;
+
+use crate::alias::PinAlias;
+use crate::cmd::check_tags;
+use crate::directive::{decode, encode};
+use crate::error::PinError;
+use crate::store::Store;
+
+const HEADER: &str = "pin-export 1";
+
+/// How [`import`] treats an alias that is already in the store.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum Conflict {
+    /// Keep the pin in the store and skip the imported one.
+    #[default]
+    Keep,
+    /// Replace the pin in the store, unless it is locked.
+    Replace,
+}
+
+/// The aliases an [`import`] added, replaced or left alone.
+#[derive(Default, Debug)]
+pub struct Imported {
+    /// New to the store.
+    pub added: Vec<String>,
+    /// Already in the store, and replaced.
+    pub replaced: Vec<String>,
+    /// Already in the store, and kept because of [`Conflict::Keep`].
+    pub skipped: Vec<String>,
+    /// Locked in the store, and kept even with [`Conflict::Replace`].
+    pub locked: Vec<String>,
+}
+
+/// Write every pin in the store except session pins, returning how many were written.
+pub fn export(store: &Store, mut out: impl Write) -> Result<usize, PinError> {
+    let mut text = format!("{HEADER}\n");
+    let mut count = 0;
+    for (alias, record) in store.iter().filter(|(_, r)| !r.session) {
+        text += &export_line(alias, record);
+        text.push('\n');
+        count += 1;
+    }
+
+    out.write_all(text.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| PinError::Transfer(format!("unable to write the export: {e}")))?;
+    Ok(count)
+}
+
+fn export_line(alias: &str, record: &PinAlias) -> String {
+    let mut fields = vec![encode(alias), encode(&record.path)];
+    let mut field = |name: &str, value: &str| fields.push(format!("{name}={}", encode(value)));
+
+    for tag in &record.tags {
+        field("tag", tag);
+    }
+    if !record.note.is_empty() {
+        field("note", &record.note);
+    }
+    for hook in &record.hooks {
+        field("hook", hook);
+    }
+    for (name, value) in &record.env {
+        field("env", &format!("{name}={value}"));
+    }
+    if let Some(time) = record.expires {
+        field("expires", &time.to_string());
+    }
+
+    let policy = record.policy;
+    let flags = [
+        ("locked", record.locked),
+        ("keep-symlinks", policy.keep_symlinks),
+        ("allow-missing", policy.allow_missing),
+        ("literal", policy.literal),
+        ("raw", policy.raw),
+    ];
+    for (flag, set) in flags {
+        if set {
+            fields.push(flag.to_string());
+        }
+    }
+    fields.join("\t")
+}
+
+/// Add the pins in an export to the store. Nothing is added unless the whole export can be read.
+/// Locked pins in the store are never replaced unless `force` is set. The store isn't saved, so
+/// take it with [`Store::lock`] and save it afterwards.
+pub fn import(
+    store: &mut Store,
+    text: &str,
+    conflict: Conflict,
+    force: bool,
+) -> Result<Imported, PinError> {
+    let mut lines = text.lines().enumerate();
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err(PinError::Transfer(format!(
+            "this isn't a pin export. It should start with \"{HEADER}\"."
+        )));
+    }
+
+    let mut pins = Vec::new();
+    for (i, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+        let pin = import_line(line)
+            .map_err(|e| PinError::Transfer(format!("line {} of the export {e}.", i + 1)))?;
+        pins.push(pin);
+    }
+
+    let mut imported = Imported::default();
+    for (alias, mut record) in pins {
+        match store.get(&alias) {
+            None => imported.added.push(alias.clone()),
+            Some(_) if conflict == Conflict::Keep => {
+                imported.skipped.push(alias);
+                continue;
+            }
+            Some(old) if old.locked && !force => {
+                imported.locked.push(alias);
+                continue;
+            }
+            Some(_) => imported.replaced.push(alias.clone()),
+        }
+        record.stamp();
+        store.add(alias, record);
+    }
+    Ok(imported)
+}
+
+// Read one pin, or what is wrong with it
+fn import_line(line: &str) -> Result<(String, PinAlias), String> {
+    let mut fields = line.split('\t');
+    let field = |text: Option<&str>, what: &str| match text.map(decode) {
+        Some(Some(text)) if !text.is_empty() => Ok(text),
+        _ => Err(format!("has no {what}")),
+    };
+    let alias = field(fields.next(), "alias")?;
+    let path = field(fields.next(), "path")?;
+
+    let mut record = PinAlias {
+        path,
+        ..Default::default()
+    };
+    for text in fields {
+        let (name, value) = text.split_once('=').unwrap_or((text, ""));
+        let Some(value) = decode(value) else {
+            return Err(format!("has a badly encoded {name}"));
+        };
+        match name {
+            "tag" => {
+                check_tags(std::slice::from_ref(&value))
+                    .map_err(|_| format!("has a tag \"{value}\" that can't be used"))?;
+                record.tags.push(value);
+            }
+            "note" => record.note = value,
+            "hook" => record.hooks.push(value),
+            "env" => {
+                let Some((name, value)) = value.split_once('=') else {
+                    return Err("has an env without a value".to_string());
+                };
+                record.env.insert(name.to_string(), value.to_string());
+            }
+            "expires" => match value.parse() {
+                Ok(time) => record.expires = Some(time),
+                Err(_) => return Err(format!("has a bad expiry \"{value}\"")),
+            },
+            "locked" => record.locked = true,
+            "keep-symlinks" => record.policy.keep_symlinks = true,
+            "allow-missing" => record.policy.allow_missing = true,
+            "literal" => record.policy.literal = true,
+            "raw" => record.policy.raw = true,
+            _ => {}
+        }
+    }
+    record.tags.sort();
+    record.tags.dedup();
+
+    Ok((alias, record))
+}
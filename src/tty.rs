@@ -1,65 +1,956 @@
-use std::{fs::File, io::Write};
+// Talking to the user on the terminal
+//
+// Prompts read keys from /dev/tty in raw mode, so they work while stdout is captured by the shell
+// wrapper. Tty puts the terminal back when it is dropped, and also when pin panics or is killed
+// with SIGINT or SIGTERM, so a crash never leaves the shell in raw mode. Ctrl-C at any prompt
+// comes back as PinError::Interrupted, and a resize redraws whatever prompt is showing.
+
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::IntoRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Once, OnceLock};
 
 use crossterm::{
-    event::{self, Event, KeyCode},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    queue,
+    terminal::{
+        self, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    },
 };
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::error::PinError;
+use crate::path::{PathPolicy, parse_path};
+use crate::store::{pin_dir, write_atomic};
+
+// The terminal settings from before raw mode, with a descriptor to put them back through. Kept
+// outside any Tty so the panic hook and signal handler can reach them
+static ORIGINAL: OnceLock<(libc::c_int, libc::termios)> = OnceLock::new();
+static GUARD: Once = Once::new();
+// Whether the alternate screen is showing, so restoring only leaves it when it was entered
+static ALTERNATE: AtomicBool = AtomicBool::new(false);
 
 pub struct Tty {
     tty: File,
 }
 
 impl Tty {
-    pub fn new() -> Result<Tty, std::io::Error> {
-        enable_raw_mode()?;
+    pub fn new() -> Result<Tty, PinError> {
+        // Get tty before switching modes so a failure leaves the terminal untouched
+        let tty = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .map_err(PinError::TtyUnavailable)?;
 
-        // Get tty
-        let tty = std::fs::OpenOptions::new().write(true).open("/dev/tty")?;
+        install_guard();
+        enable_raw_mode().map_err(PinError::TtyUnavailable)?;
 
         Ok(Tty { tty })
     }
 
-    pub fn write(&mut self, msg: String) -> std::io::Result<()> {
-        write!(self.tty, "{}", msg.replace("\n", "\n\r"))
+    pub fn write(&mut self, msg: String) -> Result<(), PinError> {
+        write!(self.tty, "{}", msg.replace("\n", "\n\r")).map_err(PinError::TtyUnavailable)?;
+        self.tty.flush().map_err(PinError::TtyUnavailable)
     }
 
-    pub fn ask_confirmation(&mut self) -> bool {
-        let mut tty = &self.tty;
+    // Switch to the alternate screen with the cursor hidden, for full-screen views
+    pub fn enter_alternate_screen(&mut self) -> Result<(), PinError> {
+        ALTERNATE.store(true, Ordering::SeqCst);
+        queue!(self, EnterAlternateScreen, cursor::Hide).map_err(PinError::TtyUnavailable)?;
+        self.flush().map_err(PinError::TtyUnavailable)
+    }
 
-        let mut confirm = false;
+    pub fn leave_alternate_screen(&mut self) -> Result<(), PinError> {
+        queue!(self, cursor::Show, LeaveAlternateScreen).map_err(PinError::TtyUnavailable)?;
+        self.flush().map_err(PinError::TtyUnavailable)?;
+        ALTERNATE.store(false, Ordering::SeqCst);
+        Ok(())
+    }
 
+    // Read the next key press. Returns None when the terminal was resized, so whatever is on
+    // screen should be drawn again. Ctrl-C is an error so it gets out of any prompt
+    pub fn read_key(&mut self) -> Result<Option<KeyEvent>, PinError> {
         loop {
+            match event::read().map_err(PinError::TtyUnavailable)? {
+                Event::Key(key)
+                    if key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('c') =>
+                {
+                    // End the prompt's line so the message after it starts on its own
+                    self.write("\n".to_string())?;
+                    return Err(PinError::Interrupted);
+                }
+                Event::Key(key) => return Ok(Some(key)),
+                Event::Resize(..) => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Where commands talk to the user. The real terminal is only opened when a command needs it, so
+/// commands that never ask anything still work without one. Tests use a Script instead.
+pub trait Terminal {
+    /// Start talking to the user. Anything the terminal needed, such as raw mode, lasts until the
+    /// prompt is dropped.
+    fn open(&mut self) -> Result<Box<dyn Prompt + '_>, PinError>;
+}
+
+/// The terminal at /dev/tty.
+pub struct DevTty;
+
+impl Terminal for DevTty {
+    fn open(&mut self) -> Result<Box<dyn Prompt + '_>, PinError> {
+        Ok(Box::new(Tty::new()?))
+    }
+}
+
+/// An open terminal. Only writing and reading keys differ between terminals, and every kind of
+/// prompt is built on those two.
+pub trait Prompt {
+    /// Show some text. Newlines start the next line at the left edge even in raw mode.
+    fn write(&mut self, msg: String) -> Result<(), PinError>;
+
+    /// The next key press, or None when the screen needs drawing again.
+    fn read_key(&mut self) -> Result<Option<KeyEvent>, PinError>;
+
+    /// How many columns wide the terminal is, or None if it isn't a real terminal, e.g. a test's.
+    fn columns(&self) -> Option<usize> {
+        None
+    }
+
+    /// How many lines tall the terminal is, or None if it isn't a real terminal.
+    fn lines(&self) -> Option<usize> {
+        None
+    }
+
+    /// Switch to a screen of its own with the cursor hidden, for full-screen views. Terminals
+    /// without one are drawn on as they are.
+    fn enter_alternate_screen(&mut self) -> Result<(), PinError> {
+        Ok(())
+    }
+
+    /// Go back to the screen from before [`enter_alternate_screen`](Prompt::enter_alternate_screen).
+    fn leave_alternate_screen(&mut self) -> Result<(), PinError> {
+        Ok(())
+    }
+    /// Ask a yes or no question.
+    fn confirm(&mut self, question: &str) -> Result<bool, PinError> {
+        let confirm = loop {
             // Handle output
-            write!(tty, "\r\x1B[2K").unwrap();
-            write!(tty, "Are you sure you want to do this? (y/n)").unwrap();
-            tty.flush().unwrap();
+            self.write(format!("\r\x1B[2K{question} (y/n) "))?;
 
             // Handle inputs
-            if let Event::Key(key_event) = event::read().unwrap() {
-                match key_event.code {
-                    KeyCode::Char('y') | KeyCode::Char('Y') => {
-                        confirm = true;
-                        break;
-                    }
-                    KeyCode::Esc
-                    | KeyCode::Char('n')
-                    | KeyCode::Char('N')
-                    | KeyCode::Char('q')
-                    | KeyCode::Char('Q') => break,
-                    _ => {}
+            let Some(key) = self.read_key()? else {
+                continue;
+            };
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => break true,
+                KeyCode::Esc
+                | KeyCode::Char('n')
+                | KeyCode::Char('N')
+                | KeyCode::Char('q')
+                | KeyCode::Char('Q') => break false,
+                _ => {}
+            }
+        };
+        self.write("\n".to_string())?;
+
+        Ok(confirm)
+    }
+
+    /// Edit a line of text after a prompt. Returns None if the user backs out.
+    fn read_line(
+        &mut self,
+        prompt: &str,
+        mut input: LineInput,
+    ) -> Result<Option<String>, PinError> {
+        let offset = prompt.width() + 3;
+
+        let line = loop {
+            // Handle output, with any completions or problems dimmed after the text
+            let status = input
+                .status()
+                .map(|s| format!("  \x1B[2m{s}\x1B[0m"))
+                .unwrap_or_default();
+            self.write(format!(
+                "\r\x1B[2K{}: {}{}\r\x1B[{}G",
+                prompt,
+                input.text(),
+                status,
+                offset + input.cursor()
+            ))?;
+
+            // Handle input
+            let Some(key) = self.read_key()? else {
+                continue;
+            };
+            match input.handle(key) {
+                Edited::Done => break Some(input.text().to_string()),
+                Edited::Cancel => break None,
+                Edited::Editing => {}
+            }
+        };
+        self.write("\n".to_string())?;
+
+        Ok(line)
+    }
+
+    /// Pick one of the options on a single line. Each option has a key that picks it straight away.
+    /// Returns None if the user backs out.
+    fn select(&mut self, options: &[(&str, char)]) -> Result<Option<usize>, PinError> {
+        let mut selected = 0;
+
+        // Loop till choice is made or escape
+        let choice = loop {
+            // Handle output
+            let mut line = String::from("\r\x1B[2K");
+            for (i, (option, key)) in options.iter().enumerate() {
+                if i == selected {
+                    line += &format!("\x1b[32:5:41m[{} ({})]\x1b[0m", option, key);
+                } else {
+                    line += &format!(" {} ({}) ", option, key);
+                }
+            }
+            self.write(line)?;
+
+            // Handle input
+            let Some(key) = self.read_key()? else {
+                continue;
+            };
+            match key.code {
+                KeyCode::Enter => break Some(selected),
+                KeyCode::Char(c)
+                    if let Some(i) = options
+                        .iter()
+                        .position(|(_, key)| key.eq_ignore_ascii_case(&c)) =>
+                {
+                    break Some(i);
+                }
+                KeyCode::Left | KeyCode::Char('h') => selected = selected.saturating_sub(1),
+                KeyCode::Right | KeyCode::Char('l') => {
+                    selected = (selected + 1).min(options.len() - 1)
+                }
+                KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => break None,
+                _ => {}
+            }
+        };
+        self.write("\n".to_string())?;
+
+        Ok(choice)
+    }
+
+    /// Tick any number of options, one per line, starting from those in `chosen`. Space ticks the
+    /// option under the cursor and a ticks or clears them all. Returns the indices of the ticked
+    /// options, or None if the user backs out.
+    fn multi_select(
+        &mut self,
+        options: &[String],
+        chosen: &[usize],
+    ) -> Result<Option<Vec<usize>>, PinError> {
+        const HINT: &str = "space to tick, a for all, enter to accept, esc to cancel";
+        let mut ticked: Vec<bool> = (0..options.len()).map(|i| chosen.contains(&i)).collect();
+        let mut selected = 0;
+        let mut drawn = false;
+
+        let choice = loop {
+            // Handle output, going back up over the last draw first
+            let mut screen = String::new();
+            if drawn {
+                screen += &format!("\r\x1B[{}A", options.len());
+            }
+            for (i, option) in options.iter().enumerate() {
+                let tick = if ticked[i] { 'x' } else { ' ' };
+                if i == selected {
+                    screen += &format!("\r\x1B[2K\x1b[7m> [{tick}] {option}\x1b[0m\n");
+                } else {
+                    screen += &format!("\r\x1B[2K  [{tick}] {option}\n");
+                }
+            }
+            screen += &format!("\r\x1B[2K\x1B[2m{HINT}\x1B[0m");
+            self.write(screen)?;
+            drawn = true;
+
+            // Handle input
+            let Some(key) = self.read_key()? else {
+                continue;
+            };
+            match key.code {
+                KeyCode::Enter => {
+                    break Some((0..options.len()).filter(|&i| ticked[i]).collect());
+                }
+                KeyCode::Char(' ') => ticked[selected] = !ticked[selected],
+                KeyCode::Char('a') | KeyCode::Char('A') => {
+                    let all = ticked.iter().all(|&t| t);
+                    ticked.iter_mut().for_each(|t| *t = !all);
+                }
+                KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => {
+                    selected = (selected + 1).min(options.len().saturating_sub(1))
                 }
+                KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => break None,
+                _ => {}
+            }
+        };
+        self.write("\n".to_string())?;
+
+        Ok(choice)
+    }
+}
+
+impl Prompt for Tty {
+    fn write(&mut self, msg: String) -> Result<(), PinError> {
+        Tty::write(self, msg)
+    }
+
+    fn read_key(&mut self) -> Result<Option<KeyEvent>, PinError> {
+        Tty::read_key(self)
+    }
+
+    fn columns(&self) -> Option<usize> {
+        // A terminal that doesn't know its size reports 0
+        terminal::size()
+            .ok()
+            .map(|(width, _)| width as usize)
+            .filter(|&width| width > 0)
+    }
+
+    fn lines(&self) -> Option<usize> {
+        terminal::size()
+            .ok()
+            .map(|(_, height)| height as usize)
+            .filter(|&height| height > 0)
+    }
+
+    fn enter_alternate_screen(&mut self) -> Result<(), PinError> {
+        Tty::enter_alternate_screen(self)
+    }
+
+    fn leave_alternate_screen(&mut self) -> Result<(), PinError> {
+        Tty::leave_alternate_screen(self)
+    }
+}
+
+impl<P: Prompt + ?Sized> Prompt for &mut P {
+    fn write(&mut self, msg: String) -> Result<(), PinError> {
+        (**self).write(msg)
+    }
+
+    fn read_key(&mut self) -> Result<Option<KeyEvent>, PinError> {
+        (**self).read_key()
+    }
+
+    fn columns(&self) -> Option<usize> {
+        (**self).columns()
+    }
+
+    fn lines(&self) -> Option<usize> {
+        (**self).lines()
+    }
+
+    fn enter_alternate_screen(&mut self) -> Result<(), PinError> {
+        (**self).enter_alternate_screen()
+    }
+
+    fn leave_alternate_screen(&mut self) -> Result<(), PinError> {
+        (**self).leave_alternate_screen()
+    }
+}
+
+// Remember how the terminal was and put it back if pin panics or is killed. Only done once, by the
+// first Tty, which is the one that sees the terminal before raw mode
+fn install_guard() {
+    GUARD.call_once(|| {
+        // A descriptor of our own that is never closed, so the restore never depends on a Tty
+        let Ok(tty) = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+        else {
+            return;
+        };
+        let fd = tty.into_raw_fd();
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+            return;
+        }
+        let _ = ORIGINAL.set((fd, termios));
+
+        // Restore before the panic message is printed, so it isn't garbled by raw mode
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            previous(info);
+        }));
+
+        let handler = on_signal as extern "C" fn(libc::c_int);
+        unsafe {
+            libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+            libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+        }
+    });
+}
+
+// Put the terminal back how it was found. Only makes calls that are safe in a signal handler
+fn restore_terminal() {
+    let Some((fd, termios)) = ORIGINAL.get() else {
+        return;
+    };
+    let reset: &[u8] = if ALTERNATE.load(Ordering::SeqCst) {
+        b"\x1B[?25h\x1B[?1049l"
+    } else {
+        b"\x1B[?25h"
+    };
+    unsafe {
+        libc::write(*fd, reset.as_ptr().cast(), reset.len());
+        libc::tcsetattr(*fd, libc::TCSANOW, termios);
+    }
+}
+
+// Exit the way the shell expects for the signal, with the terminal usable again
+extern "C" fn on_signal(signal: libc::c_int) {
+    restore_terminal();
+    unsafe { libc::_exit(128 + signal) }
+}
+
+// Lets crossterm commands be queued straight to the terminal
+impl Write for Tty {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.tty.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.tty.flush()
+    }
+}
+
+/// A line of text being edited. The cursor is a byte offset that only ever sits between
+/// graphemes, so accented letters, emoji and other multi-byte characters move and delete as one.
+/// Keys follow emacs and readline: ctrl-a/e, ctrl-b/f, alt-b/f for words, ctrl-w/u/k to cut,
+/// up and down for history and tab to complete.
+pub struct LineInput {
+    text: String,
+    cursor: usize,
+    // Earlier entries, oldest first
+    history: Vec<String>,
+    // Position in the history while browsing it, with the line that was being typed before
+    browsing: Option<(usize, String)>,
+    complete: Complete,
+    validate: Option<Box<Validate>>,
+    // Candidates from the last tab that matched more than one thing
+    candidates: Vec<String>,
+}
+
+/// Check a line as it is typed, returning what is wrong with it.
+pub type Validate = dyn Fn(&str) -> Result<(), String>;
+
+/// What tab completes.
+pub enum Complete {
+    /// Tab does nothing.
+    Nothing,
+    /// Files and directories, for the whole line.
+    Paths,
+    /// Any of these, for the word under the cursor.
+    Words(Vec<String>),
+}
+
+/// What a key press did to a line.
+pub enum Edited {
+    /// The line is still being edited.
+    Editing,
+    /// Enter was pressed on a valid line.
+    Done,
+    /// The user backed out.
+    Cancel,
+}
+
+impl LineInput {
+    /// Start editing some text, with the cursor at the end.
+    pub fn new(text: &str) -> LineInput {
+        LineInput {
+            text: text.to_string(),
+            cursor: text.len(),
+            history: Vec::new(),
+            browsing: None,
+            complete: Complete::Nothing,
+            validate: None,
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Earlier entries for up and down, oldest first.
+    pub fn with_history(mut self, history: Vec<String>) -> LineInput {
+        self.history = history;
+        self
+    }
+
+    /// What tab completes.
+    pub fn completing(mut self, complete: Complete) -> LineInput {
+        self.complete = complete;
+        self
+    }
+
+    /// Check the line as it is typed. Enter is refused while it fails.
+    pub fn validating(mut self, validate: impl Fn(&str) -> Result<(), String> + 'static) -> Self {
+        self.validate = Some(Box::new(validate));
+        self
+    }
+
+    /// The text as it is now.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Column of the cursor from the start of the text, in terminal cells.
+    pub fn cursor(&self) -> usize {
+        self.text[..self.cursor].width()
+    }
+
+    /// Anything to show after the line: completions to pick from, or what is wrong with the text.
+    pub fn status(&self) -> Option<String> {
+        if !self.candidates.is_empty() {
+            return Some(self.candidates.join("  "));
+        }
+        self.validate.as_ref()?(&self.text).err()
+    }
+
+    /// Apply a key press to the line.
+    pub fn handle(&mut self, key: KeyEvent) -> Edited {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        self.candidates.clear();
+
+        match key.code {
+            // Stay put until the line is valid. The reason is already on screen
+            KeyCode::Enter if self.validate.as_ref().is_none_or(|v| v(&self.text).is_ok()) => {
+                return Edited::Done;
             }
+            KeyCode::Esc => return Edited::Cancel,
+            KeyCode::Char('g') if ctrl => return Edited::Cancel,
+
+            // Movement
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Char('e') if ctrl => self.cursor = self.text.len(),
+            KeyCode::Left if ctrl || alt => self.cursor = self.word_start(),
+            KeyCode::Char('b') if alt => self.cursor = self.word_start(),
+            KeyCode::Right if ctrl || alt => self.cursor = self.word_end(),
+            KeyCode::Char('f') if alt => self.cursor = self.word_end(),
+            KeyCode::Left => self.cursor = self.prev_grapheme(),
+            KeyCode::Char('b') if ctrl => self.cursor = self.prev_grapheme(),
+            KeyCode::Right => self.cursor = self.next_grapheme(),
+            KeyCode::Char('f') if ctrl => self.cursor = self.next_grapheme(),
+
+            // Deletion
+            KeyCode::Backspace if alt => self.cut(self.word_start(), self.cursor),
+            KeyCode::Char('w') if ctrl => self.cut(self.word_start(), self.cursor),
+            KeyCode::Char('d') if alt => self.cut(self.cursor, self.word_end()),
+            KeyCode::Backspace => self.cut(self.prev_grapheme(), self.cursor),
+            KeyCode::Char('h') if ctrl => self.cut(self.prev_grapheme(), self.cursor),
+            KeyCode::Delete => self.cut(self.cursor, self.next_grapheme()),
+            KeyCode::Char('d') if ctrl => self.cut(self.cursor, self.next_grapheme()),
+            KeyCode::Char('u') if ctrl => self.cut(0, self.cursor),
+            KeyCode::Char('k') if ctrl => self.cut(self.cursor, self.text.len()),
+
+            // History
+            KeyCode::Up => self.browse(true),
+            KeyCode::Char('p') if ctrl => self.browse(true),
+            KeyCode::Down => self.browse(false),
+            KeyCode::Char('n') if ctrl => self.browse(false),
+
+            KeyCode::Tab => self.complete(),
+            KeyCode::Char(c) if !ctrl && !alt => {
+                self.text.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            _ => {}
+        }
+        Edited::Editing
+    }
+
+    fn prev_grapheme(&self) -> usize {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_grapheme(&self) -> usize {
+        self.text[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map_or(self.cursor, |g| self.cursor + g.len())
+    }
+
+    // Start of the word before the cursor. Words are split by spaces and slashes, so ctrl-w takes
+    // off one directory of a path at a time
+    fn word_start(&self) -> usize {
+        let before: Vec<(usize, &str)> = self.text[..self.cursor].grapheme_indices(true).collect();
+        let mut i = before.len();
+        while i > 0 && is_separator(before[i - 1].1) {
+            i -= 1;
+        }
+        while i > 0 && !is_separator(before[i - 1].1) {
+            i -= 1;
         }
-        write!(tty, "\n\r").unwrap();
+        before.get(i).map_or(self.cursor, |(start, _)| *start)
+    }
+
+    // End of the word after the cursor
+    fn word_end(&self) -> usize {
+        let mut graphemes = self.text[self.cursor..].grapheme_indices(true).peekable();
+        while graphemes.next_if(|(_, g)| is_separator(g)).is_some() {}
+        while graphemes.next_if(|(_, g)| !is_separator(g)).is_some() {}
+        graphemes
+            .peek()
+            .map_or(self.text.len(), |(i, _)| self.cursor + i)
+    }
 
-        confirm
+    fn cut(&mut self, start: usize, end: usize) {
+        self.text.replace_range(start..end, "");
+        self.cursor = start;
+    }
+
+    fn browse(&mut self, back: bool) {
+        let (pos, typed) = match self.browsing.take() {
+            Some(browsing) => browsing,
+            None => (self.history.len(), self.text.clone()),
+        };
+        let pos = if back {
+            pos.saturating_sub(1)
+        } else {
+            (pos + 1).min(self.history.len())
+        };
+
+        self.text = self.history.get(pos).unwrap_or(&typed).clone();
+        self.cursor = self.text.len();
+        if pos < self.history.len() {
+            self.browsing = Some((pos, typed));
+        }
+    }
+
+    fn complete(&mut self) {
+        // The text being completed starts here
+        let start = match &self.complete {
+            Complete::Nothing => return,
+            Complete::Paths => 0,
+            Complete::Words(_) => self.text[..self.cursor]
+                .rfind([' ', ','])
+                .map_or(0, |i| i + 1),
+        };
+        let word = &self.text[start..self.cursor];
+
+        let (keep, mut candidates) = match &self.complete {
+            Complete::Paths => complete_path(word),
+            Complete::Words(words) => (
+                String::new(),
+                words
+                    .iter()
+                    .filter(|w| w.starts_with(word))
+                    .cloned()
+                    .collect(),
+            ),
+            Complete::Nothing => return,
+        };
+        candidates.sort();
+        candidates.dedup();
+
+        // Fill in as much as every candidate shares, and list them if that isn't all of one
+        let Some(first) = candidates.first() else {
+            return;
+        };
+        let common = candidates.iter().fold(first.as_str(), |common, c| {
+            let len = common
+                .char_indices()
+                .zip(c.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(common.len().min(c.len()), |((i, _), _)| i);
+            &common[..len]
+        });
+        let completed = format!("{keep}{common}");
+        self.text.replace_range(start..self.cursor, &completed);
+        self.cursor = start + completed.len();
+
+        if candidates.len() > 1 {
+            self.candidates = candidates.into_iter().take(10).collect();
+        }
     }
 }
 
+fn is_separator(grapheme: &str) -> bool {
+    grapheme == "/" || grapheme.chars().all(char::is_whitespace)
+}
+
+// Complete the last part of a path. Returns the part of the path that is kept and the names of the
+// entries that could follow it, with a / after directories
+fn complete_path(path: &str) -> (String, Vec<String>) {
+    let policy = PathPolicy {
+        allow_missing: true,
+        ..Default::default()
+    };
+    let (dir, prefix) = match path.rfind('/') {
+        Some(i) => path.split_at(i + 1),
+        // A home directory on its own, e.g. ~ or ~user, only gets its slash if it exists
+        None if path.starts_with('~') => {
+            let home = parse_path(path, policy).is_ok_and(|home| home.is_dir());
+            let candidates = home.then(|| format!("{path}/")).into_iter().collect();
+            return (String::new(), candidates);
+        }
+        None => ("", path),
+    };
+
+    let Ok(entries) = parse_path(if dir.is_empty() { "." } else { dir }, policy)
+        .and_then(|dir| std::fs::read_dir(dir).map_err(PinError::StoreIo))
+    else {
+        return (dir.to_string(), Vec::new());
+    };
+
+    let candidates = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Hidden entries only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(if is_dir { format!("{name}/") } else { name })
+        })
+        .collect();
+    (dir.to_string(), candidates)
+}
+
+// Lines entered at each kind of prompt before, oldest first, for browsing with up and down
+pub fn load_history(kind: &str) -> Vec<String> {
+    let Ok(path) = pin_dir().map(|dir| dir.join("input_history")) else {
+        return Vec::new();
+    };
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+
+    text.lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(k, _)| *k == kind)
+        .map(|(_, line)| line.to_string())
+        .collect()
+}
+
+// Add a line to the history of a prompt. History is a convenience, so failing to save it isn't an
+// error
+pub fn remember(kind: &str, line: &str) {
+    const MAX_LINES: usize = 50;
+    if line.is_empty() || line.contains(['\t', '\n']) {
+        return;
+    }
+    let Ok(path) = pin_dir().map(|dir| dir.join("input_history")) else {
+        return;
+    };
+
+    let text = std::fs::read_to_string(&path).unwrap_or_default();
+    let mut lines: Vec<(&str, &str)> = text
+        .lines()
+        .filter_map(|l| l.split_once('\t'))
+        .filter(|&(k, l)| !(k == kind && l == line))
+        .collect();
+    lines.push((kind, line));
+
+    // Keep the newest lines of each kind
+    let mut count = 0;
+    let mut kept: Vec<&(&str, &str)> = lines
+        .iter()
+        .rev()
+        .filter(|(k, _)| {
+            if *k != kind {
+                return true;
+            }
+            count += 1;
+            count <= MAX_LINES
+        })
+        .collect();
+    kept.reverse();
+
+    let text: String = kept.iter().map(|(k, l)| format!("{k}\t{l}\n")).collect();
+    let _ = write_atomic(&path, text.as_bytes());
+}
+
 impl Drop for Tty {
     fn drop(&mut self) {
-        self.tty.flush().unwrap();
+        let _ = self.tty.flush();
+
+        let _ = disable_raw_mode();
+    }
+}
+
+// A terminal for tests. It plays back a script of key presses and keeps everything written to it
+#[cfg(test)]
+pub struct Script {
+    keys: std::collections::VecDeque<KeyEvent>,
+    pub output: String,
+}
+
+#[cfg(test)]
+impl Script {
+    pub fn new() -> Script {
+        Script {
+            keys: std::collections::VecDeque::new(),
+            output: String::new(),
+        }
+    }
+
+    pub fn key(mut self, code: KeyCode) -> Script {
+        self.keys.push_back(KeyEvent::new(code, KeyModifiers::NONE));
+        self
+    }
+
+    pub fn ctrl(mut self, c: char) -> Script {
+        let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        self.keys.push_back(key);
+        self
+    }
+
+    pub fn text(self, text: &str) -> Script {
+        text.chars()
+            .fold(self, |script, c| script.key(KeyCode::Char(c)))
+    }
+
+    // Whether every key was read, so a test can tell a prompt it expected never came
+    pub fn finished(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[cfg(test)]
+impl Terminal for Script {
+    fn open(&mut self) -> Result<Box<dyn Prompt + '_>, PinError> {
+        Ok(Box::new(self))
+    }
+}
+
+#[cfg(test)]
+impl Prompt for Script {
+    fn write(&mut self, msg: String) -> Result<(), PinError> {
+        self.output += &msg;
+        Ok(())
+    }
+
+    // Ctrl-C behaves as it does on a real terminal. Running out of keys means a prompt came that
+    // the test didn't expect, and is an error rather than a hang
+    fn read_key(&mut self) -> Result<Option<KeyEvent>, PinError> {
+        match self.keys.pop_front() {
+            Some(key)
+                if key.modifiers.contains(KeyModifiers::CONTROL)
+                    && key.code == KeyCode::Char('c') =>
+            {
+                Err(PinError::Interrupted)
+            }
+            Some(key) => Ok(Some(key)),
+            None => Err(PinError::TtyUnavailable(std::io::Error::other(
+                "the script has no more keys",
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Feed keys to a line, returning it
+    fn edit(mut input: LineInput, keys: &[(KeyCode, KeyModifiers)]) -> LineInput {
+        for &(code, modifiers) in keys {
+            input.handle(KeyEvent::new(code, modifiers));
+        }
+        input
+    }
+
+    const NONE: KeyModifiers = KeyModifiers::NONE;
+    const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+    const ALT: KeyModifiers = KeyModifiers::ALT;
+
+    #[test]
+    fn combining_marks_and_emoji_are_one_grapheme() {
+        // e and a combining acute accent
+        let input = edit(LineInput::new("cafe\u{301}"), &[(KeyCode::Backspace, NONE)]);
+        assert_eq!(input.text(), "caf");
+
+        // A family joined with zero width joiners moves and deletes as a whole
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let input = edit(
+            LineInput::new(&format!("a{family}b")),
+            &[(KeyCode::Left, NONE), (KeyCode::Left, NONE)],
+        );
+        assert_eq!(input.cursor(), 1);
+        let input = edit(input, &[(KeyCode::Delete, NONE)]);
+        assert_eq!(input.text(), "ab");
+        let input = edit(
+            LineInput::new(&format!("a{family}")),
+            &[(KeyCode::Backspace, NONE)],
+        );
+        assert_eq!(input.text(), "a");
+    }
+
+    #[test]
+    fn ctrl_w_cuts_a_word_or_directory_at_a_time() {
+        let mut input = LineInput::new("cd ~/code/api");
+        for expected in ["cd ~/code/", "cd ~/", "cd ", "", ""] {
+            input = edit(input, &[(KeyCode::Char('w'), CTRL)]);
+            assert_eq!(input.text(), expected);
+        }
+    }
+
+    #[test]
+    fn alt_b_and_alt_f_move_by_words() {
+        let input = edit(
+            LineInput::new("one two three"),
+            &[
+                (KeyCode::Char('b'), ALT),
+                (KeyCode::Char('b'), ALT),
+                (KeyCode::Char('f'), ALT),
+                (KeyCode::Char('X'), NONE),
+            ],
+        );
+        assert_eq!(input.text(), "one twoX three");
+
+        let input = edit(
+            LineInput::new("one two"),
+            &[
+                (KeyCode::Char('a'), CTRL),
+                (KeyCode::Char('f'), ALT),
+                (KeyCode::Char('f'), ALT),
+                (KeyCode::Char('f'), ALT),
+                (KeyCode::Char('!'), NONE),
+            ],
+        );
+        assert_eq!(input.text(), "one two!");
+    }
+
+    #[test]
+    fn history_browses_back_and_returns_to_the_typed_line() {
+        let history = vec!["first".to_string(), "second".to_string()];
+        let mut input = LineInput::new("typed").with_history(history);
+
+        let steps = [
+            (KeyCode::Up, "second"),
+            (KeyCode::Up, "first"),
+            (KeyCode::Up, "first"),
+            (KeyCode::Down, "second"),
+            (KeyCode::Down, "typed"),
+            (KeyCode::Down, "typed"),
+        ];
+        for (code, expected) in steps {
+            input = edit(input, &[(code, NONE)]);
+            assert_eq!(input.text(), expected, "{code:?}");
+            assert_eq!(input.cursor(), expected.len());
+        }
+    }
 
-        disable_raw_mode().unwrap();
+    #[test]
+    fn home_directories_complete_only_when_they_exist() {
+        assert_eq!(complete_path("~"), (String::new(), vec!["~/".to_string()]));
+        assert_eq!(complete_path("~nosuchuser"), (String::new(), Vec::new()));
     }
 }
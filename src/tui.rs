@@ -0,0 +1,908 @@
+// Full-screen pin manager
+//
+// `pin --tui` lists every pin above a pane with the selected pin's details. Pins can be filtered,
+// sorted, renamed, re-pointed, tagged, noted and deleted in place, and any change can be undone.
+// Each change is written as soon as it is made, holding the store lock only for the write, so
+// other shells can keep using pin while the manager is open.
+
+use std::path::Path;
+
+use crossterm::{
+    cursor,
+    event::{KeyCode, KeyEvent},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{Clear, ClearType},
+};
+
+use crate::{
+    alias::PinAlias,
+    cmd::{check_tags, path_input},
+    error::PinError,
+    expiry::fmt_left,
+    history::{History, fmt_ago},
+    list::take_width,
+    path::parse_for_store,
+    pattern::search_rank,
+    store::Store,
+    tty::{Complete, Edited, LineInput, Prompt, Terminal, load_history, remember},
+};
+use unicode_width::UnicodeWidthStr;
+
+// Lines taken by the detail pane, including its border
+const DETAIL_HEIGHT: u16 = 7;
+
+// The size drawn at on terminals that don't know theirs
+const DEFAULT_SIZE: (usize, usize) = (80, 24);
+
+const HELP: &[(&str, &str)] = &[
+    ("up/down, j/k", "move"),
+    ("pgup/pgdn, g/G", "move a page, or to the ends"),
+    ("enter", "jump to the pin"),
+    ("/", "filter by alias, path, tag or note"),
+    ("s, S", "change the sort, or reverse it"),
+    ("r", "rename"),
+    ("e", "edit the path"),
+    ("t", "edit the tags"),
+    ("n", "edit the note"),
+    ("d", "delete"),
+    ("u", "undo the last change"),
+    ("?", "show or hide this help"),
+    ("q, esc", "quit"),
+];
+
+// Run the manager. Returns the alias to jump to, if the user picked one
+pub fn run(term: &mut dyn Terminal) -> Result<Option<String>, PinError> {
+    let mut manager = Manager::load()?;
+    let mut screen = Screen::enter(term.open()?)?;
+
+    loop {
+        manager.draw(&mut *screen.tty)?;
+        // A resize just needs a redraw, and Ctrl-C comes back as an error that leaves the screen
+        if let Some(key) = screen.tty.read_key()? {
+            match manager.handle(key) {
+                Outcome::Stay => {}
+                Outcome::Quit => return Ok(None),
+                Outcome::Jump(alias) => return Ok(Some(alias)),
+            }
+        }
+    }
+}
+
+// The alternate screen, left again when dropped so the shell's scrollback is untouched
+struct Screen<'a> {
+    tty: Box<dyn Prompt + 'a>,
+}
+
+impl<'a> Screen<'a> {
+    fn enter(mut tty: Box<dyn Prompt + 'a>) -> Result<Screen<'a>, PinError> {
+        tty.enter_alternate_screen()?;
+
+        Ok(Screen { tty })
+    }
+}
+
+impl Drop for Screen<'_> {
+    fn drop(&mut self) {
+        let _ = self.tty.leave_alternate_screen();
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Sort {
+    Alias,
+    Path,
+    Recent,
+}
+
+impl Sort {
+    fn next(self) -> Sort {
+        match self {
+            Sort::Alias => Sort::Path,
+            Sort::Path => Sort::Recent,
+            Sort::Recent => Sort::Alias,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Sort::Alias => "alias",
+            Sort::Path => "path",
+            Sort::Recent => "recent",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Alias,
+    Path,
+    Tags,
+    Note,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Alias => "Rename",
+            Field::Path => "Path",
+            Field::Tags => "Tags",
+            Field::Note => "Note",
+        }
+    }
+
+    // Which prompt history the field uses
+    fn kind(self) -> &'static str {
+        match self {
+            Field::Alias => "alias",
+            Field::Path => "path",
+            Field::Tags => "tags",
+            Field::Note => "note",
+        }
+    }
+}
+
+enum Mode {
+    List,
+    Filter(LineInput),
+    Edit(Field, LineInput),
+    ConfirmDelete,
+    Help,
+}
+
+enum Outcome {
+    Stay,
+    Quit,
+    Jump(String),
+}
+
+// A change to one pin, as the field edited with its value before and after. Only that field is
+// written, so changes made from another shell while the manager is open are kept
+#[derive(Clone)]
+enum Change {
+    Rename(String, String),
+    Path(String, String, String),
+    Tags(String, Vec<String>, Vec<String>),
+    Note(String, String, String),
+    Delete(String, PinAlias),
+    Restore(String, PinAlias),
+}
+
+impl Change {
+    // The change that puts this one back
+    fn inverse(self) -> Change {
+        match self {
+            Change::Rename(from, to) => Change::Rename(to, from),
+            Change::Path(alias, before, after) => Change::Path(alias, after, before),
+            Change::Tags(alias, before, after) => Change::Tags(alias, after, before),
+            Change::Note(alias, before, after) => Change::Note(alias, after, before),
+            Change::Delete(alias, record) => Change::Restore(alias, record),
+            Change::Restore(alias, record) => Change::Delete(alias, record),
+        }
+    }
+
+    // The alias that exists once the change is made
+    fn alias(&self) -> &str {
+        match self {
+            Change::Rename(_, alias)
+            | Change::Path(alias, ..)
+            | Change::Tags(alias, ..)
+            | Change::Note(alias, ..)
+            | Change::Delete(alias, _)
+            | Change::Restore(alias, _) => alias,
+        }
+    }
+
+    // Make the change to the pins as they are in the store now, which may not be as they were
+    // when the manager read them
+    fn make(&self, store: &mut Store) -> Result<(), PinError> {
+        let found = |store: &Store, alias: &str| match store.get(alias) {
+            Some(record) => Ok(record.locked),
+            None => Err(PinError::AliasNotFound(alias.to_string())),
+        };
+        let free = |store: &Store, alias: &str| match store.get(alias) {
+            None => Ok(()),
+            Some(record) if record.locked => Err(PinError::Locked(alias.to_string())),
+            Some(_) => Err(PinError::Usage(format!("\"{alias}\" already exists."))),
+        };
+        let unlocked = |store: &Store, alias: &str| match found(store, alias)? {
+            true => Err(PinError::Locked(alias.to_string())),
+            false => Ok(()),
+        };
+
+        match self {
+            Change::Rename(from, to) => {
+                unlocked(store, from)?;
+                free(store, to)?;
+                if let Some(record) = store.delete(from) {
+                    store.add(to.clone(), record);
+                }
+            }
+            Change::Path(alias, _, path) => {
+                unlocked(store, alias)?;
+                if let Some(record) = store.get_mut(alias) {
+                    record.path = path.clone();
+                    record.stamp();
+                }
+            }
+            // Tags and notes can be changed on locked pins
+            Change::Tags(alias, _, tags) => {
+                found(store, alias)?;
+                if let Some(record) = store.get_mut(alias) {
+                    record.tags = tags.clone();
+                }
+            }
+            Change::Note(alias, _, note) => {
+                found(store, alias)?;
+                if let Some(record) = store.get_mut(alias) {
+                    record.note = note.clone();
+                }
+            }
+            Change::Delete(alias, _) => {
+                unlocked(store, alias)?;
+                store.delete(alias);
+            }
+            Change::Restore(alias, record) => {
+                free(store, alias)?;
+                store.add(alias.clone(), record.clone());
+            }
+        }
+        Ok(())
+    }
+
+    // Every alias the change touches
+    fn aliases(&self) -> Vec<&String> {
+        match self {
+            Change::Rename(from, to) => vec![from, to],
+            Change::Path(alias, ..)
+            | Change::Tags(alias, ..)
+            | Change::Note(alias, ..)
+            | Change::Delete(alias, _)
+            | Change::Restore(alias, _) => vec![alias],
+        }
+    }
+}
+
+struct Manager {
+    // Every pin, in alias order
+    pins: Vec<(String, PinAlias)>,
+    // For the number of jumps to each alias and when the last one was
+    history: History,
+    // Indexes into pins of the ones shown, filtered and sorted
+    view: Vec<usize>,
+    // Index into view
+    selected: usize,
+    scroll: usize,
+    // Rows in the list at the last draw, for paging
+    page: usize,
+    filter: String,
+    sort: Sort,
+    reverse: bool,
+    mode: Mode,
+    undo: Vec<Change>,
+    message: String,
+}
+
+impl Manager {
+    fn load() -> Result<Manager, PinError> {
+        let store = Store::init()?;
+
+        let mut manager = Manager {
+            pins: store.into_pins().into_iter().collect(),
+            history: History::load()?,
+            view: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            page: 1,
+            filter: String::new(),
+            sort: Sort::Alias,
+            reverse: false,
+            mode: Mode::List,
+            undo: Vec::new(),
+            message: String::new(),
+        };
+        manager.refresh();
+        Ok(manager)
+    }
+
+    fn current(&self) -> Option<&(String, PinAlias)> {
+        self.pins.get(*self.view.get(self.selected)?)
+    }
+
+    // Rebuild the view after the pins, filter or sort change
+    fn refresh(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.view = (0..self.pins.len())
+            .filter(|&i| {
+                let (alias, record) = &self.pins[i];
+                filter.is_empty() || search_rank(alias, record, &filter).is_some()
+            })
+            .collect();
+
+        let last_jump = |i: &usize| self.history.usage(&self.pins[*i].0).last;
+        match self.sort {
+            // Pins are already in alias order
+            Sort::Alias => {}
+            Sort::Path => self.view.sort_by_key(|&i| &self.pins[i].1.path),
+            Sort::Recent => self.view.sort_by_key(|i| std::cmp::Reverse(last_jump(i))),
+        }
+        if self.reverse {
+            self.view.reverse();
+        }
+
+        self.selected = self.selected.min(self.view.len().saturating_sub(1));
+    }
+
+    // Move the selection onto an alias, if it is shown
+    fn select(&mut self, alias: &str) {
+        if let Some(pos) = self.view.iter().position(|&i| self.pins[i].0 == alias) {
+            self.selected = pos;
+        }
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> Outcome {
+        match std::mem::replace(&mut self.mode, Mode::List) {
+            Mode::List => return self.handle_list(key.code),
+            Mode::Help => {}
+            Mode::ConfirmDelete => {
+                if let (KeyCode::Char('y' | 'Y'), Some((alias, record))) =
+                    (key.code, self.current())
+                {
+                    let change = Change::Delete(alias.clone(), record.clone());
+                    self.apply(change, false);
+                }
+            }
+            Mode::Filter(mut input) => {
+                match input.handle(key) {
+                    Edited::Editing => self.mode = Mode::Filter(input),
+                    Edited::Done => {}
+                    Edited::Cancel => self.filter.clear(),
+                }
+                // Filter as the user types
+                if let Mode::Filter(input) = &self.mode {
+                    self.filter = input.text().to_string();
+                }
+                self.selected = 0;
+                self.refresh();
+            }
+            Mode::Edit(field, mut input) => match input.handle(key) {
+                Edited::Editing => self.mode = Mode::Edit(field, input),
+                Edited::Done => {
+                    remember(field.kind(), input.text());
+                    self.commit(field, input.text().to_string());
+                }
+                Edited::Cancel => {}
+            },
+        }
+        Outcome::Stay
+    }
+
+    fn handle_list(&mut self, key: KeyCode) -> Outcome {
+        self.message.clear();
+        let last = self.view.len().saturating_sub(1);
+
+        match key {
+            KeyCode::Char('q') => return Outcome::Quit,
+            KeyCode::Esc if self.filter.is_empty() => return Outcome::Quit,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.refresh();
+            }
+            KeyCode::Enter => {
+                if let Some((alias, _)) = self.current() {
+                    return Outcome::Jump(alias.clone());
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(self.page),
+            KeyCode::PageDown => self.selected = (self.selected + self.page).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Char('/') => self.mode = Mode::Filter(LineInput::new(&self.filter)),
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next();
+                self.refresh();
+            }
+            KeyCode::Char('S') => {
+                self.reverse = !self.reverse;
+                self.refresh();
+            }
+            KeyCode::Char('u') => match self.undo.pop() {
+                Some(change) => self.apply(change, true),
+                None => self.message = "Nothing to undo.".to_string(),
+            },
+            KeyCode::Char('?') => self.mode = Mode::Help,
+            KeyCode::Char(c @ ('r' | 'e' | 't' | 'n' | 'd')) => {
+                let Some((alias, record)) = self.current() else {
+                    return Outcome::Stay;
+                };
+                // Locked pins can only be changed from the command line with --force
+                if record.locked && c != 't' && c != 'n' {
+                    self.message = format!("\"{alias}\" is locked.");
+                    return Outcome::Stay;
+                }
+
+                let aliases = || self.pins.iter().map(|(a, _)| a.clone()).collect();
+                let tags = || self.pins.iter().flat_map(|(_, r)| r.tags.clone()).collect();
+                let (field, input) = match c {
+                    'r' => (
+                        Field::Alias,
+                        LineInput::new(alias).completing(Complete::Words(aliases())),
+                    ),
+                    'e' => (Field::Path, path_input(&record.path, record.policy)),
+                    't' => (
+                        Field::Tags,
+                        LineInput::new(&record.tags.join(" ")).completing(Complete::Words(tags())),
+                    ),
+                    'n' => (Field::Note, LineInput::new(&record.note)),
+                    _ => {
+                        self.mode = Mode::ConfirmDelete;
+                        return Outcome::Stay;
+                    }
+                };
+                self.mode = Mode::Edit(field, input.with_history(load_history(field.kind())));
+            }
+            _ => {}
+        }
+        Outcome::Stay
+    }
+
+    // Make the change an edit describes
+    fn commit(&mut self, field: Field, text: String) {
+        let Some((alias, record)) = self.current().cloned() else {
+            return;
+        };
+
+        let change = match field {
+            Field::Alias => {
+                let text = text.trim().to_string();
+                if text.is_empty() || text == alias {
+                    return;
+                }
+                Change::Rename(alias, text)
+            }
+            Field::Path => match parse_for_store(&text, record.policy) {
+                Ok(path) => Change::Path(alias, record.path, path),
+                Err(e) => {
+                    self.message = e.to_string();
+                    return;
+                }
+            },
+            Field::Tags => {
+                let mut tags: Vec<String> = text
+                    .split([' ', ','])
+                    .filter(|t| !t.is_empty())
+                    .map(String::from)
+                    .collect();
+                if let Err(e) = check_tags(&tags) {
+                    self.message = e.to_string();
+                    return;
+                }
+                tags.sort();
+                tags.dedup();
+                Change::Tags(alias, record.tags, tags)
+            }
+            Field::Note => Change::Note(alias, record.note, text.trim().to_string()),
+        };
+        self.apply(change, false);
+    }
+
+    // Write a change to the store, or undo one. It is made under the store lock against the pins
+    // as they are now, and refused if another shell has since locked a pin or taken an alias
+    fn apply(&mut self, change: Change, undoing: bool) {
+        let mut made = if undoing {
+            change.clone().inverse()
+        } else {
+            change
+        };
+        let result = (|| {
+            let mut store = Store::lock()?;
+            // Undoing a delete brings back the pin as it was deleted, not as it was read
+            if let Change::Delete(alias, record) = &mut made
+                && let Some(current) = store.get(alias)
+            {
+                *record = current.clone();
+            }
+            made.make(&mut store)?;
+
+            // Only the pins that changed are updated, rather than copying the whole store again
+            let records: Vec<(String, Option<PinAlias>)> = made
+                .aliases()
+                .into_iter()
+                .map(|alias| (alias.clone(), store.get(alias).cloned()))
+                .collect();
+            store.save()?;
+            Ok::<_, PinError>(records)
+        })();
+        let records = match result {
+            Ok(records) => records,
+            Err(e) => {
+                self.message = e.to_string();
+                // Keep a failed undo so it can be tried again
+                if undoing {
+                    self.undo.push(made.inverse());
+                }
+                return;
+            }
+        };
+
+        for (alias, record) in records {
+            match (self.pins.binary_search_by(|(a, _)| a.cmp(&alias)), record) {
+                (Ok(i), Some(record)) => self.pins[i].1 = record,
+                (Err(i), Some(record)) => self.pins.insert(i, (alias, record)),
+                (Ok(i), None) => {
+                    self.pins.remove(i);
+                }
+                (Err(_), None) => {}
+            }
+        }
+
+        // Follow the pin that changed, which for a rename is the one that now exists
+        self.refresh();
+        self.select(made.alias());
+        self.message = if undoing { "Undone." } else { "Saved." }.to_string();
+        if !undoing {
+            self.undo.push(made);
+        }
+    }
+
+    fn draw(&mut self, tty: &mut dyn Prompt) -> Result<(), PinError> {
+        let width = tty.columns().unwrap_or(DEFAULT_SIZE.0);
+        let height = tty.lines().unwrap_or(DEFAULT_SIZE.1) as u16;
+        let list_height = height.saturating_sub(DETAIL_HEIGHT + 2).max(1) as usize;
+        self.page = list_height;
+
+        // Keep the selection on screen
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+
+        let mut out = Vec::new();
+        let mut line = |row: u16, text: String, attr: Attribute| {
+            queue!(
+                out,
+                cursor::MoveTo(0, row),
+                SetAttribute(attr),
+                Print(fit(&text, width)),
+                SetAttribute(Attribute::Reset)
+            )
+        };
+        let io = PinError::TtyUnavailable;
+
+        // Title
+        let mut title = format!(" pin: {} of {} pins", self.view.len(), self.pins.len());
+        title += &format!(", sorted by {}", self.sort.name());
+        if self.reverse {
+            title += " (reversed)";
+        }
+        if !self.filter.is_empty() {
+            title += &format!(", filter \"{}\"", self.filter);
+        }
+        line(0, pad(&title, width), Attribute::Reverse).map_err(io)?;
+
+        // List
+        let alias_width = self
+            .pins
+            .iter()
+            .map(|(alias, _)| alias.width())
+            .max()
+            .unwrap_or(0)
+            .min(width / 3);
+        for row in 0..list_height {
+            let Some(&i) = self.view.get(self.scroll + row) else {
+                break;
+            };
+            let (alias, record) = &self.pins[i];
+            let text = format!(" {}  {}", pad(alias, alias_width), record.path);
+            let attr = if self.scroll + row == self.selected {
+                Attribute::Reverse
+            } else {
+                Attribute::Reset
+            };
+            line(row as u16 + 1, pad(&text, width), attr).map_err(io)?;
+        }
+
+        // Details of the selected pin
+        let top = list_height as u16 + 1;
+        line(top, "─".repeat(width), Attribute::Dim).map_err(io)?;
+        for (row, text) in self.details().into_iter().enumerate() {
+            line(top + 1 + row as u16, text, Attribute::Reset).map_err(io)?;
+        }
+
+        // Status line, with the cursor placed in anything being typed
+        let status_row = height.saturating_sub(1);
+        let (status, cursor) = match &self.mode {
+            Mode::Filter(input) => (format!("/{}", input.text()), Some(1 + input.cursor())),
+            Mode::Edit(field, input) => {
+                let prompt = format!("{}: ", field.name());
+                let cursor = prompt.width() + input.cursor();
+                let status = input.status().map(|s| format!("  {s}")).unwrap_or_default();
+                (prompt + input.text() + &status, Some(cursor))
+            }
+            Mode::ConfirmDelete => match self.current() {
+                Some((alias, _)) => (format!("Delete \"{alias}\"? (y/n)"), None),
+                None => (String::new(), None),
+            },
+            _ if !self.message.is_empty() => (self.message.clone(), None),
+            _ => (
+                "enter jump  / filter  r rename  e path  t tags  n note  d delete  u undo  ? help  q quit"
+                    .to_string(),
+                None,
+            ),
+        };
+        line(status_row, status, Attribute::Reset).map_err(io)?;
+
+        if let Mode::Help = self.mode {
+            self.draw_help(&mut out, width, height).map_err(io)?;
+        }
+
+        // The whole frame goes in one write, so the screen never shows half of one
+        let mut frame = Vec::new();
+        queue!(frame, Clear(ClearType::All)).map_err(io)?;
+        frame.append(&mut out);
+        match cursor {
+            Some(col) => queue!(frame, cursor::MoveTo(col as u16, status_row), cursor::Show),
+            None => queue!(frame, cursor::Hide),
+        }
+        .map_err(io)?;
+        tty.write(String::from_utf8_lossy(&frame).into_owned())
+    }
+
+    // Lines of the detail pane
+    fn details(&self) -> Vec<String> {
+        let Some((alias, record)) = self.current() else {
+            return vec![" No pins to show.".to_string()];
+        };
+
+        let status = match record.resolve() {
+            Ok(path) if Path::new(&path).exists() && record.policy.raw => format!("-> {path}"),
+            Ok(path) if Path::new(&path).exists() => "exists".to_string(),
+            Ok(_) => "missing".to_string(),
+            Err(e) => e.to_string(),
+        };
+        let jumps = match self.history.usage(alias) {
+            usage if usage.count > 0 => format!("{}, last {}", usage.count, fmt_ago(usage.last)),
+            _ => "none".to_string(),
+        };
+
+        let mut flags = Vec::new();
+        if record.locked {
+            flags.push("locked".to_string());
+        }
+        if record.file {
+            flags.push("file".to_string());
+        }
+        if record.session {
+            flags.push("until the shell exits".to_string());
+        }
+        if let Some(time) = record.expires {
+            flags.push(format!("expires {}", fmt_left(time)));
+        }
+        if record.has_hooks() {
+            let trust = if record.trusted {
+                "trusted"
+            } else {
+                "not trusted"
+            };
+            flags.push(format!("hooks ({trust})"));
+        }
+
+        vec![
+            format!(" Alias:  {alias}"),
+            format!(" Path:   {} ({status})", record.path),
+            format!(" Tags:   {}", record.tags.join(", ")),
+            format!(" Note:   {}", record.note),
+            format!(" Jumps:  {jumps}"),
+            format!(" Flags:  {}", flags.join(", ")),
+        ]
+    }
+
+    // A box over the middle of the screen listing the keys
+    fn draw_help(&self, out: &mut Vec<u8>, width: usize, height: u16) -> std::io::Result<()> {
+        let keys_width = HELP.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        let lines: Vec<String> = HELP
+            .iter()
+            .map(|(keys, desc)| format!(" {keys:<keys_width$}  {desc} "))
+            .collect();
+        let inner = lines
+            .iter()
+            .map(|l| l.width())
+            .max()
+            .unwrap_or(0)
+            .min(width.saturating_sub(2));
+        let left = (width.saturating_sub(inner + 2) / 2) as u16;
+        let top = height.saturating_sub(lines.len() as u16 + 2) / 2;
+
+        queue!(
+            out,
+            cursor::MoveTo(left, top),
+            Print(format!("┌{}┐", "─".repeat(inner)))
+        )?;
+        for (row, text) in lines.iter().enumerate() {
+            queue!(
+                out,
+                cursor::MoveTo(left, top + 1 + row as u16),
+                Print(format!("│{}│", pad(text, inner)))
+            )?;
+        }
+        queue!(
+            out,
+            cursor::MoveTo(left, top + 1 + lines.len() as u16),
+            Print(format!("└{}┘", "─".repeat(inner)))
+        )
+    }
+}
+
+// Cut text down to a number of columns, counting wide characters as two
+fn fit(text: &str, width: usize) -> String {
+    take_width(text.chars(), width)
+}
+
+// Cut or pad text to exactly a number of columns
+fn pad(text: &str, width: usize) -> String {
+    let text = fit(text, width);
+    let gap = width.saturating_sub(text.width());
+    text + &" ".repeat(gap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crossterm::event::KeyModifiers;
+    use tempfile::TempDir;
+
+    use crate::path::PathPolicy;
+    use crate::store::TEST_DIR;
+
+    // A store of its own for one test, deleted when it drops
+    struct Sandbox {
+        _dir: TempDir,
+    }
+
+    impl Sandbox {
+        fn new() -> Sandbox {
+            let dir = TempDir::new().unwrap();
+            TEST_DIR.set(Some(dir.path().join(".pin")));
+            Sandbox { _dir: dir }
+        }
+    }
+
+    impl Drop for Sandbox {
+        fn drop(&mut self) {
+            TEST_DIR.set(None);
+        }
+    }
+
+    // Change the store as another shell would while the manager is open
+    fn elsewhere(change: impl FnOnce(&mut Store)) {
+        let mut store = Store::lock().unwrap();
+        change(&mut store);
+        store.save().unwrap();
+    }
+
+    fn pin(path: &str) -> PinAlias {
+        PinAlias::new(path.to_string(), PathPolicy::default())
+    }
+
+    fn press(manager: &mut Manager, keys: &[KeyCode]) {
+        for &code in keys {
+            manager.handle(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    fn typed(text: &str) -> Vec<KeyCode> {
+        text.chars().map(KeyCode::Char).collect()
+    }
+
+    fn stored(alias: &str) -> Option<PinAlias> {
+        Store::init().unwrap().get(alias).cloned()
+    }
+
+    #[test]
+    fn edits_only_change_their_field() {
+        let _sandbox = Sandbox::new();
+        elsewhere(|store| {
+            store.add("api".to_string(), pin("/tmp"));
+        });
+        let mut manager = Manager::load().unwrap();
+
+        elsewhere(|store| {
+            let record = store.get_mut("api").unwrap();
+            record.note = "noted elsewhere".to_string();
+            record.locked = true;
+        });
+        press(&mut manager, &typed("twork"));
+        press(&mut manager, &[KeyCode::Enter]);
+        assert_eq!(manager.message, "Saved.");
+
+        let api = stored("api").unwrap();
+        assert_eq!(api.tags, ["work"]);
+        assert_eq!(api.note, "noted elsewhere");
+        assert!(api.locked);
+
+        // Undo puts back the tags and nothing else
+        press(&mut manager, &[KeyCode::Char('u')]);
+        let api = stored("api").unwrap();
+        assert!(api.tags.is_empty());
+        assert_eq!(api.note, "noted elsewhere");
+    }
+
+    #[test]
+    fn renames_check_the_store_as_it_is_now() {
+        let _sandbox = Sandbox::new();
+        elsewhere(|store| {
+            store.add("api".to_string(), pin("/tmp"));
+        });
+        let mut manager = Manager::load().unwrap();
+
+        elsewhere(|store| {
+            let mut web = pin("/");
+            web.locked = true;
+            store.add("web".to_string(), web);
+        });
+        let mut keys = vec![KeyCode::Char('r'), KeyCode::Home];
+        keys.extend(typed("web"));
+        keys.extend([KeyCode::Delete, KeyCode::Delete, KeyCode::Delete]);
+        keys.push(KeyCode::Enter);
+        press(&mut manager, &keys);
+
+        assert_eq!(
+            manager.message,
+            PinError::Locked("web".to_string()).to_string()
+        );
+        assert_eq!(stored("web").unwrap().path, "/");
+        assert_eq!(stored("api").unwrap().path, "/tmp");
+    }
+
+    #[test]
+    fn undo_never_replaces_a_pin_made_since() {
+        let _sandbox = Sandbox::new();
+        elsewhere(|store| {
+            store.add("api".to_string(), pin("/tmp"));
+        });
+        let mut manager = Manager::load().unwrap();
+
+        press(&mut manager, &typed("dy"));
+        assert!(stored("api").is_none());
+        elsewhere(|store| {
+            store.add("api".to_string(), pin("/"));
+        });
+
+        press(&mut manager, &typed("u"));
+        assert_eq!(manager.message, "Error: \"api\" already exists.");
+        assert_eq!(stored("api").unwrap().path, "/");
+        assert_eq!(manager.undo.len(), 1);
+    }
+
+    #[test]
+    fn jumps_count_more_than_the_recent_history() {
+        let _sandbox = Sandbox::new();
+        elsewhere(|store| {
+            store.add("api".to_string(), pin("/tmp"));
+        });
+        let mut history = History::load().unwrap();
+        for _ in 0..150 {
+            history.record("api", "/tmp");
+        }
+        history.save().unwrap();
+
+        let manager = Manager::load().unwrap();
+        let details = manager.details();
+        assert!(
+            details.iter().any(|line| line.contains("Jumps:  150,")),
+            "{details:?}"
+        );
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        assert_eq!(fit("日本語", 5), "日本");
+        assert_eq!(pad("日本語", 5), "日本 ");
+        assert_eq!(pad("e\u{301}", 3), "e\u{301}  ");
+        assert_eq!(pad("api", 0), "");
+    }
+}
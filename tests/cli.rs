@@ -0,0 +1,1047 @@
+// Every command, run as the shell wrapper runs _pin
+//
+// Each test gets a sandbox of its own, see tests/common. Commands that talk to the user are run on
+// a pseudo terminal, and the rest without one, which also checks they never need one.
+
+mod common;
+
+use common::{BIN, Sandbox};
+
+const NO_TTY: &str = "Error: unable to get tty access: No such device or address (os error 6)\n";
+
+fn not_found(alias: &str) -> String {
+    format!("Error: alias \"{alias}\" not found in store. Type \"pin --help\" for help.\n")
+}
+
+// The exit code protocol, used without PIN_PROTOCOL
+
+#[test]
+fn success_without_output_exits_0() {
+    let s = Sandbox::new();
+    let api = s.mkdir("code/api");
+
+    let run = s.pin(&["--add", "api", &api]);
+    assert_eq!(
+        (run.code, run.stdout.as_str(), run.stderr.as_str()),
+        (0, "", "")
+    );
+}
+
+#[test]
+fn jump_prints_the_path_and_exits_2() {
+    let s = Sandbox::new();
+    let api = s.mkdir("code/api");
+    s.pin(&["--add", "api", &api]);
+
+    for args in [&["api"][..], &["--push", "api"], &["-p", "api"]] {
+        let run = s.pin(args);
+        assert_eq!(run.code, 2, "{args:?}");
+        assert_eq!(run.stdout, format!("{api}\n"));
+        assert_eq!(run.stderr, "");
+    }
+}
+
+#[test]
+fn file_pin_prints_the_path_and_exits_4() {
+    let s = Sandbox::new();
+    let notes = s.touch("notes.md", "# Notes\n");
+    s.pin(&["--add", "notes", &notes]);
+
+    let run = s.pin(&["notes"]);
+    assert_eq!((run.code, run.stdout), (4, format!("{notes}\n")));
+}
+
+#[test]
+fn unknown_alias_exits_11() {
+    let s = Sandbox::new();
+
+    let run = s.pin(&["nope"]);
+    assert_eq!((run.code, run.stdout.as_str()), (11, ""));
+    assert_eq!(run.stderr, not_found("nope"));
+}
+
+#[test]
+fn missing_directory_exits_13() {
+    let s = Sandbox::new();
+    let gone = s.mkdir("gone");
+    s.pin(&["--add", "gone", &gone]);
+    std::fs::remove_dir(&gone).unwrap();
+
+    let run = s.pin(&["gone"]);
+    assert_eq!(run.code, 13);
+    assert_eq!(run.stdout, "");
+    assert!(run.stderr.contains(&gone), "{}", run.stderr);
+}
+
+#[test]
+fn bad_command_lines_exit_10() {
+    let s = Sandbox::new();
+
+    for (args, stderr) in [
+        (
+            &["--bogus"][..],
+            "Error: No function named --bogus, type --help to list all commands.\n",
+        ),
+        (
+            &["--add", "api"],
+            "Error: missing path from command. Please use \"pin --add [alias] [path]\".\n",
+        ),
+        (
+            &["--back", "two"],
+            "Error: \"two\" is not a number of jumps.\n",
+        ),
+        (
+            &["--exec", "api", "--"],
+            "Error: missing command from command. Please use \"pin --exec [alias|@tag] [-j n] -- [cmd...]\".\n",
+        ),
+        (
+            &["--exec", "api", "-j", "0", "--", "pwd"],
+            "Error: invalid number of jobs \"0\". Please use 1 or more.\n",
+        ),
+    ] {
+        let run = s.pin(args);
+        assert_eq!((run.code, run.stderr.as_str()), (10, stderr), "{args:?}");
+        assert_eq!(run.stdout, "");
+    }
+}
+
+// The directive protocol, used by the wrappers from --init
+
+#[test]
+fn protocol_jump_exits_3_with_directives() {
+    let s = Sandbox::new();
+    let api = s.mkdir("code/api");
+    let notes = s.touch("notes.md", "");
+    s.pin(&["--add", "api", &api]);
+    s.pin(&["--add", "notes", &notes]);
+
+    for (args, directive) in [
+        (&["api"][..], format!("cd\t{api}")),
+        (&["--push", "api"], format!("pushd\t{api}")),
+        (&["notes"], format!("open\t{notes}\t")),
+    ] {
+        let run = s.run({
+            let mut cmd = s.command(args);
+            cmd.env("PIN_PROTOCOL", "1");
+            cmd
+        });
+        assert_eq!(run.code, 3, "{args:?}");
+        assert_eq!(run.stdout, format!("pin-directives 1\n{directive}\n"));
+    }
+}
+
+#[test]
+fn protocol_errors_keep_their_codes() {
+    let s = Sandbox::new();
+
+    let mut cmd = s.command(&["nope"]);
+    cmd.env("PIN_PROTOCOL", "1");
+    let run = s.run(cmd);
+    assert_eq!((run.code, run.stdout.as_str()), (11, ""));
+}
+
+#[test]
+fn trusted_hooks_become_directives() {
+    let s = Sandbox::new();
+    let api = s.mkdir("code/api");
+    s.pin(&["--add", "api", &api]);
+
+    let run = s.pin(&["--hook", "api", "add", "echo", "hi"]);
+    assert_eq!(run.code, 0);
+    assert_eq!(
+        run.stderr,
+        "Hooks changed. Run \"pin --trust api\" to allow them to run.\n"
+    );
+    s.pin(&["--hook", "api", "env", "MODE=dev"]);
+
+    let jump = || {
+        let mut cmd = s.command(&["api"]);
+        cmd.env("PIN_PROTOCOL", "1");
+        s.run(cmd)
+    };
+    let run = jump();
+    assert_eq!(run.code, 3);
+    assert!(!run.stdout.contains("run\t"), "{}", run.stdout);
+    assert!(run.stdout.contains("pin --trust api"), "{}", run.stdout);
+
+    let run = s.tty(&["--trust", "api"], &[("Trust these hooks? (y/n)", "y")]);
+    assert_eq!(run.code, 0);
+    assert!(run.tty.contains("echo hi"), "{}", run.tty);
+
+    let run = jump();
+    assert_eq!(
+        run.stdout,
+        format!("pin-directives 1\ncd\t{api}\nsetenv\tMODE\tdev\nrun\techo hi\n")
+    );
+
+    assert_eq!(s.pin(&["--untrust", "api"]).code, 0);
+    assert!(!jump().stdout.contains("run\t"));
+}
+
+#[test]
+fn hooks_keep_their_quoting() {
+    let s = Sandbox::new();
+    let api = s.mkdir("code/api");
+    s.pin(&["--add", "api", &api]);
+
+    s.pin(&["--hook", "api", "add", "echo", "it's", "a  b", r"c\d"]);
+    s.pin(&["--hook", "api", "add", "make && make test"]);
+    let run = s.tty(&["--hook", "api"], &[]);
+    assert_eq!(run.code, 0);
+    assert!(
+        run.tty.contains(r"echo 'it'\''s' 'a  b' 'c'\\'d'"),
+        "{}",
+        run.tty
+    );
+    assert!(run.tty.contains("make && make test"), "{}", run.tty);
+}
+
+// Adding pins
+
+#[test]
+fn add_refuses_paths_that_dont_exist() {
+    let s = Sandbox::new();
+
+    let run = s.pin(&["--add", "bad", "/does/not/exist"]);
+    assert_eq!(run.code, 12);
+    assert_eq!(
+        run.stderr,
+        "Error: unable to canonicalise path \"/does/not/exist\". Check path exists.\n"
+    );
+    assert_eq!(s.pin(&["bad"]).code, 11);
+}
+
+#[test]
+fn add_over_an_alias_asks_first() {
+    let s = Sandbox::new();
+    let (old, new) = (s.mkdir("old"), s.mkdir("new"));
+    s.pin(&["--add", "api", &old]);
+
+    // With nobody to ask, nothing changes
+    let run = s.pin(&["--add", "api", &new]);
+    assert_eq!((run.code, run.stderr.as_str()), (17, NO_TTY));
+
+    let run = s.tty(&["--add", "api", &new], &[("Overwrite it? (y/n)", "n")]);
+    assert_eq!(run.code, 0);
+    assert!(
+        run.tty
+            .contains(&format!("overwrite existing alias to {old}"))
+    );
+    assert_eq!(s.pin(&["api"]).stdout, format!("{old}\n"));
+
+    let run = s.tty(&["--add", "api", &new], &[("Overwrite it? (y/n)", "y")]);
+    assert_eq!(run.code, 0);
+    assert_eq!(s.pin(&["api"]).stdout, format!("{new}\n"));
+}
+
+#[test]
+fn ctrl_c_at_a_prompt_exits_130() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api]);
+
+    let run = s.tty(&["--delete", "api"], &[("(y/n)", "\x03")]);
+    assert_eq!((run.code, run.stderr.as_str()), (130, "Cancelled.\n"));
+    assert_eq!(s.pin(&["api"]).code, 2);
+}
+
+#[test]
+fn here_pins_the_current_directory() {
+    let s = Sandbox::new();
+    let api = s.mkdir("code/api");
+
+    let mut cmd = s.command(&["--here"]);
+    cmd.current_dir(&api);
+    let run = s.run(cmd);
+    assert_eq!((run.code, run.stderr.as_str()), (0, ""));
+    assert_eq!(s.pin(&["api"]).stdout, format!("{api}\n"));
+
+    let mut cmd = s.command(&["--here", "api"]);
+    cmd.current_dir(&api);
+    let run = s.run(cmd);
+    assert_eq!(run.code, 0);
+    assert_eq!(run.stderr, "This directory is already pinned as \"api\".\n");
+}
+
+#[test]
+fn temporary_pins_expire() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+
+    assert_eq!(s.pin(&["--add", "api", &api, "--ttl", "1h"]).code, 0);
+    assert_eq!(s.pin(&["--add", "old", &api, "--ttl", "1s"]).code, 0);
+    assert_eq!(s.pin(&["--add", "here", &api, "--session"]).code, 0);
+    std::thread::sleep(std::time::Duration::from_millis(2100));
+
+    assert_eq!(s.pin(&["api"]).code, 2);
+    assert_eq!(s.pin(&["here"]).code, 2);
+    assert_eq!(s.pin(&["old"]).code, 11);
+
+    let run = s.tty(&["--list"], &[]);
+    assert!(run.tty.contains("| in 59m "), "{}", run.tty);
+    assert!(run.tty.contains("with shell"), "{}", run.tty);
+    assert!(run.tty.contains("Removed expired pins: old"), "{}", run.tty);
+}
+
+#[test]
+fn impossible_expiry_times_exit_10() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+
+    for expiry in [
+        ["--ttl", "18446744073709551615s"],
+        ["--ttl", "2x"],
+        ["--until", "2026-02-31"],
+        ["--until", "2027-02-29 10:00"],
+        ["--until", "2026-13-01"],
+    ] {
+        let run = s.pin(&["--add", "api", &api, expiry[0], expiry[1]]);
+        assert_eq!(run.code, 10, "{expiry:?}: {}", run.stderr);
+    }
+    assert_eq!(s.pin(&["api"]).code, 11);
+    assert_eq!(
+        s.pin(&["--add", "api", &api, "--until", "2028-02-29"]).code,
+        0
+    );
+}
+
+#[test]
+fn invalid_expiry_times_are_quoted() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+
+    let run = s.pin(&["--add", "api", &api, "--ttl", "2x"]);
+    assert_eq!(run.code, 10);
+    assert!(
+        run.stderr.contains("invalid time to live \"2x\""),
+        "{}",
+        run.stderr
+    );
+    assert!(run.stderr.contains("30m, 2h or 1d"), "{}", run.stderr);
+
+    let run = s.pin(&["--add", "api", &api, "--until", "2026-13-01"]);
+    assert_eq!(run.code, 10);
+    assert!(
+        run.stderr.contains("invalid date \"2026-13-01\""),
+        "{}",
+        run.stderr
+    );
+    assert!(run.stderr.contains("YYYY-MM-DD HH:MM"), "{}", run.stderr);
+
+    let run = s.pin(&["--add", "api", &api, "--ttl"]);
+    assert!(
+        run.stderr.contains("missing time to live"),
+        "{}",
+        run.stderr
+    );
+}
+
+// Changing pins
+
+#[test]
+fn delete_asks_first() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api]);
+
+    assert_eq!(s.pin(&["--delete", "api"]).code, 17);
+
+    let run = s.tty(&["--delete", "api"], &[("Delete \"api\"? (y/n)", "n")]);
+    assert_eq!(run.code, 0);
+    assert_eq!(s.pin(&["api"]).code, 2);
+
+    let run = s.tty(&["-d", "api"], &[("Delete \"api\"? (y/n)", "y")]);
+    assert_eq!(run.code, 0);
+    assert_eq!(s.pin(&["api"]).code, 11);
+
+    assert_eq!(s.pin(&["--delete", "api"]).stderr, not_found("api"));
+}
+
+#[test]
+fn locked_pins_need_force() {
+    let s = Sandbox::new();
+    let (api, other) = (s.mkdir("api"), s.mkdir("other"));
+    s.pin(&["--add", "api", &api]);
+    assert_eq!(s.pin(&["--lock", "api"]).code, 0);
+
+    let locked = "Error: \"api\" is locked. Use --force to change it anyway, or \"pin --unlock api\" first.\n";
+    for args in [
+        &["--delete", "api"][..],
+        &["--rename", "api", "backend"],
+        &["--update", "api"],
+    ] {
+        let run = s.pin(args);
+        assert_eq!((run.code, run.stderr.as_str()), (22, locked), "{args:?}");
+    }
+
+    let run = s.tty(&["--add", "api", &other, "--force"], &[("(y/n)", "y")]);
+    assert_eq!(run.code, 0);
+    assert_eq!(s.pin(&["api"]).stdout, format!("{other}\n"));
+
+    assert_eq!(s.pin(&["--unlock", "api"]).code, 0);
+    assert_eq!(s.pin(&["--rename", "api", "backend"]).code, 0);
+}
+
+#[test]
+fn rename_moves_the_pin() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api]);
+
+    let run = s.pin(&["--rename", "api", "backend"]);
+    assert_eq!((run.code, run.stderr.as_str()), (0, ""));
+    assert_eq!(s.pin(&["api"]).code, 11);
+    assert_eq!(s.pin(&["backend"]).stdout, format!("{api}\n"));
+
+    assert_eq!(s.pin(&["--rename", "api", "x"]).code, 11);
+}
+
+#[test]
+fn update_edits_a_pin() {
+    let s = Sandbox::new();
+    let (api, moved) = (s.mkdir("api"), s.mkdir("moved"));
+    s.pin(&["--add", "api", &api]);
+
+    assert_eq!(s.pin(&["--update", "api"]).code, 17);
+    assert_eq!(s.pin(&["--update", "nope"]).code, 11);
+
+    // Pick the path, clear it and type the new one
+    let run = s.tty(
+        &["--update", "api"],
+        &[("note (n)", "p"), (&api, &format!("\x15{moved}\r"))],
+    );
+    assert_eq!(run.code, 0, "{run:?}");
+    assert_eq!(s.pin(&["api"]).stdout, format!("{moved}\n"));
+}
+
+#[test]
+fn retarget_moves_pins_under_a_root() {
+    let s = Sandbox::new();
+    let old = s.mkdir("old/api");
+    let new = s.mkdir("new/api");
+    s.pin(&["--add", "api", &old]);
+
+    let root = |dir: &str| s.home().join(dir).to_str().unwrap().to_string();
+    let run = s.pin(&["--retarget", "/nowhere", &root("new")]);
+    assert_eq!(run.code, 12);
+    assert_eq!(run.stderr, "Error: no pins are under /nowhere.\n");
+
+    let run = s.tty(
+        &["--retarget", &root("old"), &root("new")],
+        &[("(y/n)", "y")],
+    );
+    assert_eq!(run.code, 0, "{run:?}");
+    assert_eq!(s.pin(&["api"]).stdout, format!("{new}\n"));
+}
+
+#[test]
+fn prune_removes_chosen_missing_pins() {
+    let s = Sandbox::new();
+    let (a, b, c) = (s.mkdir("a"), s.mkdir("b"), s.mkdir("c"));
+    for (alias, dir) in [("a", &a), ("b", &b), ("c", &c)] {
+        s.pin(&["--add", alias, dir]);
+    }
+
+    let run = s.tty(&["--prune"], &[]);
+    assert_eq!(run.code, 0);
+    assert!(run.tty.contains("No pins to prune."), "{}", run.tty);
+
+    std::fs::remove_dir(&a).unwrap();
+    std::fs::remove_dir(&b).unwrap();
+    // Untick b, the second missing pin, to keep it
+    let run = s.tty(&["--prune"], &[("Pins to remove", "j \r")]);
+    assert_eq!(run.code, 0, "{run:?}");
+    assert_eq!(s.pin(&["a"]).code, 11);
+    assert_eq!(s.pin(&["b"]).code, 13);
+    assert_eq!(s.pin(&["c"]).code, 2);
+}
+
+#[test]
+fn tags_and_notes() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api, "--note", "The backend"]);
+
+    assert_eq!(s.pin(&["--tag", "api", "work", "rust"]).code, 0);
+    let run = s.tty(&["--list"], &[]);
+    assert!(run.tty.contains("rust, work"), "{}", run.tty);
+    assert!(run.tty.contains("The backend"), "{}", run.tty);
+
+    assert_eq!(s.pin(&["--untag", "api", "rust"]).code, 0);
+    assert_eq!(s.pin(&["--note", "api", "The", "api"]).code, 0);
+    let run = s.tty(&["--note", "api"], &[]);
+    assert_eq!(run.code, 0);
+    assert!(run.tty.contains("The api"), "{}", run.tty);
+
+    let run = s.tty(&["--list"], &[]);
+    assert!(!run.tty.contains("rust"), "{}", run.tty);
+    assert!(run.tty.contains("work"), "{}", run.tty);
+}
+
+// Looking at pins
+
+#[test]
+fn list_and_search_write_to_the_terminal() {
+    let s = Sandbox::new();
+    let (api, web) = (s.mkdir("code/api"), s.mkdir("code/web"));
+    s.pin(&["--add", "api", &api]);
+    s.pin(&["--add", "web", &web, "--note", "frontend"]);
+
+    assert_eq!(s.pin(&["--list"]).code, 17);
+
+    let run = s.tty(&["--list"], &[]);
+    assert_eq!(
+        (run.code, run.stdout.as_str(), run.stderr.as_str()),
+        (0, "", "")
+    );
+    // Paths in $HOME are shown from ~
+    assert!(run.tty.contains("| api   | ~/code/api |"), "{}", run.tty);
+    assert!(run.tty.contains("| web   |"), "{}", run.tty);
+
+    let run = s.tty(&["--list", "web"], &[]);
+    assert!(!run.tty.contains("api"), "{}", run.tty);
+    let run = s.tty(&["--list", &api], &[]);
+    assert!(!run.tty.contains("web"), "{}", run.tty);
+
+    let run = s.tty(&["--search", "FRONT"], &[]);
+    assert_eq!(run.code, 0);
+    assert!(run.tty.contains("| web "), "{}", run.tty);
+    assert!(!run.tty.contains("| api "), "{}", run.tty);
+}
+
+#[test]
+fn list_columns_sort_and_width() {
+    let s = Sandbox::new();
+    let (api, web) = (s.mkdir("code/api"), s.mkdir("code/web"));
+    let deep = s.mkdir(&format!("{}/end", "very-long-directory-name/".repeat(6)));
+    s.pin(&["--add", "api", &api]);
+    s.pin(&["--add", "web", &web]);
+    s.pin(&["--add", "deep", &deep]);
+    s.pin(&["web"]);
+    s.pin(&["web"]);
+    s.pin(&["api"]);
+    std::fs::remove_dir(&api).unwrap();
+
+    let args = [
+        "--list",
+        "--columns",
+        "alias,uses,exists",
+        "--sort",
+        "frecency",
+    ];
+    let run = s.tty(&args, &[]);
+    assert_eq!(run.code, 0);
+    let rows: Vec<&str> = run.tty.lines().filter(|l| l.starts_with("| ")).collect();
+    assert_eq!(
+        rows,
+        [
+            "| Alias | Uses | Exists |",
+            "| web   | 2    | yes    |",
+            "| api   | 1    | no     |",
+            "| deep  | 0    | yes    |",
+        ]
+    );
+
+    let run = s.tty(
+        &["--list", "--columns=alias", "--sort=recent", "--reverse"],
+        &[],
+    );
+    let rows: Vec<&str> = run.tty.lines().filter(|l| l.starts_with("| ")).collect();
+    assert_eq!(rows[1..], ["| deep  |", "| web   |", "| api   |"]);
+
+    // The terminal is 100 columns wide, so the long path loses its middle
+    let run = s.tty(&["--list"], &[]);
+    assert!(
+        run.tty.lines().all(|l| l.chars().count() <= 100),
+        "{}",
+        run.tty
+    );
+    assert!(run.tty.contains("| ~/code/api "), "{}", run.tty);
+    assert!(run.tty.contains("…"), "{}", run.tty);
+    assert!(run.tty.contains("name/end |"), "{}", run.tty);
+
+    for (args, msg) in [
+        (&["--list", "--sort", "size"][..], "unknown sort \"size\""),
+        (
+            &["--list", "--columns", "alias,size"],
+            "unknown column \"size\"",
+        ),
+        (
+            &["--list", "--color=sometimes"],
+            "unknown color \"sometimes\"",
+        ),
+    ] {
+        let run = s.pin(args);
+        assert_eq!(run.code, 10, "{args:?}");
+        assert!(run.stderr.contains(msg), "{}", run.stderr);
+    }
+}
+
+#[test]
+fn list_tree_and_tags() {
+    let s = Sandbox::new();
+    for (alias, dir) in [
+        ("api", "code/work/api"),
+        ("backend", "code/work/api"),
+        ("web", "code/work/web"),
+        ("ca", "code/work/client-a/src"),
+        ("tools", "code/tools"),
+        ("nvim", "dots/nvim"),
+    ] {
+        s.pin(&["--add", alias, &s.mkdir(dir)]);
+    }
+    s.pin(&["--tag", "api", "work"]);
+    s.pin(&["--tag", "ca", "work", "client"]);
+
+    // Directories with a single child are merged, and each branch counts the pins under it
+    let run = s.tty(&["--list", "--tree"], &[]);
+    assert_eq!(run.code, 0);
+    assert_eq!(
+        run.tty,
+        "~ (6)\n\
+         ├── code (5)\n\
+         │   ├── tools: tools\n\
+         │   └── work (4)\n\
+         │       ├── api: api, backend\n\
+         │       ├── client-a/src: ca\n\
+         │       └── web: web\n\
+         └── dots/nvim: nvim\n"
+    );
+
+    let run = s.tty(&["--list", "--group-by", "tag"], &[]);
+    assert_eq!(run.code, 0);
+    assert_eq!(
+        run.tty,
+        "client (1)\n\
+         └── ca  ~/code/work/client-a/src\n\
+         work (2)\n\
+         ├── api  ~/code/work/api\n\
+         └── ca   ~/code/work/client-a/src\n\
+         (untagged) (4)\n\
+         ├── backend  ~/code/work/api\n\
+         ├── nvim     ~/dots/nvim\n\
+         ├── tools    ~/code/tools\n\
+         └── web      ~/code/work/web\n"
+    );
+
+    let run = s.tty(&["--list", "work", "--tree"], &[]);
+    assert!(run.tty.starts_with("~/code/work (4)\n"), "{}", run.tty);
+
+    let run = s.pin(&["--list", "--tree", "--columns", "alias"]);
+    assert_eq!(run.code, 10);
+    assert_eq!(s.pin(&["--list", "--group-by", "size"]).code, 10);
+}
+
+#[test]
+fn history_and_going_back() {
+    let s = Sandbox::new();
+    let (a, b) = (s.mkdir("a"), s.mkdir("b"));
+    s.pin(&["--add", "a", &a]);
+    s.pin(&["--add", "b", &b]);
+
+    let run = s.pin(&["-"]);
+    assert_eq!(run.code, 10);
+    assert_eq!(run.stderr, "Error: there is no previous pin.\n");
+
+    s.pin(&["a"]);
+    s.pin(&["b"]);
+    assert_eq!(s.pin(&["-"]).stdout, format!("{a}\n"));
+    assert_eq!(s.pin(&["--back"]).stdout, format!("{b}\n"));
+    assert_eq!(s.pin(&["--forward"]).stdout, format!("{a}\n"));
+    assert_eq!(s.pin(&["--back", "9"]).code, 10);
+
+    let run = s.tty(&["--history"], &[]);
+    assert_eq!(run.code, 0);
+    assert!(run.tty.contains("| Alias |"), "{}", run.tty);
+    assert!(run.tty.contains(&b), "{}", run.tty);
+}
+
+#[test]
+fn jumps_at_the_same_time_are_all_recorded() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api]);
+
+    let jumps: Vec<_> = (0..20)
+        .map(|_| {
+            let mut cmd = s.command(&["api"]);
+            cmd.stdout(std::process::Stdio::piped());
+            cmd.spawn().unwrap()
+        })
+        .collect();
+    for jump in jumps {
+        let output = jump.wait_with_output().unwrap();
+        assert_eq!(output.status.code(), Some(2), "{output:?}");
+        assert!(output.stderr.is_empty(), "{output:?}");
+    }
+
+    let run = s.tty(&["--list", "--columns", "alias,uses"], &[]);
+    assert_eq!(run.code, 0);
+    assert!(
+        run.tty
+            .lines()
+            .any(|line| line.contains("api") && line.contains("20")),
+        "{}",
+        run.tty
+    );
+}
+
+#[test]
+fn doctor_reports_each_pin() {
+    let s = Sandbox::new();
+    let (api, gone) = (s.mkdir("api"), s.mkdir("gone"));
+    s.pin(&["--add", "api", &api]);
+    s.pin(&["--add", "gone", &gone]);
+    std::fs::remove_dir(&gone).unwrap();
+
+    let run = s.tty(&["--doctor"], &[]);
+    assert_eq!(run.code, 0);
+    let status = |alias: &str| {
+        let row = run
+            .tty
+            .lines()
+            .find(|line| line.starts_with(&format!("| {alias} ")));
+        row.and_then(|row| row.split('|').nth(3)).map(str::trim)
+    };
+    assert_eq!(status("api"), Some("ok"), "{}", run.tty);
+    assert_eq!(status("gone"), Some("missing"), "{}", run.tty);
+}
+
+#[test]
+fn jumps_only_search_for_moved_pins_within_the_budget() {
+    let s = Sandbox::new();
+    let api = s.mkdir("code/api");
+    s.mkdir("code/api/.git");
+    s.touch(
+        "code/api/.git/config",
+        "[remote \"origin\"]\n\turl = git@example.com:me/api.git\n",
+    );
+    s.pin(&["--add", "api", &api]);
+    let moved = format!("{}/api", s.mkdir("work"));
+    std::fs::rename(&api, &moved).unwrap();
+
+    let config = s.store().join("config");
+    std::fs::write(&config, "auto_heal = true\nsearch_budget = 0\n").unwrap();
+    assert_eq!(s.pin(&["api"]).code, 13);
+
+    // The doctor has no budget
+    let run = s.tty(&["--doctor", "--fix"], &[]);
+    assert!(
+        run.tty.contains(&format!("moved to {moved}")),
+        "{}",
+        run.tty
+    );
+    assert_eq!(s.pin(&["api"]).stdout, format!("{moved}\n"));
+
+    // And within the default budget a jump finds it
+    std::fs::rename(&moved, &api).unwrap();
+    std::fs::write(&config, "auto_heal = true\n").unwrap();
+    assert_eq!(s.pin(&["api"]).stdout, format!("{api}\n"));
+}
+
+#[test]
+fn a_match_on_inode_alone_is_always_asked_about() {
+    let s = Sandbox::new();
+    let api = s.mkdir("code/api");
+    s.pin(&["--add", "api", &api]);
+    let moved = format!("{}/api", s.mkdir("work"));
+    std::fs::rename(&api, &moved).unwrap();
+    std::fs::write(s.store().join("config"), "auto_heal = true\n").unwrap();
+
+    // Without a terminal to ask on, the jump leaves the pin alone
+    assert_eq!(s.pin(&["api"]).code, 13);
+
+    let run = s.tty(&["api"], &[("Update the pin?", "n")]);
+    assert!(run.tty.contains("it may have moved to"), "{}", run.tty);
+    assert_eq!(run.code, 13);
+
+    let run = s.tty(
+        &["--doctor", "--fix"],
+        &[("matched only by inode? (y/n)", "y")],
+    );
+    assert!(
+        run.tty.contains(&format!("maybe moved to {moved}")),
+        "{}",
+        run.tty
+    );
+    assert_eq!(s.pin(&["api"]).stdout, format!("{moved}\n"));
+}
+
+#[test]
+fn help_lists_every_command() {
+    let s = Sandbox::new();
+
+    let run = s.tty(&["--help"], &[]);
+    assert_eq!(run.code, 0);
+    for name in [
+        "pin",
+        "add",
+        "here",
+        "delete",
+        "help",
+        "list",
+        "update",
+        "tui",
+        "rename",
+        "prune",
+        "retarget",
+        "lock",
+        "-",
+        "back",
+        "history",
+        "hook",
+        "trust",
+        "tag",
+        "note",
+        "search",
+        "exec",
+        "doctor",
+        "export",
+        "import",
+        "migrate-store",
+        "init",
+    ] {
+        assert!(
+            run.tty.contains(&format!("| {name} ")),
+            "{name}:\n{}",
+            run.tty
+        );
+    }
+
+    // A command can be named by its flags too
+    for name in ["add", "--add", "-a"] {
+        let run = s.tty(&["--help", name], &[]);
+        assert_eq!(run.code, 0);
+        assert!(run.tty.starts_with("add (--add, -a):\n"), "{}", run.tty);
+        assert!(run.tty.contains("--allow-missing"), "{}", run.tty);
+        assert!(run.tty.contains("Examples:\n  pin --add"), "{}", run.tty);
+        assert!(run.tty.contains("  22  The alias is locked"), "{}", run.tty);
+    }
+    let run = s.tty(&["--help", "--forward"], &[]);
+    assert!(run.tty.starts_with("back (--back, -b, --forward):\n"));
+
+    assert_eq!(s.pin(&["--help", "nope"]).code, 10);
+}
+
+#[test]
+fn help_all_and_generated_docs() {
+    let s = Sandbox::new();
+
+    let run = s.tty(&["--help", "--all"], &[]);
+    assert_eq!(run.code, 0);
+    for name in [
+        "pin (--push, -p):",
+        "-:",
+        "add (--add, -a):",
+        "migrate-store (--migrate-store):",
+    ] {
+        assert!(run.tty.contains(name), "{name}:\n{}", run.tty);
+    }
+    assert!(run.tty.contains(" 130  Cancelled with Ctrl-C"));
+
+    // The man page and reference are printed straight to stdout, without a tty
+    let run = s.pin(&["--help", "--man"]);
+    assert_eq!(run.code, 0);
+    assert!(run.stdout.starts_with(".TH PIN 1"), "{}", run.stdout);
+    assert!(run.stdout.contains(".SS migrate\\-store\n"));
+    assert!(run.stdout.contains(".SH EXIT STATUS\n"));
+
+    let run = s.pin(&["--help", "--markdown"]);
+    assert_eq!(run.code, 0);
+    assert!(run.stdout.starts_with("# pin command reference\n"));
+    assert!(run.stdout.contains("\n## add\n"));
+}
+
+#[test]
+fn tui_quits_on_q() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api]);
+
+    assert_eq!(s.pin(&["--tui"]).code, 17);
+    let run = s.tty(&["--tui"], &[("api", "q")]);
+    assert_eq!((run.code, run.stdout.as_str()), (0, ""));
+}
+
+// Running commands in pins
+
+#[test]
+fn exec_runs_in_the_pin_and_keeps_its_exit_code() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api]);
+
+    let run = s.pin(&["--exec", "api", "--", "pwd"]);
+    assert_eq!((run.code, run.stdout), (0, format!("{api}\n")));
+
+    let run = s.pin(&["api", "--", "sh", "-c", "exit 5"]);
+    assert_eq!(run.code, 5);
+
+    let run = s.pin(&["--exec", "nope", "--", "pwd"]);
+    assert_eq!((run.code, run.stderr), (11, not_found("nope")));
+
+    let run = s.pin(&["--exec", "api", "--", "/does/not/exist"]);
+    assert_eq!(run.code, 21);
+}
+
+#[test]
+fn exec_across_a_tag() {
+    let s = Sandbox::new();
+    let (a, b) = (s.mkdir("a"), s.mkdir("b"));
+    s.pin(&["--add", "a", &a]);
+    s.pin(&["--add", "b", &b]);
+    s.pin(&["--tag", "a", "work"]);
+    s.pin(&["--tag", "b", "work"]);
+
+    let run = s.pin(&["--exec", "@work", "--", "pwd"]);
+    assert_eq!(run.code, 0, "{run:?}");
+    assert!(run.stdout.contains(&a), "{}", run.stdout);
+    assert!(run.stdout.contains(&b), "{}", run.stdout);
+
+    let run = s.pin(&["--exec", "@none", "--", "pwd"]);
+    assert_eq!(run.code, 10);
+    assert_eq!(run.stderr, "Error: no usable pins are tagged \"none\".\n");
+}
+
+#[test]
+fn exec_prefixes_line_up_for_wide_aliases() {
+    let s = Sandbox::new();
+    let (a, b) = (s.mkdir("a"), s.mkdir("b"));
+    s.pin(&["--add", "a", &a]);
+    s.pin(&["--add", "日本", &b]);
+    s.pin(&["--tag", "a", "work"]);
+    s.pin(&["--tag", "日本", "work"]);
+
+    let run = s.pin(&["--exec", "@work", "-j", "2", "--", "echo", "hi"]);
+    assert_eq!(run.code, 0, "{run:?}");
+    let mut lines: Vec<&str> = run.stdout.lines().collect();
+    lines.sort();
+    assert_eq!(lines, ["[   a] hi", "[日本] hi"]);
+}
+
+// Moving pins between stores
+
+#[test]
+fn export_and_import() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api, "--note", "The backend"]);
+    s.pin(&["--tag", "api", "work"]);
+
+    let file = s.home().join("pins.txt");
+    let file = file.to_str().unwrap();
+    let run = s.pin(&["--export", file]);
+    assert_eq!(
+        (run.code, run.stderr),
+        (0, format!("Exported 1 pin(s) to {file}.\n"))
+    );
+
+    let other = Sandbox::new();
+    let run = other.pin(&["--import", file]);
+    assert_eq!(run.code, 0);
+    assert_eq!(run.stderr, "Added 1 pin(s) and replaced 0.\n");
+    assert_eq!(other.pin(&["api"]).stdout, format!("{api}\n"));
+
+    let run = other.pin(&["--import", file]);
+    assert_eq!(
+        run.stderr,
+        "Added 0 pin(s) and replaced 0.\nAlready pinned, so kept: api. Use --replace to overwrite them.\n"
+    );
+    let run = other.pin(&["--import", file, "--replace"]);
+    assert_eq!(run.stderr, "Added 0 pin(s) and replaced 1.\n");
+
+    let bad = other.touch("bad.txt", "not an export\n");
+    assert_eq!(other.pin(&["--import", &bad]).code, 23);
+}
+
+#[test]
+fn migrate_store_between_backends() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api]);
+
+    let mut from = "store.bin";
+    for (backend, file) in [
+        ("toml", "store.toml"),
+        ("json", "store.json"),
+        ("sqlite", "store.db"),
+        ("bincode", "store.bin"),
+    ]
+    .into_iter()
+    .filter(|(backend, _)| *backend != "sqlite" || cfg!(feature = "sqlite"))
+    {
+        let run = s.pin(&["--migrate-store", "--to", backend]);
+        let store = s.store();
+        assert_eq!(run.code, 0, "{run:?}");
+        assert_eq!(
+            run.stderr,
+            format!(
+                "Moved 1 pin(s) to {}. The old store is still at {}.\n",
+                store.join(file).display(),
+                store.join(from).display()
+            )
+        );
+        assert_eq!(s.pin(&["api"]).stdout, format!("{api}\n"), "{backend}");
+        from = file;
+    }
+
+    let run = s.pin(&["--migrate-store", "--to", "bincode"]);
+    assert_eq!(
+        (run.code, run.stderr.as_str()),
+        (0, "Pins are already stored with bincode.\n")
+    );
+    assert_eq!(s.pin(&["--migrate-store", "--to", "csv"]).code, 10);
+}
+
+#[test]
+fn pin_store_replaces_the_home_directory() {
+    let s = Sandbox::new();
+    let api = s.mkdir("api");
+    s.pin(&["--add", "api", &api]);
+
+    assert!(s.store().join("store.bin").exists());
+    assert!(!s.home().join(".pin").exists());
+}
+
+#[test]
+fn a_truncated_store_is_a_decode_error() {
+    let s = Sandbox::new();
+    for alias in ["api", "web", "www"] {
+        let dir = s.mkdir(alias);
+        s.pin(&["--add", alias, &dir]);
+    }
+
+    let store = s.store().join("store.bin");
+    let bytes = std::fs::read(&store).unwrap();
+    std::fs::write(&store, &bytes[..bytes.len() - 8]).unwrap();
+    assert_eq!(s.pin(&["www"]).code, 16);
+    std::fs::write(&store, &bytes[..20]).unwrap();
+    assert_eq!(s.pin(&["api"]).code, 16);
+}
+
+// Setting up shells
+
+#[test]
+fn init_prints_a_wrapper_for_each_shell() {
+    let s = Sandbox::new();
+
+    for shell in ["bash", "zsh", "fish"] {
+        let run = s.pin(&["--init", shell]);
+        assert_eq!(run.code, 0, "{shell}");
+        assert!(
+            run.stdout.starts_with(&format!(
+                "# pin wrapper for {shell}, generated by `_pin --init {shell}`\n"
+            )),
+            "{}",
+            run.stdout
+        );
+        assert!(run.stdout.contains(BIN), "{shell}");
+    }
+
+    let run = s.pin(&["--init", "nosh"]);
+    assert_eq!(run.code, 10);
+    assert_eq!(
+        run.stderr,
+        "Error: unknown shell \"nosh\". Supported shells are bash, zsh, fish.\n"
+    );
+}
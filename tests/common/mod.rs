@@ -0,0 +1,270 @@
+// Runs the _pin binary against a store of its own
+//
+// Every Sandbox has a temporary HOME with the store in $PIN_STORE inside it, so the tests never
+// touch the real ~/.pin and can run side by side. Commands are run in a session of their own with
+// no controlling terminal, so anything that would prompt fails instead of waiting on whoever ran
+// the tests. Sandbox::tty runs a command on a pseudo terminal instead, typing keys once the
+// expected text has been written to it.
+
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+pub const BIN: &str = env!("CARGO_BIN_EXE__pin");
+
+// How long to wait for a prompt before giving up
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Sandbox {
+    dir: TempDir,
+}
+
+// What a run of _pin left behind
+#[derive(Debug)]
+pub struct Run {
+    pub code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    // What was written to the terminal, without escape sequences
+    pub tty: String,
+}
+
+impl Sandbox {
+    pub fn new() -> Sandbox {
+        let sandbox = Sandbox {
+            dir: tempfile::tempdir().unwrap(),
+        };
+        std::fs::create_dir(sandbox.home()).unwrap();
+        sandbox
+    }
+
+    pub fn home(&self) -> PathBuf {
+        self.dir.path().join("home")
+    }
+
+    pub fn store(&self) -> PathBuf {
+        self.dir.path().join("store")
+    }
+
+    // Make a directory in the home directory, returning its path
+    pub fn mkdir(&self, name: &str) -> String {
+        let path = self.home().join(name);
+        std::fs::create_dir_all(&path).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    // Write a file in the home directory, returning its path
+    pub fn touch(&self, name: &str, text: &str) -> String {
+        let path = self.home().join(name);
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    // _pin with the sandbox's HOME and store, run from the home directory
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(BIN);
+        cmd.args(args)
+            .current_dir(self.home())
+            .env("HOME", self.home())
+            .env("PIN_STORE", self.store())
+            .env("PIN_SESSION", std::process::id().to_string())
+            .env_remove("PIN_PROTOCOL")
+            .env_remove("VISUAL")
+            .env_remove("EDITOR")
+            .stdin(Stdio::null());
+        cmd
+    }
+
+    // Run _pin without a terminal
+    pub fn pin(&self, args: &[&str]) -> Run {
+        self.run(self.command(args))
+    }
+
+    pub fn run(&self, mut cmd: Command) -> Run {
+        // SAFETY: setsid is async signal safe
+        unsafe {
+            cmd.pre_exec(|| {
+                libc::setsid();
+                Ok(())
+            });
+        }
+        let output = cmd.output().unwrap();
+
+        Run {
+            code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8(output.stdout).unwrap(),
+            stderr: String::from_utf8(output.stderr).unwrap(),
+            tty: String::new(),
+        }
+    }
+
+    // Run _pin on a terminal. For each step, once the text has been written the keys are typed
+    pub fn tty(&self, args: &[&str], steps: &[(&str, &str)]) -> Run {
+        let (master, slave) = open_pty();
+        let mut cmd = self.command(args);
+        let slave_fd = slave.as_raw_fd();
+        // SAFETY: setsid and ioctl are async signal safe
+        unsafe {
+            cmd.pre_exec(move || {
+                libc::setsid();
+                if libc::ioctl(slave_fd, libc::TIOCSCTTY, 0) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // The terminal is kept open here until the child has exited, as closing every end of it
+        // can lose what was written but not yet read
+        let mut master = File::from(master);
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let mut master = master.try_clone().unwrap();
+            let (written, done) = (written.clone(), done.clone());
+            std::thread::spawn(move || {
+                let mut buf = [0; 4096];
+                loop {
+                    let mut poll = libc::pollfd {
+                        fd: master.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    };
+                    // SAFETY: one pollfd, for a descriptor that stays open while this runs
+                    let ready = unsafe { libc::poll(&mut poll, 1, 50) } > 0;
+                    if ready {
+                        match master.read(&mut buf) {
+                            Ok(n @ 1..) => written.lock().unwrap().extend_from_slice(&buf[..n]),
+                            _ => return,
+                        }
+                    } else if done.load(SeqCst) {
+                        return;
+                    }
+                }
+            })
+        };
+
+        let mut seen = 0;
+        for (wait, keys) in steps {
+            let start = Instant::now();
+            loop {
+                let text = strip(&String::from_utf8_lossy(&written.lock().unwrap()));
+                if let Some(at) = text[seen..].find(wait) {
+                    seen += at + wait.len();
+                    break;
+                }
+                assert!(
+                    start.elapsed() < TIMEOUT,
+                    "never saw {wait:?} on the terminal, only:\n{text}"
+                );
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            master.write_all(keys.as_bytes()).unwrap();
+        }
+
+        let output = child.wait_with_output().unwrap();
+        done.store(true, SeqCst);
+        reader.join().unwrap();
+        drop(slave);
+        let tty = strip(&String::from_utf8_lossy(&written.lock().unwrap()));
+
+        Run {
+            code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8(output.stdout).unwrap(),
+            stderr: String::from_utf8(output.stderr).unwrap(),
+            tty,
+        }
+    }
+
+    // Run a script in a shell with the sandbox's HOME and store, and _pin at $PIN_BIN. A missing
+    // shell fails the test, so a wrapper is never reported as working when it wasn't run
+    pub fn shell(&self, shell: &str, args: &[&str], script: &str) -> Run {
+        let found = Command::new(shell)
+            .args(args)
+            .arg("-c")
+            .arg("exit 0")
+            .stdin(Stdio::null())
+            .output()
+            .is_ok();
+        assert!(
+            found,
+            "{shell} isn't installed, so its wrapper can't be tested"
+        );
+
+        let mut cmd = Command::new(shell);
+        cmd.args(args)
+            .arg("-c")
+            .arg(script)
+            .current_dir(self.home())
+            .env("HOME", self.home())
+            .env("PIN_STORE", self.store())
+            .env("PIN_BIN", BIN)
+            .env_remove("PIN_PROTOCOL")
+            .env_remove("PIN_SESSION")
+            .stdin(Stdio::null());
+        self.run(cmd)
+    }
+}
+
+fn open_pty() -> (OwnedFd, OwnedFd) {
+    let (mut master, mut slave) = (0, 0);
+    let size = libc::winsize {
+        ws_row: 24,
+        ws_col: 100,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: openpty fills in two new file descriptors, which are owned from here on
+    unsafe {
+        assert_eq!(
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                &size
+            ),
+            0
+        );
+        for fd in [master, slave] {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+        (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave))
+    }
+}
+
+// Drop escape sequences and carriage returns so what was written can be compared as text
+pub fn strip(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1B' => {
+                if chars.next() == Some('[') {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
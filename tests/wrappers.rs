@@ -0,0 +1,133 @@
+// The shell wrappers from `_pin --init`, checked by running them
+//
+// Each shell sources its wrapper and then jumps around, printing where it ends up. Only bash is
+// run by default. The zsh and fish tests are ignored, as those shells often aren't installed, and
+// are run with `cargo test -- --include-ignored` where they are. Run that way, a missing shell
+// fails rather than passing without testing anything.
+
+mod common;
+
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+
+use common::Sandbox;
+
+// Pins for the scripts to jump between: api and web, a trusted hook on web and a missing pin
+fn sandbox() -> (Sandbox, String, String) {
+    let s = Sandbox::new();
+    let (api, web) = (s.mkdir("code/api"), s.mkdir("code/web"));
+    s.pin(&["--add", "api", &api]);
+    s.pin(&["--add", "web", &web]);
+    s.pin(&["--hook", "web", "env", "MODE=dev"]);
+    s.pin(&["--hook", "web", "add", "echo", "entered web"]);
+    let run = s.tty(&["--trust", "web"], &[("(y/n)", "y")]);
+    assert_eq!(run.code, 0, "{run:?}");
+
+    let gone = s.mkdir("gone");
+    s.pin(&["--add", "gone", &gone]);
+    std::fs::remove_dir(&gone).unwrap();
+
+    (s, api, web)
+}
+
+// The same script works in bash and zsh
+const POSIX: &str = r#"
+eval "$("$PIN_BIN" --init $PIN_SHELL)"
+pin api && pwd
+pin web && pwd && echo "MODE=$MODE"
+pin --push api && pwd && dirs -l -p | tail -n 1
+pin nope; echo "code=$?"
+pin gone; echo "code=$?"
+pin - && pwd
+pin web -- pwd
+"#;
+
+fn check(run: common::Run, api: &str, web: &str) {
+    assert_eq!(run.code, 0, "{run:?}");
+    let expected = [
+        api.to_string(),
+        "entered web".to_string(),
+        web.to_string(),
+        "MODE=dev".to_string(),
+        api.to_string(),
+        web.to_string(),
+        "code=11".to_string(),
+        "code=13".to_string(),
+        "entered web".to_string(),
+        web.to_string(),
+        web.to_string(),
+    ];
+    let lines: Vec<&str> = run.stdout.lines().collect();
+    assert_eq!(lines, expected, "{run:?}");
+    assert!(run.stderr.contains("alias \"nope\" not found"), "{run:?}");
+}
+
+#[test]
+fn bash_wrapper() {
+    let (s, api, web) = sandbox();
+    let script = format!("PIN_SHELL=bash\n{POSIX}");
+
+    let run = s.shell("bash", &["--norc", "--noprofile"], &script);
+    check(run, &api, &web);
+}
+
+#[test]
+#[ignore = "needs zsh installed"]
+fn zsh_wrapper() {
+    let (s, api, web) = sandbox();
+    let script = format!("PIN_SHELL=zsh\n{POSIX}");
+
+    let run = s.shell("zsh", &["-f"], &script);
+    check(run, &api, &web);
+}
+
+// A file pin opened with an editor that is a program on PATH, which the wrapper mustn't lose
+const OPEN: &str = r#"
+eval "$("$PIN_BIN" --init $PIN_SHELL)"
+PATH="$HOME/bin:$PATH"
+EDITOR=edit
+pin notes; echo "code=$?"
+"#;
+
+fn open_file(shell: &str, args: &[&str]) {
+    let s = Sandbox::new();
+    let notes = s.touch("notes.md", "# Notes\n");
+    s.pin(&["--add", "notes", &notes]);
+
+    s.mkdir("bin");
+    let edit = s.touch("bin/edit", "#!/bin/sh\necho \"edited $1\"\n");
+    std::fs::set_permissions(&edit, Permissions::from_mode(0o755)).unwrap();
+
+    let run = s.shell(shell, args, &format!("PIN_SHELL={shell}\n{OPEN}"));
+    assert_eq!(run.stdout, format!("edited {notes}\ncode=0\n"), "{run:?}");
+}
+
+#[test]
+fn bash_wrapper_opens_files() {
+    open_file("bash", &["--norc", "--noprofile"]);
+}
+
+#[test]
+#[ignore = "needs zsh installed"]
+fn zsh_wrapper_opens_files() {
+    open_file("zsh", &["-f"]);
+}
+
+#[test]
+#[ignore = "needs fish installed"]
+fn fish_wrapper() {
+    let (s, api, web) = sandbox();
+    let script = r#"
+"$PIN_BIN" --init fish | source
+pin api; and pwd
+pin web; and pwd; and echo "MODE=$MODE"
+pin --push api; and pwd; and echo $dirstack[-1]
+pin nope; echo "code=$status"
+pin gone; echo "code=$status"
+pin -; and pwd
+pin web -- pwd
+"#;
+
+    let run = s.shell("fish", &["--no-config"], script);
+    check(run, &api, &web);
+}